@@ -1,14 +1,26 @@
 use anyhow::{anyhow, Result};
 use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
 use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::Stream;
 
 // Audio device enumeration will be implemented using Core Audio APIs
 // For now, we use a simplified approach with hardcoded devices
 
+/// Core Audio can report no input devices for a moment after launch; tries
+/// before settling for that, with the pause between them doubling from
+/// `ENUMERATION_RETRY_DELAY`
+const ENUMERATION_ATTEMPTS: u32 = 4;
+const ENUMERATION_RETRY_DELAY: Duration = Duration::from_millis(100);
+
 /// Represents an audio input device
 #[derive(Clone, Debug, PartialEq)]
 pub struct AudioDevice {
@@ -17,6 +29,15 @@ pub struct AudioDevice {
     pub is_default: bool,
 }
 
+/// Virtual devices that pass the Mac's output back in as an input, which is
+/// how a recording gets system audio
+const LOOPBACK_DEVICE_NAMES: [&str; 4] = ["BlackHole", "Loopback", "Soundflower", "Background Music"];
+
+/// The first loopback device among `devices`, for recording system audio
+pub fn system_audio_device(devices: &[AudioDevice]) -> Option<&AudioDevice> {
+    devices.iter().find(|device| LOOPBACK_DEVICE_NAMES.iter().any(|name| device.name.contains(name)))
+}
+
 /// Audio level monitoring for a device
 pub struct AudioLevelMonitor {
     pub device_id: String,
@@ -46,86 +67,23 @@ impl AudioLevelMonitor {
 
         self.is_monitoring.store(true, Ordering::Relaxed);
         
-        // Get the default audio host
-        let host = cpal::default_host();
-        
-        // Find the specific device by index or name match
-        let device = if let Ok(index) = self.device_id.parse::<usize>() {
-            // Use index-based lookup
-            host.input_devices()
-                .map_err(|e| anyhow!("Failed to enumerate input devices: {}", e))?
-                .nth(index)
-                .or_else(|| host.default_input_device())
-        } else {
-            // Fallback to name-based lookup for legacy compatibility
-            host.input_devices()
-                .map_err(|e| anyhow!("Failed to enumerate input devices: {}", e))?
-                .find(|d| d.name().map(|name| name == self.device_id).unwrap_or(false))
-                .or_else(|| host.default_input_device())
-        }
-        .ok_or_else(|| anyhow!("No input device available"))?;
-        
-        // Get the default input config
-        let config = device.default_input_config()
-            .map_err(|e| anyhow!("Failed to get default input config: {}", e))?;
+        // A meter showing the default input beats no meter while the picked one is unplugged
+        let device = find_input_device(&self.device_id)
+            .or_else(|e| cpal::default_host().default_input_device().ok_or(e))?;
+        let config = input_config(&device)?;
+        let stream_config = config.config();
         
         let level = self.level.clone();
         let is_monitoring = self.is_monitoring.clone();
         
         // Create audio stream
         let stream = match config.sample_format() {
-            cpal::SampleFormat::F32 => {
-                device.build_input_stream(
-                    &config.into(),
-                    move |data: &[f32], _: &cpal::InputCallbackInfo| {
-                        if is_monitoring.load(Ordering::Relaxed) {
-                            let rms = calculate_rms(data);
-                            if let Ok(mut level_guard) = level.lock() {
-                                *level_guard = rms;
-                            }
-                        }
-                    },
-                    move |err| {
-                        eprintln!("Audio stream error: {}", err);
-                    },
-                    None,
-                )?
-            },
-            cpal::SampleFormat::I16 => {
-                device.build_input_stream(
-                    &config.into(),
-                    move |data: &[i16], _: &cpal::InputCallbackInfo| {
-                        if is_monitoring.load(Ordering::Relaxed) {
-                            let rms = calculate_rms_i16(data);
-                            if let Ok(mut level_guard) = level.lock() {
-                                *level_guard = rms;
-                            }
-                        }
-                    },
-                    move |err| {
-                        eprintln!("Audio stream error: {}", err);
-                    },
-                    None,
-                )?
-            },
-            cpal::SampleFormat::U16 => {
-                device.build_input_stream(
-                    &config.into(),
-                    move |data: &[u16], _: &cpal::InputCallbackInfo| {
-                        if is_monitoring.load(Ordering::Relaxed) {
-                            let rms = calculate_rms_u16(data);
-                            if let Ok(mut level_guard) = level.lock() {
-                                *level_guard = rms;
-                            }
-                        }
-                    },
-                    move |err| {
-                        eprintln!("Audio stream error: {}", err);
-                    },
-                    None,
-                )?
-            },
-            _ => return Err(anyhow!("Unsupported sample format")),
+            cpal::SampleFormat::F32 => build_level_stream(&device, &stream_config, level, is_monitoring, calculate_rms)?,
+            cpal::SampleFormat::I16 => build_level_stream(&device, &stream_config, level, is_monitoring, calculate_rms_i16)?,
+            cpal::SampleFormat::U16 => build_level_stream(&device, &stream_config, level, is_monitoring, calculate_rms_u16)?,
+            cpal::SampleFormat::I32 => build_level_stream(&device, &stream_config, level, is_monitoring, calculate_rms_i32)?,
+            cpal::SampleFormat::U8 => build_level_stream(&device, &stream_config, level, is_monitoring, calculate_rms_u8)?,
+            format => return Err(anyhow!("Unsupported sample format: {:?}", format)),
         };
         
         // Start the stream
@@ -145,25 +103,376 @@ impl AudioLevelMonitor {
     }
 }
 
-// Helper functions to calculate RMS (Root Mean Square) for different sample formats
+/// Tracks how long an audio level has stayed below a threshold
+#[derive(Default)]
+pub struct SilenceTimer {
+    quiet_since: Option<Instant>,
+}
+
+impl SilenceTimer {
+    /// Feed the current level; returns how long it has been below `threshold`
+    pub fn update(&mut self, level: f32, threshold: f32) -> Duration {
+        if level >= threshold {
+            self.quiet_since = None;
+            return Duration::ZERO;
+        }
+        self.quiet_since.get_or_insert_with(Instant::now).elapsed()
+    }
+}
+
+/// Interleaved s16le audio as `PcmCapture` writes it, at the device's own rate
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PcmFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+/// Records an input device through cpal and writes it as s16le into a named
+/// pipe that ffmpeg reads (`-f s16le -i <pipe>`), so recordings pick devices
+/// the same way the level meter does. Audio starts when ffmpeg opens the pipe,
+/// in step with the first video frame. Stops when dropped.
+pub struct PcmCapture {
+    format: PcmFormat,
+    pipe: Arc<Mutex<PathBuf>>, // Where samples go; the writer follows changes
+    pipes_made: AtomicU32,
+    name: String, // Distinguishes this capture's pipes in the temp directory
+    stop: Arc<AtomicBool>,
+}
+
+impl PcmCapture {
+    /// Start capturing `device_id` into a new pipe. The cpal stream lives on
+    /// its own thread, which also writes to the pipe.
+    pub fn start(device_id: &str, name: &str) -> Result<Self> {
+        let pipe = pipe_path(name, 1);
+        make_fifo(&pipe)?;
+        let pipe = Arc::new(Mutex::new(pipe));
+        let stop = Arc::new(AtomicBool::new(false));
+        
+        let (ready_tx, ready_rx) = mpsc::channel();
+        let device_id = device_id.to_string();
+        let writer_pipe = pipe.clone();
+        let writer_stop = stop.clone();
+        thread::spawn(move || {
+            let (tx, rx) = mpsc::channel();
+            let stream = match open_pcm_stream(&device_id, tx) {
+                Ok((stream, format)) => {
+                    let _ = ready_tx.send(Ok(format));
+                    stream
+                }
+                Err(e) => {
+                    let _ = ready_tx.send(Err(e));
+                    return;
+                }
+            };
+            write_pcm(rx, &writer_pipe, &writer_stop);
+            drop(stream);
+        });
+        
+        let format = match ready_rx.recv() {
+            Ok(Ok(format)) => format,
+            Ok(Err(e)) => {
+                let _ = std::fs::remove_file(&*pipe.lock().unwrap_or_else(|e| e.into_inner()));
+                return Err(e);
+            }
+            Err(_) => return Err(anyhow!("Audio capture thread exited")),
+        };
+        Ok(Self { format, pipe, pipes_made: AtomicU32::new(1), name: name.to_string(), stop })
+    }
+
+    pub fn format(&self) -> PcmFormat {
+        self.format
+    }
+
+    /// The pipe ffmpeg should read
+    pub fn pipe(&self) -> PathBuf {
+        self.pipe.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Send samples to a fresh pipe from now on, e.g. for the next file of a
+    /// split recording. The previous pipe is closed, ending that ffmpeg's audio.
+    pub fn new_pipe(&self) -> Result<()> {
+        let counter = self.pipes_made.fetch_add(1, Ordering::Relaxed) + 1;
+        let pipe = pipe_path(&self.name, counter);
+        make_fifo(&pipe)?;
+        *self.pipe.lock().unwrap_or_else(|e| e.into_inner()) = pipe;
+        Ok(())
+    }
+}
+
+impl Drop for PcmCapture {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        // A writer still waiting for ffmpeg to open the pipe is blocked in
+        // open(); opening the pipe read-write ourselves releases it
+        let _ = OpenOptions::new().read(true).write(true).open(self.pipe());
+    }
+}
+
+fn pipe_path(name: &str, counter: u32) -> PathBuf {
+    std::env::temp_dir().join(format!("screencast-{}-{}-{}.pcm", std::process::id(), name, counter))
+}
+
+fn make_fifo(path: &Path) -> Result<()> {
+    let _ = std::fs::remove_file(path);
+    let status = std::process::Command::new("mkfifo")
+        .arg(path)
+        .status()
+        .map_err(|e| anyhow!("Failed to run mkfifo: {}", e))?;
+    if !status.success() {
+        return Err(anyhow!("mkfifo failed for {}", path.display()));
+    }
+    Ok(())
+}
+
+/// The format `PcmCapture::start` would report for `device_id`, without opening a stream
+pub fn pcm_format(device_id: &str) -> Result<PcmFormat> {
+    let config = input_config(&find_input_device(device_id)?)?;
+    Ok(PcmFormat { sample_rate: config.sample_rate().0, channels: config.channels() })
+}
+
+/// A short clip recorded by `record_sample`
+pub struct AudioSample {
+    pub path: PathBuf, // 16-bit WAV in the temp folder
+    pub peak: f32, // Loudest sample, 0.0 to 1.0 of full scale
+}
+
+/// Record `duration` from `device_id` into a WAV file, to check by ear that
+/// it's the right input and neither silent nor clipping
+pub fn record_sample(device_id: &str, duration: Duration) -> Result<AudioSample> {
+    let (tx, rx) = mpsc::channel();
+    let (stream, format) = open_pcm_stream(device_id, tx)?;
+    let deadline = Instant::now() + duration;
+    let mut samples: Vec<i16> = Vec::new();
+    while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+        match rx.recv_timeout(left) {
+            Ok(buffer) => samples.extend(buffer),
+            Err(_) => break,
+        }
+    }
+    drop(stream);
+
+    let path = std::env::temp_dir().join(format!("screencast-{}-sample.wav", std::process::id()));
+    write_wav(&path, format, &samples)?;
+    let peak = samples.iter().map(|s| s.unsigned_abs()).max().unwrap_or(0);
+    Ok(AudioSample { path, peak: (peak as f32 / i16::MAX as f32).min(1.0) })
+}
+
+/// Write s16le samples as a PCM WAV file
+fn write_wav(path: &Path, format: PcmFormat, samples: &[i16]) -> Result<()> {
+    let data_len = (samples.len() * 2) as u32;
+    let block_align = format.channels * 2;
+    let mut bytes = Vec::with_capacity(44 + data_len as usize);
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes()); // fmt chunk size
+    bytes.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    bytes.extend_from_slice(&format.channels.to_le_bytes());
+    bytes.extend_from_slice(&format.sample_rate.to_le_bytes());
+    bytes.extend_from_slice(&(format.sample_rate * block_align as u32).to_le_bytes());
+    bytes.extend_from_slice(&block_align.to_le_bytes());
+    bytes.extend_from_slice(&16u16.to_le_bytes()); // Bits per sample
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_len.to_le_bytes());
+    bytes.extend(samples.iter().flat_map(|s| s.to_le_bytes()));
+    std::fs::write(path, bytes).map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Play a sound file on the default output with afplay, returning once it ends
+pub fn play_file(path: &Path) -> Result<()> {
+    let status = std::process::Command::new("afplay")
+        .arg(path)
+        .status()
+        .map_err(|e| anyhow!("Failed to run afplay: {}", e))?;
+    if !status.success() {
+        return Err(anyhow!("afplay failed for {}", path.display()));
+    }
+    Ok(())
+}
+
+/// Open `device_id` for capture, sending each buffer as s16le samples to `tx`
+fn open_pcm_stream(device_id: &str, tx: Sender<Vec<i16>>) -> Result<(Stream, PcmFormat)> {
+    let device = find_input_device(device_id)?;
+    let config = input_config(&device)?;
+    let stream_config = config.config();
+    let stream = match config.sample_format() {
+        cpal::SampleFormat::F32 => build_pcm_stream::<f32>(&device, &stream_config, tx)?,
+        cpal::SampleFormat::I16 => build_pcm_stream::<i16>(&device, &stream_config, tx)?,
+        cpal::SampleFormat::U16 => build_pcm_stream::<u16>(&device, &stream_config, tx)?,
+        cpal::SampleFormat::I32 => build_pcm_stream::<i32>(&device, &stream_config, tx)?,
+        cpal::SampleFormat::U8 => build_pcm_stream::<u8>(&device, &stream_config, tx)?,
+        format => return Err(anyhow!("Unsupported sample format: {:?}", format)),
+    };
+    stream.play().map_err(|e| anyhow!("Failed to start audio stream: {}", e))?;
+    let format = PcmFormat { sample_rate: stream_config.sample_rate.0, channels: stream_config.channels };
+    Ok((stream, format))
+}
+
+fn build_pcm_stream<T>(device: &cpal::Device, config: &cpal::StreamConfig, tx: Sender<Vec<i16>>) -> Result<Stream>
+where
+    T: cpal::SizedSample + 'static,
+    i16: cpal::FromSample<T>,
+{
+    let stream = device.build_input_stream(
+        config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            let _ = tx.send(data.iter().map(|sample| sample.to_sample::<i16>()).collect());
+        },
+        move |err| {
+            eprintln!("Audio capture error: {}", err);
+        },
+        None,
+    )?;
+    Ok(stream)
+}
+
+/// Write captured samples to the current pipe until `stop`. Opening a pipe
+/// blocks until ffmpeg opens it too; samples from before then are dropped.
+fn write_pcm(rx: Receiver<Vec<i16>>, pipe: &Mutex<PathBuf>, stop: &AtomicBool) {
+    let mut open: Option<(PathBuf, std::fs::File)> = None;
+    let mut last_target = None;
+    while !stop.load(Ordering::Relaxed) {
+        let target = pipe.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        if last_target.as_ref() != Some(&target) {
+            // Switched to a new pipe: the old one is done with
+            if let Some(old) = last_target.replace(target.clone()) {
+                open = None;
+                let _ = std::fs::remove_file(old);
+            }
+        }
+        
+        let Some((_, file)) = open.as_mut() else {
+            match OpenOptions::new().write(true).open(&target) {
+                Ok(file) => {
+                    while rx.try_recv().is_ok() {}
+                    open = Some((target, file));
+                }
+                Err(e) => {
+                    eprintln!("Failed to open audio pipe {}: {}", target.display(), e);
+                    break;
+                }
+            }
+            continue;
+        };
+        
+        match rx.recv_timeout(Duration::from_millis(100)) {
+            Ok(samples) => {
+                let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+                if file.write_all(&bytes).is_err() {
+                    // ffmpeg went away, e.g. replaced by an encoder fallback that
+                    // reads the same pipe: wait for the next reader
+                    open = None;
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    if let Some(pipe) = last_target {
+        let _ = std::fs::remove_file(pipe);
+    }
+}
+
+/// The connected input device with this id, i.e. this name
+fn input_device_named(device_id: &str) -> Option<cpal::Device> {
+    cpal::default_host()
+        .input_devices()
+        .ok()?
+        .find(|d| d.name().is_ok_and(|name| name == device_id))
+}
+
+/// Find an input device by id. A device that's gone is an error naming it:
+/// recording the default input in its place would record the wrong mic.
+fn find_input_device(device_id: &str) -> Result<cpal::Device> {
+    input_device_named(device_id)
+        .ok_or_else(|| anyhow!("audio input \"{}\" is not connected", device_id))
+}
+
+/// Ids saved by older versions were CPAL indices, which shift whenever a
+/// device is plugged in or out. Maps such an id to the name of the device at
+/// that index now, the device it meant unless devices changed since it was
+/// saved; any other id is returned as is.
+pub fn migrate_device_id(device_id: &str) -> String {
+    let index = match device_id.parse::<usize>() {
+        Ok(index) if input_device_named(device_id).is_none() => index,
+        _ => return device_id.to_string(),
+    };
+    cpal::default_host().input_devices().ok()
+        .and_then(|mut devices| devices.nth(index)?.name().ok())
+        .unwrap_or_else(|| device_id.to_string())
+}
+
+/// The device's default input config, or the closest F32 one if its default
+/// sample format isn't one we can read
+fn input_config(device: &cpal::Device) -> Result<cpal::SupportedStreamConfig> {
+    let default_config = device.default_input_config()
+        .map_err(|e| anyhow!("Failed to get default input config: {}", e))?;
+    if is_meterable(default_config.sample_format()) {
+        return Ok(default_config);
+    }
+    closest_f32_config(device, default_config.sample_rate())
+        .ok_or_else(|| anyhow!("Unsupported sample format: {:?}", default_config.sample_format()))
+}
+
+/// Sample formats `build_level_stream` and `build_pcm_stream` are used with
+fn is_meterable(format: cpal::SampleFormat) -> bool {
+    matches!(
+        format,
+        cpal::SampleFormat::F32
+            | cpal::SampleFormat::I16
+            | cpal::SampleFormat::U16
+            | cpal::SampleFormat::I32
+            | cpal::SampleFormat::U8
+    )
+}
+
+/// The F32 input config whose sample rate is nearest `preferred`, for devices
+/// whose default format the meter can't read
+fn closest_f32_config(device: &cpal::Device, preferred: cpal::SampleRate) -> Option<cpal::SupportedStreamConfig> {
+    device.supported_input_configs().ok()?
+        .filter(|range| range.sample_format() == cpal::SampleFormat::F32)
+        .map(|range| {
+            let rate = preferred.0.clamp(range.min_sample_rate().0, range.max_sample_rate().0);
+            range.with_sample_rate(cpal::SampleRate(rate))
+        })
+        .min_by_key(|config| config.sample_rate().0.abs_diff(preferred.0))
+}
+
+/// Input stream that stores the metered level of each buffer while monitoring
+fn build_level_stream<T: cpal::SizedSample + 'static>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    level: Arc<Mutex<f32>>,
+    is_monitoring: Arc<AtomicBool>,
+    rms: fn(&[T]) -> f32,
+) -> Result<Stream> {
+    let stream = device.build_input_stream(
+        config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            if is_monitoring.load(Ordering::Relaxed) {
+                let value = rms(data);
+                if let Ok(mut level_guard) = level.lock() {
+                    *level_guard = value;
+                }
+            }
+        },
+        move |err| {
+            eprintln!("Audio stream error: {}", err);
+        },
+        None,
+    )?;
+    Ok(stream)
+}
+
+// Helper functions to calculate RMS (Root Mean Square) for different sample formats,
+// each normalized to -1.0..1.0 first
 fn calculate_rms(data: &[f32]) -> f32 {
     if data.is_empty() {
         return 0.0;
     }
     
     let sum_squares: f32 = data.iter().map(|&x| x * x).sum();
-    let rms = (sum_squares / data.len() as f32).sqrt();
-    
-    // Apply amplification and smoothing for better visibility
-    let amplified = rms * 3.0; // Amplify by 3x for better visibility
-    let smoothed = amplified.min(1.0);
-    
-    // Apply a slight curve to make low levels more visible
-    if smoothed < 0.1 {
-        smoothed * 2.0 // Make very low levels more visible
-    } else {
-        smoothed
-    }
+    meter_level((sum_squares / data.len() as f32).sqrt())
 }
 
 fn calculate_rms_i16(data: &[i16]) -> f32 {
@@ -172,18 +481,7 @@ fn calculate_rms_i16(data: &[i16]) -> f32 {
     }
     
     let sum_squares: f64 = data.iter().map(|&x| (x as f64 / 32768.0).powi(2)).sum();
-    let rms = (sum_squares / data.len() as f64).sqrt() as f32;
-    
-    // Apply amplification and smoothing for better visibility
-    let amplified = rms * 3.0; // Amplify by 3x for better visibility
-    let smoothed = amplified.min(1.0);
-    
-    // Apply a slight curve to make low levels more visible
-    if smoothed < 0.1 {
-        smoothed * 2.0 // Make very low levels more visible
-    } else {
-        smoothed
-    }
+    meter_level((sum_squares / data.len() as f64).sqrt() as f32)
 }
 
 fn calculate_rms_u16(data: &[u16]) -> f32 {
@@ -192,8 +490,29 @@ fn calculate_rms_u16(data: &[u16]) -> f32 {
     }
     
     let sum_squares: f64 = data.iter().map(|&x| ((x as f64 - 32768.0) / 32768.0).powi(2)).sum();
-    let rms = (sum_squares / data.len() as f64).sqrt() as f32;
+    meter_level((sum_squares / data.len() as f64).sqrt() as f32)
+}
+
+fn calculate_rms_i32(data: &[i32]) -> f32 {
+    if data.is_empty() {
+        return 0.0;
+    }
+    
+    let sum_squares: f64 = data.iter().map(|&x| (x as f64 / 2147483648.0).powi(2)).sum();
+    meter_level((sum_squares / data.len() as f64).sqrt() as f32)
+}
+
+fn calculate_rms_u8(data: &[u8]) -> f32 {
+    if data.is_empty() {
+        return 0.0;
+    }
     
+    let sum_squares: f64 = data.iter().map(|&x| ((x as f64 - 128.0) / 128.0).powi(2)).sum();
+    meter_level((sum_squares / data.len() as f64).sqrt() as f32)
+}
+
+/// Map an RMS value to the 0.0 to 1.0 meter scale
+fn meter_level(rms: f32) -> f32 {
     // Apply amplification and smoothing for better visibility
     let amplified = rms * 3.0; // Amplify by 3x for better visibility
     let smoothed = amplified.min(1.0);
@@ -211,6 +530,7 @@ pub struct AudioDeviceManager {
     devices: Vec<AudioDevice>,
     level_monitors: HashMap<String, AudioLevelMonitor>,
     is_enumerating: Arc<AtomicBool>,
+    paused: Option<Vec<String>>, // Devices `pause_monitoring` stopped, to restart on resume
 }
 
 impl AudioDeviceManager {
@@ -219,90 +539,67 @@ impl AudioDeviceManager {
             devices: Vec::new(),
             level_monitors: HashMap::new(),
             is_enumerating: Arc::new(AtomicBool::new(false)),
+            paused: None,
         }
     }
 
     /// Enumerate available audio input devices
     pub fn enumerate_devices(&mut self) -> Result<Vec<AudioDevice>> {
+        self.enumerate_devices_retrying(1)
+    }
+
+    /// Enumerate devices, retrying with backoff while Core Audio lists none
+    /// or fails. For launch, when it may not be ready yet.
+    pub fn enumerate_devices_with_retry(&mut self) -> Result<Vec<AudioDevice>> {
+        self.enumerate_devices_retrying(ENUMERATION_ATTEMPTS)
+    }
+
+    fn enumerate_devices_retrying(&mut self, attempts: u32) -> Result<Vec<AudioDevice>> {
         if self.is_enumerating.load(Ordering::Relaxed) {
             return Ok(self.devices.clone());
         }
 
         self.is_enumerating.store(true, Ordering::Relaxed);
-        
-        let devices = self.enumerate_devices_impl()?;
-        self.devices = devices.clone();
-        
-        // Create level monitors for new devices
-        for device in &self.devices {
-            if !self.level_monitors.contains_key(&device.id) {
-                self.level_monitors.insert(
-                    device.id.clone(),
-                    AudioLevelMonitor::new(device.id.clone()),
-                );
+        let mut result = list_input_devices();
+        let mut delay = ENUMERATION_RETRY_DELAY;
+        for _ in 1..attempts {
+            match &result {
+                Ok(devices) if !devices.is_empty() => break,
+                Ok(_) => eprintln!("No audio input devices yet, retrying in {:?}", delay),
+                Err(e) => eprintln!("Audio device enumeration failed ({}), retrying in {:?}", e, delay),
             }
+            thread::sleep(delay);
+            delay *= 2;
+            result = list_input_devices();
         }
-        
         self.is_enumerating.store(false, Ordering::Relaxed);
-        Ok(devices)
+        Ok(self.set_devices(result?))
     }
 
-    fn enumerate_devices_impl(&self) -> Result<Vec<AudioDevice>> {
-        #[cfg(target_os = "macos")]
-        {
-            self.enumerate_macos_devices()
-        }
-        #[cfg(not(target_os = "macos"))]
-        {
-            // For non-macOS platforms, return a dummy device
-            Ok(vec![AudioDevice {
-                id: "default".to_string(),
+    /// Take a device list from `list_input_devices`, which may have run on
+    /// another thread, and return it as `get_devices` will
+    pub fn set_devices(&mut self, mut devices: Vec<AudioDevice>) -> Vec<AudioDevice> {
+        // Nothing found: offer the system default rather than an empty list
+        if devices.is_empty() {
+            devices.push(AudioDevice {
+                id: "default".to_string(), // Matches no device, so the default input is used
                 name: "Default Audio Input".to_string(),
                 is_default: true,
-            }])
+            });
         }
-    }
-
-    #[cfg(target_os = "macos")]
-    fn enumerate_macos_devices(&self) -> Result<Vec<AudioDevice>> {
-        // Use CPAL devices directly for the settings tab
-        let mut devices = Vec::new();
-        let host = cpal::default_host();
-        
-        // Get all input devices
-        let input_devices = host.input_devices()
-            .map_err(|e| anyhow!("Failed to enumerate input devices: {}", e))?;
-        
-        // Get default device for comparison
-        let default_device = host.default_input_device();
+        self.devices = devices.clone();
         
-        // Collect all devices with their actual names and CPAL indices
-        for (cpal_index, cpal_device) in input_devices.enumerate() {
-            if let Ok(device_name) = cpal_device.name() {
-                let is_default = default_device.as_ref().and_then(|d| {
-                    d.name().ok().and_then(|default_name| {
-                        Some(device_name == default_name)
-                    })
-                }).unwrap_or(false);
-                
-                devices.push(AudioDevice {
-                    id: cpal_index.to_string(), // Use CPAL index for device ID
-                    name: device_name,
-                    is_default,
-                });
+        // Create level monitors for new devices
+        for device in &self.devices {
+            if !self.level_monitors.contains_key(&device.id) {
+                self.level_monitors.insert(
+                    device.id.clone(),
+                    AudioLevelMonitor::new(device.id.clone()),
+                );
             }
         }
         
-        // If no devices found, add a fallback
-        if devices.is_empty() {
-            devices.push(AudioDevice {
-                id: "0".to_string(),
-                name: "Default Audio Input".to_string(),
-                is_default: true,
-            });
-        }
-
-        Ok(devices)
+        devices
     }
 
     /// Get level monitor for a device
@@ -315,14 +612,51 @@ impl AudioDeviceManager {
         if let Some(monitor) = self.level_monitors.get_mut(device_id) {
             // Force stop any existing monitoring first
             monitor.stop_monitoring();
-            // Then start fresh monitoring
-            monitor.start_monitoring()?;
+            // Then start fresh monitoring; a failed start isn't left looking active
+            if let Err(e) = monitor.start_monitoring() {
+                monitor.stop_monitoring();
+                return Err(e);
+            }
             Ok(())
         } else {
             Err(anyhow!("Device not found: {}", device_id))
         }
     }
 
+    /// Stop every level monitor until `resume_monitoring`, releasing the
+    /// microphone. Recordings capture audio through ffmpeg and keep going.
+    pub fn pause_monitoring(&mut self) {
+        if self.paused.is_some() {
+            return;
+        }
+        let mut stopped = Vec::new();
+        for (device_id, monitor) in &mut self.level_monitors {
+            if monitor.is_monitoring.load(Ordering::Relaxed) {
+                monitor.stop_monitoring();
+                stopped.push(device_id.clone());
+            }
+        }
+        self.paused = Some(stopped);
+    }
+
+    /// Restart the monitors `pause_monitoring` stopped
+    pub fn resume_monitoring(&mut self) -> Result<()> {
+        for device_id in self.paused.take().unwrap_or_default() {
+            self.start_level_monitoring(&device_id)?;
+        }
+        Ok(())
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.is_some()
+    }
+
+    /// Whether a device's level is being monitored
+    pub fn is_monitoring(&self, device_id: &str) -> bool {
+        self.level_monitors.get(device_id)
+            .is_some_and(|monitor| monitor.is_monitoring.load(Ordering::Relaxed))
+    }
+
     /// Stop monitoring audio levels for a device
     pub fn stop_level_monitoring(&mut self, device_id: &str) {
         if let Some(monitor) = self.level_monitors.get_mut(device_id) {
@@ -349,70 +683,79 @@ impl Default for AudioDeviceManager {
     }
 }
 
-/// Get the ffmpeg device index for a given device ID
-/// This maps CPAL device IDs to their corresponding ffmpeg avfoundation indices
-pub fn get_ffmpeg_device_index(device_id: &str) -> Option<usize> {
-    // Try to parse as a number first (CPAL index)
-    if let Ok(cpal_index) = device_id.parse::<usize>() {
-        // Get the device name from CPAL using the index
+/// Input devices Core Audio lists right now. Doesn't touch an
+/// `AudioDeviceManager`, so it can run off the UI thread; hand the result to
+/// `AudioDeviceManager::set_devices`.
+pub fn list_input_devices() -> Result<Vec<AudioDevice>> {
+    #[cfg(target_os = "macos")]
+    {
+        // Use CPAL devices directly for the settings tab
+        let mut devices = Vec::new();
         let host = cpal::default_host();
-        if let Ok(mut devices) = host.input_devices() {
-            if let Some(device) = devices.nth(cpal_index) {
-                if let Ok(device_name) = device.name() {
-                    // Now find the ffmpeg index for this device name
-                    if let Ok(ffmpeg_devices) = get_ffmpeg_device_mapping() {
-                        for (ffmpeg_index, ffmpeg_name) in ffmpeg_devices {
-                            if ffmpeg_name == device_name {
-                                return Some(ffmpeg_index);
-                            }
-                        }
-                    }
-                }
-            }
-        }
-    }
     
-    // If it's not a number, try to find the device by name using ffmpeg mapping
-    if let Ok(ffmpeg_devices) = get_ffmpeg_device_mapping() {
-        for (index, name) in ffmpeg_devices {
-            if name == device_id {
-                return Some(index);
+        // Get all input devices
+        let input_devices = host.input_devices()
+            .map_err(|e| anyhow!("Failed to enumerate input devices: {}", e))?;
+    
+        // Get default device for comparison
+        let default_device = host.default_input_device();
+    
+        // Collect all devices with their actual names
+        for cpal_device in input_devices {
+            if let Ok(device_name) = cpal_device.name() {
+                let is_default = default_device.as_ref().and_then(|d| {
+                    d.name().ok().and_then(|default_name| {
+                        Some(device_name == default_name)
+                    })
+                }).unwrap_or(false);
+            
+                devices.push(AudioDevice {
+                    id: device_name.clone(), // Stays put when other devices come and go, unlike the CPAL index
+                    name: device_name,
+                    is_default,
+                });
             }
         }
+
+        Ok(devices)
     }
-    
-    // Fallback to first device if not found
-    Some(0)
+    #[cfg(not(target_os = "macos"))]
+    {
+        // For non-macOS platforms, return a dummy device
+        Ok(vec![AudioDevice {
+            id: "default".to_string(),
+            name: "Default Audio Input".to_string(),
+            is_default: true,
+        }])
+    }
+}
+
+/// Name of an input device from its id, used to title its audio track
+/// None once the device is disconnected
+pub fn device_name(device_id: &str) -> Option<String> {
+    input_device_named(device_id)?.name().ok()
+}
+
+/// Get the ffmpeg device index for a given device ID
+/// Device ids are names, so this looks the name up in ffmpeg's avfoundation
+/// list each time; its indices shift like CPAL's. A device that isn't listed
+/// is an error naming it, like `find_input_device`.
+pub fn get_ffmpeg_device_index(device_id: &str) -> Result<usize> {
+    get_ffmpeg_device_mapping()?
+        .into_iter()
+        .find(|(_, name)| name == device_id)
+        .map(|(index, _)| index)
+        .ok_or_else(|| anyhow!("audio input \"{}\" is not connected", device_id))
 }
 
 /// Get the optimal sample rate for a given audio device
 /// This helps avoid sample rate conversion artifacts
 pub fn get_optimal_sample_rate(device_id: &str) -> u32 {
-    // Try to get the device's native sample rate
-    let host = cpal::default_host();
-    
-    if let Ok(index) = device_id.parse::<usize>() {
-        // Use index-based lookup
-        if let Ok(mut devices) = host.input_devices() {
-            if let Some(device) = devices.nth(index) {
-                if let Ok(config) = device.default_input_config() {
-                    return config.sample_rate().0;
-                }
-            }
-        }
-    } else {
-        // Fallback to name-based lookup
-        if let Ok(mut devices) = host.input_devices() {
-            if let Some(device) = devices.find(|d| d.name().map(|name| name == device_id).unwrap_or(false)) {
-                if let Ok(config) = device.default_input_config() {
-                    return config.sample_rate().0;
-                }
-            }
-        }
-    }
-    
-    // Default to 48kHz if we can't determine the device's native rate
-    48000
+    // Try to get the device's native sample rate, and default to 48kHz if
+    // we can't determine it
+    input_device_named(device_id)
+        .and_then(|device| device.default_input_config().ok())
+        .map_or(48000, |config| config.sample_rate().0)
 }
 
 /// Get the optimal buffer size for a given audio device
@@ -521,3 +864,53 @@ pub fn debug_list_audio_devices() -> Result<()> {
     
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One second of a 440 Hz sine at `amplitude` (0-1), sampled at 8 kHz
+    fn sine(amplitude: f32) -> Vec<f32> {
+        (0..8000)
+            .map(|n| amplitude * (n as f32 * 440.0 * std::f32::consts::TAU / 8000.0).sin())
+            .collect()
+    }
+
+    #[test]
+    fn silence_meters_zero() {
+        assert_eq!(calculate_rms_i32(&[0; 64]), 0.0);
+        assert_eq!(calculate_rms_u8(&[128; 64]), 0.0);
+        assert_eq!(calculate_rms_i32(&[]), 0.0);
+        assert_eq!(calculate_rms_u8(&[]), 0.0);
+    }
+
+    #[test]
+    fn full_scale_meters_one() {
+        assert_eq!(calculate_rms_i32(&[i32::MAX, i32::MIN, i32::MAX, i32::MIN]), 1.0);
+        assert_eq!(calculate_rms_u8(&[255, 0, 255, 0]), 1.0);
+    }
+
+    #[test]
+    fn integer_formats_meter_like_f32() {
+        // Both sides of the meter's low-level curve
+        for amplitude in [0.02, 0.1, 0.25] {
+            let signal = sine(amplitude);
+            let expected = calculate_rms(&signal);
+            let i32s: Vec<i32> = signal.iter().map(|&x| (x as f64 * 2147483647.0) as i32).collect();
+            let u8s: Vec<u8> = signal.iter().map(|&x| (128.0 + x * 127.0).round() as u8).collect();
+            let i32_level = calculate_rms_i32(&i32s);
+            let u8_level = calculate_rms_u8(&u8s);
+            assert!((i32_level - expected).abs() < 1e-4, "i32 at {}: {} vs {}", amplitude, i32_level, expected);
+            // 8 bits only resolve the sine to about 1/128 of full scale
+            assert!((u8_level - expected).abs() < 0.03, "u8 at {}: {} vs {}", amplitude, u8_level, expected);
+        }
+    }
+
+    #[test]
+    fn u8_is_centered_on_128() {
+        // A constant offset from the center is a DC level, not silence
+        let level = calculate_rms_u8(&[160; 16]);
+        assert!((level - meter_level(0.25)).abs() < 1e-6, "{}", level);
+        assert_eq!(calculate_rms_u8(&[96; 16]), level);
+    }
+}
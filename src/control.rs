@@ -0,0 +1,247 @@
+//! Local control server, for stream decks and other automation.
+//!
+//! Clients connect over TCP to `127.0.0.1` on the configured port and send one
+//! JSON object per line. Every request carries the token shown in the app's
+//! settings and a `command`:
+//!
+//! ```text
+//! {"token": "…", "command": "list_windows"}
+//! {"token": "…", "command": "start", "window_id": 1234}
+//! {"token": "…", "command": "stop", "window_id": 1234}
+//! {"token": "…", "command": "status"}
+//! ```
+//!
+//! Each request gets one line back, either `{"ok": true, "result": …}` or
+//! `{"ok": false, "error": "…"}`. `list_windows` returns
+//! `[{"window_id", "name", "width", "height", "recording"}]`; `status` returns
+//! `{"recording": [{"window_id", "name", "elapsed_secs"}], "starting": [ids],
+//! "failed": {id: error}}`; `start` and `stop` return `null` once the app has
+//! acted on them. A connection may send any number of requests.
+//!
+//! At most `MAX_CONNECTIONS` clients are served at once; others get an error
+//! line and are disconnected. Requests longer than `MAX_LINE_LEN` bytes are
+//! answered with an error and end the connection.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::warn;
+
+pub const DEFAULT_PORT: u16 = 47800;
+
+/// Clients served at the same time, each on its own thread
+pub const MAX_CONNECTIONS: usize = 8;
+
+/// Longest request line accepted, in bytes
+pub const MAX_LINE_LEN: usize = 64 * 1024;
+
+/// How long a connection waits for the app to handle a command
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often the accept loop checks whether the server was stopped
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// App-level control server settings. Kept out of `RecordingConfig` so that
+/// profiles and exported settings never carry the token.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ControlSettings {
+    pub enabled: bool,
+    pub port: u16,
+    pub token: String, // Required with every command; generated when the server is first enabled
+}
+
+impl Default for ControlSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: DEFAULT_PORT,
+            token: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Command {
+    ListWindows,
+    Start { window_id: u64 },
+    Stop { window_id: u64 },
+    Status,
+}
+
+#[derive(Deserialize)]
+struct Request {
+    token: String,
+    #[serde(flatten)]
+    command: Command,
+}
+
+/// A command waiting for the app to carry it out
+pub struct ControlRequest {
+    pub command: Command,
+    reply: Sender<Result<Value, String>>,
+}
+
+impl ControlRequest {
+    pub fn respond(self, result: Result<Value, String>) {
+        let _ = self.reply.send(result);
+    }
+}
+
+/// Accepts control connections on a background thread until dropped.
+/// Commands are queued for the app, which picks them up with `requests`.
+pub struct ControlServer {
+    port: u16,
+    token: String,
+    requests: Receiver<ControlRequest>,
+    stop: Arc<AtomicBool>,
+    accept_thread: Option<JoinHandle<()>>,
+}
+
+impl ControlServer {
+    /// Listen on `127.0.0.1:port`, or on a free port if `port` is 0. `wake` is
+    /// called (on a server thread) with each queued command, so the app can
+    /// handle it promptly.
+    pub fn start(port: u16, token: &str, wake: impl Fn() + Send + Sync + 'static) -> Result<Self> {
+        anyhow::ensure!(!token.is_empty(), "the control server needs a token");
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+            .with_context(|| format!("cannot listen on port {}", port))?;
+        listener.set_nonblocking(true)?;
+        let port = listener.local_addr()?.port();
+
+        let (tx, requests) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let wake: Arc<dyn Fn() + Send + Sync> = Arc::new(wake);
+        let thread_stop = stop.clone();
+        let thread_token = token.to_string();
+        let connections = Arc::new(AtomicUsize::new(0));
+        let accept_thread = thread::spawn(move || {
+            while !thread_stop.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((mut stream, _)) => {
+                        if connections.fetch_add(1, Ordering::AcqRel) >= MAX_CONNECTIONS {
+                            connections.fetch_sub(1, Ordering::AcqRel);
+                            let _ = stream.set_nonblocking(false);
+                            let _ = writeln!(stream, "{}", json!({ "ok": false, "error": "too many connections" }));
+                            continue;
+                        }
+                        let (tx, wake, token) = (tx.clone(), wake.clone(), thread_token.clone());
+                        let connections = connections.clone();
+                        thread::spawn(move || {
+                            serve_connection(stream, &token, &tx, wake.as_ref());
+                            connections.fetch_sub(1, Ordering::AcqRel);
+                        });
+                    }
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(ACCEPT_POLL_INTERVAL),
+                    Err(e) => {
+                        warn!("Control server accept failed: {}", e);
+                        thread::sleep(ACCEPT_POLL_INTERVAL);
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            port,
+            token: token.to_string(),
+            requests,
+            stop,
+            accept_thread: Some(accept_thread),
+        })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    /// Commands received since the last call
+    pub fn requests(&self) -> Vec<ControlRequest> {
+        self.requests.try_iter().collect()
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        // The listener closes with the thread; wait so the port can be reused
+        if let Some(thread) = self.accept_thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Answer requests on one connection until the client hangs up or the app
+/// stops handling commands
+fn serve_connection(stream: TcpStream, token: &str, requests: &Sender<ControlRequest>, wake: &(dyn Fn() + Send + Sync)) {
+    let _ = stream.set_nonblocking(false);
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        // One byte over the limit tells an over-long line from one that fits
+        match reader.by_ref().take(MAX_LINE_LEN as u64 + 1).read_line(&mut line) {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
+        }
+        if line.len() > MAX_LINE_LEN {
+            let error = format!("request longer than {} bytes", MAX_LINE_LEN);
+            let _ = writeln!(writer, "{}", json!({ "ok": false, "error": error }));
+            return;
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        let result = match serde_json::from_str::<Request>(&line) {
+            Err(e) => Err(format!("invalid request: {}", e)),
+            Ok(request) if !tokens_match(&request.token, token) => Err("invalid token".to_string()),
+            Ok(request) => {
+                let (reply, response) = mpsc::channel();
+                if requests.send(ControlRequest { command: request.command, reply }).is_err() {
+                    return; // Server stopped
+                }
+                wake();
+                response
+                    .recv_timeout(REPLY_TIMEOUT)
+                    .unwrap_or_else(|_| Err("the app didn't respond".to_string()))
+            }
+        };
+        let response = match result {
+            Ok(result) => json!({ "ok": true, "result": result }),
+            Err(error) => json!({ "ok": false, "error": error }),
+        };
+        if writeln!(writer, "{}", response).is_err() {
+            return;
+        }
+    }
+}
+
+/// Compare tokens without stopping at the first difference
+fn tokens_match(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given.bytes().zip(expected.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Random 32-character hex token for `ControlSettings::token`, read from the
+/// OS random number generator
+pub fn generate_token() -> io::Result<String> {
+    let mut bytes = [0u8; 16];
+    File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
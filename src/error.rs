@@ -0,0 +1,24 @@
+use thiserror::Error;
+
+/// Errors from starting a recording, distinguishable by callers.
+/// The `Display` text is suitable for showing to the user.
+#[derive(Debug, Error)]
+pub enum RecorderError {
+    #[error("ffmpeg not found. Install via Homebrew: brew install ffmpeg")]
+    FfmpegMissing,
+    #[error("Screen recording permission not granted. Enable it in System Settings > Privacy & Security > Screen Recording.")]
+    PermissionDenied,
+    #[error("Window {0} is no longer available")]
+    WindowGone(u64),
+    #[error("Cannot create output file: {0:#}")]
+    Output(anyhow::Error),
+    #[error("ffmpeg failed to start: {0:#}")]
+    Encoder(anyhow::Error),
+    #[error("Recording was canceled while starting")]
+    Canceled,
+    #[error("Cannot capture audio: {0:#}")]
+    Audio(anyhow::Error),
+    #[cfg(not(target_os = "macos"))]
+    #[error("Window capture is only supported on macOS")]
+    Unsupported,
+}
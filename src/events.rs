@@ -0,0 +1,42 @@
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use crate::ffmpeg::{CaptureStats, VideoEncoder};
+
+/// Something that happened to a recording
+#[derive(Clone, Debug)]
+pub enum RecordingEvent {
+    Started { window_id: u64, path: PathBuf },
+    /// `path` is the last file written; earlier split files were finished along the way.
+    /// `files` lists every file, `path` last. `error` says why ffmpeg didn't finalize it cleanly.
+    Stopped { window_id: u64, path: PathBuf, files: Vec<PathBuf>, stats: CaptureStats, error: Option<String> },
+    /// Failed to start, or stopped on its own (e.g. ffmpeg exited)
+    Failed { window_id: u64, error: String },
+    EncoderFellBack { window_id: u64, from: VideoEncoder, to: VideoEncoder },
+}
+
+/// Fans recording events out to every subscriber. Clones share subscribers.
+#[derive(Clone, Default)]
+pub struct EventBus {
+    subscribers: Arc<Mutex<Vec<Sender<RecordingEvent>>>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Receive every event emitted from now on. Dropping the receiver unsubscribes.
+    pub fn subscribe(&self) -> Receiver<RecordingEvent> {
+        let (tx, rx) = channel();
+        self.subscribers.lock().push(tx);
+        rx
+    }
+
+    pub fn emit(&self, event: RecordingEvent) {
+        self.subscribers.lock().retain(|tx| tx.send(event.clone()).is_ok());
+    }
+}
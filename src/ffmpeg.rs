@@ -1,25 +1,550 @@
-use anyhow::{Context, Result};
-use std::io::{BufRead, BufReader, BufWriter, Write};
-use std::path::PathBuf;
-use std::process::{Child, Command, Stdio};
+use anyhow::{anyhow, Context, Result};
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, Signal, System, UpdateKind};
 use tracing::{debug, error, info, warn};
 
+use crate::error::RecorderError;
+use crate::events::{EventBus, RecordingEvent};
+use crate::recorder::RecordingConfig;
 use crate::window::WindowInfo;
-use crate::audio::{get_ffmpeg_device_index, get_optimal_sample_rate};
+use crate::audio::{device_name, get_ffmpeg_device_index, get_optimal_sample_rate, pcm_format, PcmCapture, PcmFormat};
 
 #[cfg(target_os = "macos")]
 use crate::macos;
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum VideoEncoder {
     H264VideoToolbox,
     H264VideoToolboxFallback,
     Libx264,
-    // You can add ProRes/HEVC variants if you want different tradeoffs.
+    /// H.265 on the media engine; about half the size of H.264 at the same quality
+    HevcVideoToolbox,
+    /// Intra-only ProRes for editing; large files, no bitrate control
+    ProResVideoToolbox,
+}
+
+impl VideoEncoder {
+    pub fn label(&self) -> &'static str {
+        match self {
+            VideoEncoder::H264VideoToolbox => "H.264 VideoToolbox (Hardware)",
+            VideoEncoder::H264VideoToolboxFallback => "H.264 VideoToolbox (Fallback)",
+            VideoEncoder::Libx264 => "H.264 libx264 (Software)",
+            VideoEncoder::HevcVideoToolbox => "HEVC VideoToolbox (Hardware)",
+            VideoEncoder::ProResVideoToolbox => "ProRes VideoToolbox (Hardware)",
+        }
+    }
+
+    /// Compact name for badges on recording rows
+    pub fn short_label(&self) -> &'static str {
+        match self {
+            VideoEncoder::H264VideoToolbox => "VT-HW",
+            VideoEncoder::H264VideoToolboxFallback => "VT-FB",
+            VideoEncoder::Libx264 => "x264",
+            VideoEncoder::HevcVideoToolbox => "HEVC",
+            VideoEncoder::ProResVideoToolbox => "ProRes",
+        }
+    }
+
+    /// Bitrate limits enforced at record time, if the encoder has any
+    pub fn bitrate_limits_kbps(&self) -> Option<(i32, i32)> {
+        match self {
+            VideoEncoder::H264VideoToolbox | VideoEncoder::HevcVideoToolbox => Some((500, 50000)),
+            VideoEncoder::H264VideoToolboxFallback => Some((1000, 20000)),
+            VideoEncoder::Libx264 | VideoEncoder::ProResVideoToolbox => None,
+        }
+    }
+
+    /// Whether the bitrate setting affects this encoder at all
+    pub fn uses_bitrate(&self) -> bool {
+        *self != VideoEncoder::ProResVideoToolbox
+    }
+
+    /// Container this encoder's output has to go in, if MP4 can't hold it
+    pub fn required_container(&self) -> Option<Container> {
+        match self {
+            VideoEncoder::ProResVideoToolbox => Some(Container::Mov),
+            _ => None,
+        }
+    }
+
+    /// The bitrate this encoder will actually be run with
+    pub fn effective_bitrate_kbps(&self, bitrate_kbps: i32) -> i32 {
+        match self.bitrate_limits_kbps() {
+            Some((min, max)) => bitrate_kbps.clamp(min, max),
+            None => bitrate_kbps,
+        }
+    }
+
+    /// What the settings say when this encoder won't record at `bitrate_kbps`,
+    /// e.g. "caps bitrate at 50 Mbps"; None when it's used as set
+    pub fn bitrate_adjustment(&self, bitrate_kbps: i32) -> Option<String> {
+        let (min, max) = self.bitrate_limits_kbps()?;
+        let effective = self.effective_bitrate_kbps(bitrate_kbps);
+        if effective == bitrate_kbps {
+            return None;
+        }
+        let limit = if effective == max {
+            format!("caps bitrate at {} Mbps", max / 1000)
+        } else {
+            format!("needs at least {} kbps", min)
+        };
+        Some(format!("{} {}; recordings will use {} kbps", self.label(), limit, effective))
+    }
+}
+
+/// How the encoder spends bits
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum RateControl {
+    /// Average bitrate in kbps
+    Bitrate(i32),
+    /// x264 CRF, 0–51 with lower being better; files grow with what's on
+    /// screen instead of spending the same bits on a static window
+    ConstantQuality(u8),
+}
+
+impl RateControl {
+    /// What each mode starts at when it's picked
+    pub const DEFAULT_BITRATE_KBPS: i32 = 6000;
+    pub const DEFAULT_CRF: u8 = 23;
+    /// Bitrates the settings accept; wider than the hardware encoders'
+    /// limits, which libx264 doesn't have
+    pub const BITRATE_RANGE_KBPS: std::ops::RangeInclusive<i32> = 500..=200_000;
+
+    /// The set bitrate, or None at constant quality
+    pub fn bitrate_kbps(&self) -> Option<i32> {
+        match self {
+            RateControl::Bitrate(kbps) => Some(*kbps),
+            RateControl::ConstantQuality(_) => None,
+        }
+    }
+
+    /// VideoToolbox's `-q:v` (1–100, higher is better) for a CRF, so the
+    /// one setting drives both encoder families
+    fn videotoolbox_quality(crf: u8) -> u8 {
+        let crf = u32::from(crf.min(51));
+        (100 - crf * 99 / 51) as u8
+    }
+}
+
+/// libx264 speed/size tradeoff; slower presets make smaller files at the
+/// same quality, but a preset too slow for the machine drops frames
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum X264Preset {
+    Ultrafast,
+    Superfast,
+    #[default]
+    Veryfast,
+    Faster,
+    Fast,
+    Medium,
+    Slow,
+    Slower,
+    Veryslow,
+}
+
+impl X264Preset {
+    pub const ALL: [X264Preset; 9] = [
+        X264Preset::Ultrafast,
+        X264Preset::Superfast,
+        X264Preset::Veryfast,
+        X264Preset::Faster,
+        X264Preset::Fast,
+        X264Preset::Medium,
+        X264Preset::Slow,
+        X264Preset::Slower,
+        X264Preset::Veryslow,
+    ];
+
+    pub fn ffmpeg_name(&self) -> &'static str {
+        match self {
+            X264Preset::Ultrafast => "ultrafast",
+            X264Preset::Superfast => "superfast",
+            X264Preset::Veryfast => "veryfast",
+            X264Preset::Faster => "faster",
+            X264Preset::Fast => "fast",
+            X264Preset::Medium => "medium",
+            X264Preset::Slow => "slow",
+            X264Preset::Slower => "slower",
+            X264Preset::Veryslow => "veryslow",
+        }
+    }
+}
+
+/// ProRes flavor, from smallest to highest quality; the data rate follows
+/// from the profile and frame size rather than a bitrate
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum ProResProfile {
+    Proxy,
+    Lt,
+    #[default]
+    Standard,
+    Hq,
+}
+
+impl ProResProfile {
+    pub const ALL: [ProResProfile; 4] = [
+        ProResProfile::Proxy,
+        ProResProfile::Lt,
+        ProResProfile::Standard,
+        ProResProfile::Hq,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ProResProfile::Proxy => "422 Proxy",
+            ProResProfile::Lt => "422 LT",
+            ProResProfile::Standard => "422",
+            ProResProfile::Hq => "422 HQ",
+        }
+    }
+
+    fn ffmpeg_name(&self) -> &'static str {
+        match self {
+            ProResProfile::Proxy => "proxy",
+            ProResProfile::Lt => "lt",
+            ProResProfile::Standard => "standard",
+            ProResProfile::Hq => "hq",
+        }
+    }
+}
+
+/// Output container, picked by file extension
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Container {
+    Mp4,
+    Mov,
+    Mkv,
+}
+
+impl Container {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Container::Mp4 => "mp4",
+            Container::Mov => "mov",
+            Container::Mkv => "mkv",
+        }
+    }
+}
+
+/// Audio codec used when recording with an audio input
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AudioCodec {
+    Aac,
+    Alac,
+    Opus,
+    Pcm,
+}
+
+/// How the primary audio device reaches ffmpeg
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum AudioBackend {
+    /// ffmpeg opens the device itself as an avfoundation input
+    #[default]
+    Avfoundation,
+    /// cpal records the device and pipes s16le samples into ffmpeg
+    Cpal,
+}
+
+impl AudioBackend {
+    pub fn label(&self) -> &'static str {
+        match self {
+            AudioBackend::Avfoundation => "ffmpeg (AVFoundation)",
+            AudioBackend::Cpal => "cpal (piped)",
+        }
+    }
+}
+
+impl AudioCodec {
+    pub fn label(&self) -> &'static str {
+        match self {
+            AudioCodec::Aac => "AAC",
+            AudioCodec::Alac => "ALAC (lossless)",
+            AudioCodec::Opus => "Opus",
+            AudioCodec::Pcm => "PCM (uncompressed)",
+        }
+    }
+
+    /// ALAC and PCM are lossless and ignore the bitrate
+    pub fn uses_bitrate(&self) -> bool {
+        matches!(self, AudioCodec::Aac | AudioCodec::Opus)
+    }
+
+    /// File extension of a container that can hold this codec
+    pub fn container(&self) -> &'static str {
+        match self {
+            AudioCodec::Pcm => "mov", // mp4 has no PCM mapping
+            _ => "mp4",
+        }
+    }
+
+    /// Compatibility caveat for this codec in its container, if any
+    pub fn container_warning(&self) -> Option<&'static str> {
+        match self {
+            AudioCodec::Opus => Some("Opus in MP4 won't play in QuickTime; VLC and browsers handle it"),
+            AudioCodec::Pcm => Some("PCM can't be stored in MP4, so recordings are saved as .mov"),
+            _ => None,
+        }
+    }
+}
+
+/// How MP4/MOV files are laid out on disk
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Mp4Layout {
+    Standard,
+    Faststart,
+    Fragmented,
+}
+
+impl Mp4Layout {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Mp4Layout::Standard => "Standard",
+            Mp4Layout::Faststart => "Optimize for web/streaming",
+            Mp4Layout::Fragmented => "Fragmented (crash-resilient)",
+        }
+    }
+
+    pub fn tooltip(&self) -> &'static str {
+        match self {
+            Mp4Layout::Standard => "Index written at the end. Fastest to stop; fine for local playback.",
+            Mp4Layout::Faststart => "Index moved to the front so playback starts before download. Stopping rewrites the whole file once.",
+            Mp4Layout::Fragmented => "Written in self-contained fragments, so a crash or power loss keeps everything recorded so far. Some editors seek slower in these files.",
+        }
+    }
+
+    fn movflags(&self) -> Option<&'static str> {
+        match self {
+            Mp4Layout::Standard => None,
+            Mp4Layout::Faststart => Some("faststart"),
+            Mp4Layout::Fragmented => Some("+frag_keyframe+empty_moov"),
+        }
+    }
+}
+
+/// Clockwise rotation applied to recordings, e.g. for mirrored phones in portrait
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Rotation {
+    #[default]
+    None,
+    Cw90,
+    Cw180,
+    Cw270,
+}
+
+impl Rotation {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Rotation::None => "0°",
+            Rotation::Cw90 => "90°",
+            Rotation::Cw180 => "180°",
+            Rotation::Cw270 => "270°",
+        }
+    }
+
+    /// The `-vf` filter; None for no rotation
+    fn filter(&self) -> Option<&'static str> {
+        match self {
+            Rotation::None => None,
+            Rotation::Cw90 => Some("transpose=clock"),
+            Rotation::Cw180 => Some("hflip,vflip"),
+            Rotation::Cw270 => Some("transpose=cclock"),
+        }
+    }
+
+    /// Size of a `width`x`height` frame after rotating
+    pub fn apply(&self, width: usize, height: usize) -> (usize, usize) {
+        match self {
+            Rotation::Cw90 | Rotation::Cw270 => (height, width),
+            Rotation::None | Rotation::Cw180 => (width, height),
+        }
+    }
+
+    /// Rotate an RGBA frame the way the `-vf` filter will, so previews match recordings
+    pub fn rotate_rgba(&self, rgba: &[u8], width: usize, height: usize) -> (Vec<u8>, usize, usize) {
+        if *self == Rotation::None {
+            return (rgba.to_vec(), width, height);
+        }
+        let (out_width, out_height) = self.apply(width, height);
+        let mut out = vec![0u8; out_width * out_height * 4];
+        for y in 0..out_height {
+            for x in 0..out_width {
+                let (src_x, src_y) = match self {
+                    Rotation::Cw90 => (y, height - 1 - x),
+                    Rotation::Cw180 => (width - 1 - x, height - 1 - y),
+                    Rotation::Cw270 => (width - 1 - y, x),
+                    Rotation::None => (x, y),
+                };
+                let src = (src_y * width + src_x) * 4;
+                let dst = (y * out_width + x) * 4;
+                out[dst..dst + 4].copy_from_slice(&rgba[src..src + 4]);
+            }
+        }
+        (out, out_width, out_height)
+    }
+}
+
+/// Part of a window to record, in window points like `WindowInfo`'s size
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CropRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub follow_resize: bool, // Scale with the window as it resizes instead of keeping its size
+}
+
+impl CropRegion {
+    /// The crop as `(x, y, width, height)` pixels of a `width`x`height` frame,
+    /// clamped inside it. `scale` is pixels per point in the first frame of the
+    /// recording, which was `reference` in size.
+    pub fn pixel_rect(
+        &self,
+        width: usize,
+        height: usize,
+        scale: f64,
+        reference: (usize, usize),
+    ) -> (usize, usize, usize, usize) {
+        let (sx, sy) = if self.follow_resize {
+            (
+                scale * width as f64 / reference.0.max(1) as f64,
+                scale * height as f64 / reference.1.max(1) as f64,
+            )
+        } else {
+            (scale, scale)
+        };
+        let x = ((self.x as f64 * sx).round() as usize).min(width.saturating_sub(1));
+        let y = ((self.y as f64 * sy).round() as usize).min(height.saturating_sub(1));
+        let w = ((self.width as f64 * sx).round() as usize).min(width.saturating_sub(x)).max(1);
+        let h = ((self.height as f64 * sy).round() as usize).min(height.saturating_sub(y)).max(1);
+        (x, y, w, h)
+    }
+}
+
+/// Fixed output frame that every capture is scaled into, letterboxed on
+/// `background` where the aspect ratios differ
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Canvas {
+    pub width: u32,
+    pub height: u32,
+    pub background: [u8; 3], // sRGB
+}
+
+impl Canvas {
+    /// Size of the frames piped to ffmpeg. The canvas is the recording's final
+    /// frame, so ffmpeg's quarter turns get it transposed.
+    pub fn stream_size(&self, rotation: Rotation) -> (usize, usize) {
+        rotation.apply(self.width as usize, self.height as usize)
+    }
+}
+
+/// How a stream gets the even dimensions yuv420p needs when the window or
+/// canvas has an odd side. Frames are cropped or padded to that size, never
+/// resampled, so content keeps its pixel positions.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum EvenSize {
+    /// Drop the last column or row
+    #[default]
+    Crop,
+    /// Repeat the last column or row
+    Pad,
+}
+
+impl EvenSize {
+    pub fn label(&self) -> &'static str {
+        match self {
+            EvenSize::Crop => "Crop 1 px",
+            EvenSize::Pad => "Pad 1 px",
+        }
+    }
+
+    /// The even size for a `width`x`height` frame, never below 2x2
+    pub fn apply(&self, width: usize, height: usize) -> (usize, usize) {
+        let even = |n: usize| match self {
+            EvenSize::Crop => n & !1,
+            EvenSize::Pad => n + (n & 1),
+        };
+        (even(width).max(2), even(height).max(2))
+    }
+}
+
+/// Pixel format frames are encoded in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum PixelFormat {
+    #[default]
+    Yuv420p,
+    /// 4:2:0 like yuv420p, in the layout VideoToolbox encodes from, which
+    /// saves it a conversion
+    Nv12,
+    /// Full chroma resolution, so colored text stays sharp. libx264 only
+    /// (High 4:4:4 profile), and many players can't decode it.
+    Yuv444p,
+}
+
+impl PixelFormat {
+    pub fn label(&self) -> &'static str {
+        match self {
+            PixelFormat::Yuv420p => "yuv420p (most compatible)",
+            PixelFormat::Nv12 => "nv12 (faster with VideoToolbox)",
+            PixelFormat::Yuv444p => "yuv444p (sharpest color)",
+        }
+    }
+
+    fn ffmpeg_name(&self) -> &'static str {
+        match self {
+            PixelFormat::Yuv420p => "yuv420p",
+            PixelFormat::Nv12 => "nv12",
+            PixelFormat::Yuv444p => "yuv444p",
+        }
+    }
+
+    pub fn supported_by(&self, encoder: VideoEncoder) -> bool {
+        match self {
+            PixelFormat::Yuv420p | PixelFormat::Nv12 => true,
+            PixelFormat::Yuv444p => encoder == VideoEncoder::Libx264,
+        }
+    }
+
+    /// This format if `encoder` can encode it, else yuv420p
+    pub fn for_encoder(&self, encoder: VideoEncoder) -> PixelFormat {
+        if self.supported_by(encoder) { *self } else { PixelFormat::Yuv420p }
+    }
+}
+
+/// What ends a recording that has audio
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum StreamEnd {
+    /// The piped video defines the timeline. Audio never ends by itself, so
+    /// ffmpeg is interrupted once it has read the last frame.
+    #[default]
+    Video,
+    /// `-shortest`: whichever stream ends first ends the file, which can cut
+    /// the end of the video when the audio input stalls
+    Shortest,
+}
+
+impl StreamEnd {
+    pub fn label(&self) -> &'static str {
+        match self {
+            StreamEnd::Video => "When the video ends",
+            StreamEnd::Shortest => "At the shortest stream",
+        }
+    }
+
+    pub fn tooltip(&self) -> &'static str {
+        match self {
+            StreamEnd::Video => "Keeps every captured frame. The audio may run a moment past the last frame.",
+            StreamEnd::Shortest => "Trims to the shorter of video and audio. A stalled audio device can cut the end of the video.",
+        }
+    }
 }
 
 /// Builder for ffmpeg commands to separate concerns
@@ -27,11 +552,27 @@ pub struct FfmpegCommandBuilder {
     ffmpeg_path: PathBuf,
     width: usize,
     height: usize,
+    output_size: Option<(usize, usize)>, // Scaled size of the rotated frame; None keeps its size
+    rotation: Rotation,
     fps: i32,
-    bitrate_kbps: i32,
+    input_fps: i32, // Rate frames arrive on stdin; ffmpeg repeats them up to `fps`
+    rate_control: RateControl,
     output_path: PathBuf,
     encoder: VideoEncoder,
     audio_input_device: Option<String>,
+    second_audio_device: Option<String>,
+    pcm_input: Option<(PathBuf, PcmFormat)>, // Pipe the primary device is read from instead of avfoundation
+    metadata: Vec<(String, String)>, // Container tags
+    audio_codec: AudioCodec,
+    audio_bitrate_kbps: i32,
+    highpass_hz: Option<u32>,
+    lowpass_hz: Option<u32>,
+    mp4_layout: Mp4Layout,
+    stream_end: StreamEnd,
+    pixel_format: PixelFormat,
+    prores_profile: ProResProfile,
+    x264_preset: X264Preset,
+    segment_seconds: Option<u32>,
 }
 
 impl FfmpegCommandBuilder {
@@ -49,16 +590,193 @@ impl FfmpegCommandBuilder {
             ffmpeg_path,
             width,
             height,
+            output_size: None,
+            rotation: Rotation::None,
             fps,
-            bitrate_kbps,
+            input_fps: fps,
+            rate_control: RateControl::Bitrate(bitrate_kbps),
             output_path,
             encoder,
             audio_input_device,
+            second_audio_device: None,
+            pcm_input: None,
+            metadata: Vec::new(),
+            audio_codec: AudioCodec::Aac,
+            audio_bitrate_kbps: 192,
+            highpass_hz: Some(60),
+            lowpass_hz: Some(18000),
+            mp4_layout: Mp4Layout::Faststart,
+            stream_end: StreamEnd::Video,
+            pixel_format: PixelFormat::Yuv420p,
+            prores_profile: ProResProfile::Standard,
+            x264_preset: X264Preset::Veryfast,
+            segment_seconds: None,
+        }
+    }
+
+    /// Audio codec and bitrate; the bitrate is ignored by lossless codecs
+    pub fn audio_codec(mut self, codec: AudioCodec, bitrate_kbps: i32) -> Self {
+        self.audio_codec = codec;
+        self.audio_bitrate_kbps = bitrate_kbps;
+        self
+    }
+
+    /// Highpass/lowpass cutoffs for the audio filter chain; `None` leaves that filter out
+    pub fn audio_filters(mut self, highpass_hz: Option<u32>, lowpass_hz: Option<u32>) -> Self {
+        self.highpass_hz = highpass_hz;
+        self.lowpass_hz = lowpass_hz;
+        self
+    }
+
+    /// A second audio device, recorded as its own track rather than mixed in.
+    /// Only used together with the primary audio device.
+    pub fn second_audio_track(mut self, device_id: Option<String>) -> Self {
+        self.second_audio_device = device_id;
+        self
+    }
+
+    /// Read the primary audio device as s16le from this pipe rather than
+    /// opening it with avfoundation. Only used when an audio device is set.
+    pub fn pcm_audio_input(mut self, input: Option<(PathBuf, PcmFormat)>) -> Self {
+        self.pcm_input = input;
+        self
+    }
+
+    /// Container-level `key=value` tags. MP4/MOV keep arbitrary keys only
+    /// with `use_metadata_tags`, which is added to their movflags.
+    pub fn metadata(mut self, tags: Vec<(String, String)>) -> Self {
+        self.metadata = tags;
+        self
+    }
+
+    /// Scale the video to this size instead of encoding it at the input size
+    pub fn output_size(mut self, size: Option<(usize, usize)>) -> Self {
+        self.output_size = size;
+        self
+    }
+
+    /// Rotate the video clockwise; `output_size` then applies to the rotated frame
+    pub fn rotation(mut self, rotation: Rotation) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Rotation, output scaling, pixel format, audio codec, filter, track, stream end and MP4 layout options from a recording config
+    pub fn with_config(self, config: &RecordingConfig) -> Self {
+        let (width, height) = config.rotation.apply(self.width, self.height);
+        let output_size = config.output_size_for(width, height);
+        self.rotation(config.rotation)
+            .output_size(output_size)
+            .second_audio_track(config.second_audio_input_device.clone())
+            .audio_codec(config.audio_codec, config.audio_bitrate_kbps)
+            .audio_filters(config.audio_highpass_hz, config.audio_lowpass_hz)
+            .mp4_layout(config.mp4_layout)
+            .stream_end(config.stream_end)
+            .pixel_format(config.pixel_format)
+            .rate_control(config.rate_control)
+            .prores_profile(config.prores_profile)
+            .x264_preset(config.x264_preset)
+            .segment_seconds(config.segment_seconds)
+            .metadata(config.source_tags.clone())
+            .input_fps(config.capture_rate())
+    }
+
+    /// Rate frames are piped at, when below the output fps; `-vsync cfr`
+    /// repeats each one to fill the output rate
+    pub fn input_fps(mut self, fps: i32) -> Self {
+        self.input_fps = fps.clamp(1, self.fps.max(1));
+        self
+    }
+
+    /// Encoded pixel format; one the encoder can't take falls back to yuv420p
+    pub fn pixel_format(mut self, format: PixelFormat) -> Self {
+        self.pixel_format = format;
+        self
+    }
+
+    /// Target bitrate or constant quality; replaces the bitrate given to `new`
+    pub fn rate_control(mut self, rate_control: RateControl) -> Self {
+        self.rate_control = rate_control;
+        self
+    }
+
+    /// Profile used by the ProRes encoder; ignored by the others
+    pub fn prores_profile(mut self, profile: ProResProfile) -> Self {
+        self.prores_profile = profile;
+        self
+    }
+
+    /// Cut the output into files of this many seconds, numbered from
+    /// `_000`; the output path becomes the name they derive from
+    pub fn segment_seconds(mut self, seconds: Option<u32>) -> Self {
+        self.segment_seconds = seconds.map(|s| s.max(1));
+        self
+    }
+
+    /// Preset used by libx264; ignored by the others
+    pub fn x264_preset(mut self, preset: X264Preset) -> Self {
+        self.x264_preset = preset;
+        self
+    }
+
+    /// What ends the file when there is audio; ignored without audio
+    pub fn stream_end(mut self, stream_end: StreamEnd) -> Self {
+        self.stream_end = stream_end;
+        self
+    }
+
+    /// Layout of MP4/MOV output; ignored for other containers
+    pub fn mp4_layout(mut self, layout: Mp4Layout) -> Self {
+        self.mp4_layout = layout;
+        self
+    }
+
+    /// Output size after rotation: yuv420p needs even dimensions for every
+    /// encoder, so odd sizes are rounded down (never below 2x2)
+    fn even_dimensions(&self) -> (usize, usize) {
+        let (width, height) = self.output_size
+            .unwrap_or_else(|| self.rotation.apply(self.width, self.height));
+        ((width & !1).max(2), (height & !1).max(2))
+    }
+
+    /// `-b:v` clamped to VideoToolbox's limits, plus a peak rate when `peak`
+    /// is set, or `-q:v` in constant quality mode
+    fn videotoolbox_rate_args(&self, cmd: &mut Command, peak: bool) {
+        match self.rate_control {
+            RateControl::Bitrate(kbps) => {
+                let safe_bitrate = self.encoder.effective_bitrate_kbps(kbps);
+                cmd.arg("-b:v").arg(format!("{}k", safe_bitrate));
+                if peak {
+                    cmd.arg("-maxrate")
+                        .arg(format!("{}k", safe_bitrate + 1000))
+                        .arg("-bufsize")
+                        .arg(format!("{}k", safe_bitrate * 2));
+                }
+            }
+            RateControl::ConstantQuality(crf) => {
+                cmd.arg("-q:v").arg(RateControl::videotoolbox_quality(crf).to_string());
+            }
+        }
+    }
+
+    /// The `-af` chain: resampling for sync, the enabled cutoff filters, then gain
+    fn audio_filter_chain(&self) -> String {
+        let mut filters = vec!["aresample=async=1:min_hard_comp=0.100000:first_pts=0".to_string()];
+        if let Some(hz) = self.highpass_hz {
+            filters.push(format!("highpass=f={}:width_type=h:width=0.5", hz));
         }
+        if let Some(hz) = self.lowpass_hz {
+            filters.push(format!("lowpass=f={}:width_type=h:width=0.5", hz));
+        }
+        filters.push("volume=0.9".to_string());
+        filters.push("adelay=0|0".to_string()); // Delay compensation
+        filters.join(",")
     }
 
     pub fn build(&self) -> Command {
         let mut cmd = Command::new(&self.ffmpeg_path);
+        // Lets a later run find this encoder if the app dies while it runs
+        cmd.env(OWNER_ENV, std::process::id().to_string());
         cmd.arg("-hide_banner")
             .arg("-loglevel")
             .arg("warning")
@@ -72,21 +790,40 @@ impl FfmpegCommandBuilder {
             .arg("-s")
             .arg(format!("{}x{}", self.width, self.height))
             .arg("-r")
-            .arg(format!("{}", self.fps))
+            .arg(format!("{}", self.input_fps))
             .arg("-i")
             .arg("-");
 
-        // Add audio input if device is provided - this creates a second input stream
-        if self.audio_input_device.is_some() {
+        // Audio inputs follow the video input: the primary device, then an
+        // optional second device that becomes its own track
+        let audio_devices: Vec<&String> = self.audio_input_device.iter()
+            .chain(self.audio_input_device.as_ref().and(self.second_audio_device.as_ref()))
+            .collect();
+        for (track, device_id) in audio_devices.iter().enumerate() {
+            // A piped cpal capture stands in for the primary device
+            if let Some((pipe, format)) = self.pcm_input.as_ref().filter(|_| track == 0) {
+                cmd.arg("-f")
+                    .arg("s16le")
+                    .arg("-ar")
+                    .arg(format.sample_rate.to_string())
+                    .arg("-ac")
+                    .arg(format.channels.to_string())
+                    .arg("-i")
+                    .arg(pipe);
+                continue;
+            }
             // Use avfoundation on macOS for audio capture
             #[cfg(target_os = "macos")]
             {
-                // For macOS, map device names to ffmpeg device indices
-                let device_index = self.audio_input_device.as_ref()
-                    .and_then(|device_name| get_ffmpeg_device_index(device_name))
-                    .unwrap_or(2); // Default to MacBook Pro Microphone
+                // For macOS, map device names to ffmpeg device indices. Starting a
+                // recording checked they're connected; one unplugged since falls
+                // back to the first input rather than leaving a track out
+                let device_index = get_ffmpeg_device_index(device_id).unwrap_or_else(|e| {
+                    warn!("{:#}; using the first audio input", e);
+                    0
+                });
                 
-                info!("Using audio device index: {} for device: {:?}", device_index, self.audio_input_device);
+                info!("Using audio device index: {} for device: {:?}", device_index, device_id);
                 
                 cmd.arg("-f")
                     .arg("avfoundation")
@@ -96,6 +833,7 @@ impl FfmpegCommandBuilder {
             #[cfg(not(target_os = "macos"))]
             {
                 // For non-macOS platforms, use default audio input
+                let _ = device_id;
                 cmd.arg("-f")
                     .arg("pulse")
                     .arg("-i")
@@ -103,31 +841,32 @@ impl FfmpegCommandBuilder {
             }
         }
 
-        // Force CFR on output to match wall-clock emission
+        // Rotate first; `-s` then scales the rotated frame
+        if let Some(filter) = self.rotation.filter() {
+            cmd.arg("-vf").arg(filter);
+        }
+
+        // Force CFR on output to match wall-clock emission; scale to the output
+        // size, rounded to even
+        let (out_width, out_height) = self.even_dimensions();
+        let pixel_format = self.pixel_format.for_encoder(self.encoder);
+        if pixel_format != self.pixel_format {
+            warn!("{} can't encode {}, using yuv420p", self.encoder.label(), self.pixel_format.ffmpeg_name());
+        }
         cmd.arg("-vsync")
             .arg("cfr")
             .arg("-r")
             .arg(format!("{}", self.fps))
             .arg("-pix_fmt")
-            .arg("yuv420p");
+            .arg(pixel_format.ffmpeg_name())
+            .arg("-s")
+            .arg(format!("{}x{}", out_width, out_height));
 
         match self.encoder {
             VideoEncoder::H264VideoToolbox => {
-                // Ensure bitrate is within VideoToolbox limits and dimensions are valid
-                let safe_bitrate = self.bitrate_kbps.min(50000).max(500);
-                // Ensure dimensions are even numbers (required by VideoToolbox)
-                let safe_width = if self.width % 2 == 0 { self.width } else { self.width - 1 };
-                let safe_height = if self.height % 2 == 0 { self.height } else { self.height - 1 };
-                
-                cmd.arg("-c:v")
-                    .arg("h264_videotoolbox")
-                    .arg("-b:v")
-                    .arg(format!("{}k", safe_bitrate))
-                    .arg("-maxrate")
-                    .arg(format!("{}k", safe_bitrate + 1000))
-                    .arg("-bufsize")
-                    .arg(format!("{}k", safe_bitrate * 2))
-                    .arg("-g")
+                cmd.arg("-c:v").arg("h264_videotoolbox");
+                self.videotoolbox_rate_args(&mut cmd, true);
+                cmd.arg("-g")
                     .arg(format!("{}", self.fps * 2))
                     .arg("-profile:v")
                     .arg("high")
@@ -136,40 +875,31 @@ impl FfmpegCommandBuilder {
                     .arg("-allow_sw")
                     .arg("1")
                     .arg("-realtime")
-                    .arg("1")
-                    .arg("-s")
-                    .arg(format!("{}x{}", safe_width, safe_height));
+                    .arg("1");
             }
             VideoEncoder::H264VideoToolboxFallback => {
                 // More conservative VideoToolbox settings
-                let safe_bitrate = self.bitrate_kbps.min(20000).max(1000);
-                // Ensure dimensions are even numbers (required by VideoToolbox)
-                let safe_width = if self.width % 2 == 0 { self.width } else { self.width - 1 };
-                let safe_height = if self.height % 2 == 0 { self.height } else { self.height - 1 };
-                
-                cmd.arg("-c:v")
-                    .arg("h264_videotoolbox")
-                    .arg("-b:v")
-                    .arg(format!("{}k", safe_bitrate))
-                    .arg("-profile:v")
+                cmd.arg("-c:v").arg("h264_videotoolbox");
+                self.videotoolbox_rate_args(&mut cmd, false);
+                cmd.arg("-profile:v")
                     .arg("main")
                     .arg("-level")
                     .arg("3.1")
                     .arg("-allow_sw")
-                    .arg("1")
-                    .arg("-s")
-                    .arg(format!("{}x{}", safe_width, safe_height));
+                    .arg("1");
             }
             VideoEncoder::Libx264 => {
                 cmd.arg("-c:v")
                     .arg("libx264")
                     .arg("-preset")
-                    .arg("veryfast")
+                    .arg(self.x264_preset.ffmpeg_name())
                     .arg("-tune")
-                    .arg("zerolatency")
-                    .arg("-b:v")
-                    .arg(format!("{}k", self.bitrate_kbps))
-                    .arg("-g")
+                    .arg("zerolatency");
+                match self.rate_control {
+                    RateControl::Bitrate(kbps) => cmd.arg("-b:v").arg(format!("{}k", kbps)),
+                    RateControl::ConstantQuality(crf) => cmd.arg("-crf").arg(crf.to_string()),
+                };
+                cmd.arg("-g")
                     .arg(format!("{}", self.fps * 2))
                     .arg("-x264-params")
                     .arg(format!(
@@ -177,48 +907,144 @@ impl FfmpegCommandBuilder {
                         self.fps * 2,
                         self.fps
                     ));
+                if pixel_format == PixelFormat::Yuv444p {
+                    cmd.arg("-profile:v").arg("high444");
+                }
+            }
+            VideoEncoder::HevcVideoToolbox => {
+                cmd.arg("-c:v").arg("hevc_videotoolbox");
+                self.videotoolbox_rate_args(&mut cmd, true);
+                cmd.arg("-g")
+                    .arg(format!("{}", self.fps * 2))
+                    .arg("-profile:v")
+                    .arg("main")
+                    .arg("-allow_sw")
+                    .arg("1")
+                    .arg("-realtime")
+                    .arg("1");
+                // QuickTime only plays HEVC tagged hvc1 (ffmpeg defaults to
+                // hev1); Matroska rejects the tag
+                if self.output_path.extension().is_some_and(|ext| ext == "mp4" || ext == "mov") {
+                    cmd.arg("-tag:v").arg("hvc1");
+                }
+            }
+            VideoEncoder::ProResVideoToolbox => {
+                // Every frame is a keyframe and the profile sets the data
+                // rate, so there's no bitrate or GOP to pass
+                cmd.arg("-c:v")
+                    .arg("prores_videotoolbox")
+                    .arg("-profile:v")
+                    .arg(self.prores_profile.ffmpeg_name());
             }
         }
 
         // Add audio codec if device is provided
-        if self.audio_input_device.is_some() {
+        if !audio_devices.is_empty() {
             // Get optimal sample rate for the device to avoid conversion artifacts
-            let sample_rate = self.audio_input_device.as_ref()
-                .map(|device_id| get_optimal_sample_rate(device_id))
-                .unwrap_or(48000);
+            let sample_rate = match self.audio_codec {
+                AudioCodec::Opus => 48000, // libopus only accepts 48k and its divisors
+                _ => match &self.pcm_input {
+                    Some((_, format)) => format.sample_rate,
+                    None => self.audio_input_device.as_ref()
+                        .map(|device_id| get_optimal_sample_rate(device_id))
+                        .unwrap_or(48000),
+                },
+            };
+            
+            match self.audio_codec {
+                AudioCodec::Aac => {
+                    cmd.arg("-c:a")
+                        .arg("aac")
+                        .arg("-b:a")
+                        .arg(format!("{}k", self.audio_bitrate_kbps));
+                }
+                AudioCodec::Opus => {
+                    cmd.arg("-c:a")
+                        .arg("libopus")
+                        .arg("-b:a")
+                        .arg(format!("{}k", self.audio_bitrate_kbps));
+                }
+                AudioCodec::Alac => {
+                    cmd.arg("-c:a").arg("alac");
+                }
+                AudioCodec::Pcm => {
+                    cmd.arg("-c:a").arg("pcm_s16le");
+                }
+            }
             
-            cmd.arg("-c:a")
-                .arg("aac")
-                .arg("-b:a")
-                .arg("192k") // Higher bitrate for better quality
-                .arg("-ar")
+            cmd.arg("-ar")
                 .arg(format!("{}", sample_rate)) // Use device's optimal sample rate
                 .arg("-ac")
                 .arg("2") // Stereo
                 .arg("-af")
-                .arg("aresample=async=1:min_hard_comp=0.100000:first_pts=0,highpass=f=60:width_type=h:width=0.5,lowpass=f=18000:width_type=h:width=0.5,volume=0.9,adelay=0|0") // Improved filters with delay compensation
+                .arg(self.audio_filter_chain())
                 .arg("-map")
-                .arg("0:v") // Map video from first input (stdin)
-                .arg("-map")
-                .arg("1:a") // Map audio from second input (audio device)
-                .arg("-async")
+                .arg("0:v"); // Map video from first input (stdin)
+            
+            // Each audio input becomes a separate, titled track
+            for (track, device_id) in audio_devices.iter().enumerate() {
+                let title = device_name(device_id).unwrap_or_else(|| format!("Audio {}", track + 1));
+                cmd.arg("-map")
+                    .arg(format!("{}:a", track + 1))
+                    .arg(format!("-metadata:s:a:{}", track))
+                    .arg(format!("title={}", title));
+            }
+            
+            cmd.arg("-async")
                 .arg("1") // Audio sync method
                 .arg("-vsync")
                 .arg("cfr") // Constant frame rate for better sync
                 .arg("-copyts") // Copy timestamps to preserve sync
-                .arg("-start_at_zero") // Start timestamps at zero
-                .arg("-shortest"); // End when the shortest input ends
+                .arg("-start_at_zero"); // Start timestamps at zero
+            if self.stream_end == StreamEnd::Shortest {
+                cmd.arg("-shortest"); // End when the shortest input ends
+            }
         } else {
             // If no audio, just map the video stream
             cmd.arg("-map")
                 .arg("0:v");
         }
 
-        // MP4 with faststart for better compatibility
-        cmd.arg("-movflags")
-            .arg("faststart")
-            .arg(&self.output_path)
-            .stdout(Stdio::null())
+        for (key, value) in &self.metadata {
+            cmd.arg("-metadata").arg(format!("{}={}", key, value));
+        }
+
+        // MP4/MOV layout flags (faststart or fragmented)
+        let movflags = output_movflags(self.mp4_layout, !self.metadata.is_empty(), &self.output_path);
+
+        match self.segment_seconds {
+            Some(seconds) => {
+                // Segments can only start on a keyframe, so force one at each cut.
+                // The muxer picks the format from the pattern's extension and
+                // passes the layout flags on to each file.
+                cmd.arg("-force_key_frames")
+                    .arg(format!("expr:gte(t,n_forced*{})", seconds))
+                    .arg("-f")
+                    .arg("segment")
+                    .arg("-segment_time")
+                    .arg(seconds.to_string())
+                    .arg("-segment_start_number")
+                    .arg("0")
+                    .arg("-reset_timestamps")
+                    .arg("1")
+                    // Completed files, for `OutputSegment` to tell which one is open
+                    .arg("-segment_list")
+                    .arg(segment_list_path(&self.output_path))
+                    .arg("-segment_list_type")
+                    .arg("csv");
+                if let Some(flags) = movflags {
+                    cmd.arg("-segment_format_options").arg(format!("movflags={}", flags));
+                }
+                cmd.arg(segment_pattern(&self.output_path));
+            }
+            None => {
+                if let Some(flags) = movflags {
+                    cmd.arg("-movflags").arg(flags);
+                }
+                cmd.arg(&self.output_path);
+            }
+        }
+        cmd.stdout(Stdio::null())
             .stderr(Stdio::piped());
         cmd
     }
@@ -233,11 +1059,12 @@ fn spawn_ffmpeg_checked(
     bitrate_kbps: i32,
     out_path: &PathBuf,
     encoder: VideoEncoder,
-    audio_input_device: Option<String>,
+    config: &RecordingConfig,
+    audio_pipe: Option<&PcmCapture>,
 ) -> Result<Child> {
     // Log audio configuration for debugging
-    if audio_input_device.is_some() {
-        info!("Audio recording enabled with device: {:?}", audio_input_device);
+    if config.audio_input_device.is_some() {
+        info!("Audio recording enabled with device: {:?} ({:?})", config.audio_input_device, config.audio_codec);
     } else {
         info!("Audio recording disabled");
     }
@@ -250,14 +1077,12 @@ fn spawn_ffmpeg_checked(
         bitrate_kbps,
         out_path.clone(),
         encoder,
-        audio_input_device,
-    );
+        config.audio_input_device.clone(),
+    )
+    .with_config(config)
+    .pcm_audio_input(audio_pipe.map(|capture| (capture.pipe(), capture.format())));
     let mut cmd = builder.build();
-    info!("Executing ffmpeg command: {:?}", cmd);
-    
-    // Log the full command as a string for debugging
-    let cmd_str = format!("{:?}", cmd);
-    info!("Full ffmpeg command: {}", cmd_str);
+    info!("Executing ffmpeg command: {}", command_line(&cmd));
 
     let child = cmd
         .stdin(Stdio::piped())
@@ -270,29 +1095,164 @@ fn spawn_ffmpeg_checked(
     Ok(child)
 }
 
-/// Check if ffmpeg process failed due to VideoToolbox encoder issues
-fn is_videotoolbox_error(child: &mut Child) -> bool {
-    if let Ok(Some(status)) = child.try_wait() {
-        if !status.success() {
-            // Check stderr for VideoToolbox-specific errors
-            if let Some(stderr) = child.stderr.as_mut() {
-                let mut stderr_content = String::new();
-                if std::io::Read::read_to_string(stderr, &mut stderr_content).is_ok() {
-                    return stderr_content.contains("h264_videotoolbox") && 
-                           (stderr_content.contains("-12903") || 
-                            stderr_content.contains("-12902") ||
-                            stderr_content.contains("cannot create compression session") ||
-                            stderr_content.contains("cannot prepare encoder") ||
-                            stderr_content.contains("Error while opening encoder"));
-                }
-            }
-        }
+/// Render a command as a line that can be pasted into a POSIX shell
+pub fn command_line(cmd: &Command) -> String {
+    std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(|arg| shell_quote(&arg.to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Quote one argument for a POSIX shell. Arguments made only of characters
+/// the shell never interprets stay as they are; anything else (spaces, `|` in
+/// filter graphs, quotes, `$`...) is single-quoted, with embedded single
+/// quotes written as `'\''`.
+pub fn shell_quote(arg: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "_-+=/.,:@%".contains(c);
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
     }
-    false
 }
 
-/// Send quit signal to ffmpeg and wait for it to exit
-pub fn send_quit_and_wait(child: &mut Child) -> Result<()> {
+/// The ffmpeg command a recording of `info` would run, without capturing
+/// frames or spawning anything. Like a real recording, the frame size comes
+/// from a capture of the window when one succeeds. The command reads raw RGBA
+/// frames from stdin.
+pub fn dry_run_command(
+    ffmpeg: &Path,
+    info: &WindowInfo,
+    output_dir: Option<&PathBuf>,
+    custom_filename: Option<&str>,
+    config: &RecordingConfig,
+) -> std::result::Result<String, RecorderError> {
+    let config = &config.with_source_tags(info);
+    #[cfg(target_os = "macos")]
+    let captured = macos::capture_window_image(info.window_id).map(|(_, w, h)| (w, h));
+    #[cfg(not(target_os = "macos"))]
+    let captured = None;
+    let (width, height) =
+        captured.unwrap_or((info.width.max(2) as usize, info.height.max(2) as usize));
+    let (width, height) = stream_size(config, info, width, height);
+
+    let base_path = build_output_path(info, output_dir, config.folder_template.as_deref(), custom_filename, config.container_extension())
+        .map_err(RecorderError::Output)?;
+    // The real pipe only exists while recording; show where it would go
+    let pcm_input = match (&config.audio_input_device, config.audio_backend) {
+        (Some(device_id), AudioBackend::Cpal) => {
+            let format = pcm_format(device_id).map_err(RecorderError::Audio)?;
+            Some((PathBuf::from("<audio pipe>"), format))
+        }
+        _ => None,
+    };
+    let builder = FfmpegCommandBuilder::new(
+        ffmpeg.to_path_buf(),
+        width,
+        height,
+        config.fps.max(1),
+        config.bitrate_kbps(),
+        output_path_with_counter(&base_path, 1),
+        config.encoder,
+        config.audio_input_device.clone(),
+    )
+    .with_config(config)
+    .pcm_audio_input(pcm_input);
+    Ok(command_line(&builder.build()))
+}
+
+/// Encoder to retry with after `encoder` exited right at the start, or
+/// None if there's nothing left to try. `session_error` is whether
+/// VideoToolbox refused an encoding session (see `is_videotoolbox_session_error`).
+fn fallback_encoder(encoder: VideoEncoder, session_error: bool) -> Option<VideoEncoder> {
+    match encoder {
+        VideoEncoder::Libx264 => None,
+        // Conservative H.264 settings may still get a hardware session
+        VideoEncoder::H264VideoToolbox if session_error => Some(VideoEncoder::H264VideoToolboxFallback),
+        _ => Some(VideoEncoder::Libx264),
+    }
+}
+
+/// Whether ffmpeg's output says VideoToolbox couldn't open an encoding
+/// session, as opposed to ffmpeg failing for another reason
+fn is_videotoolbox_session_error(stderr: &str) -> bool {
+    let videotoolbox = stderr.contains("h264_videotoolbox") || stderr.contains("hevc_videotoolbox");
+    videotoolbox
+        && (stderr.contains("-12903")
+            || stderr.contains("-12902")
+            || stderr.contains("cannot create compression session")
+            || stderr.contains("cannot prepare encoder")
+            || stderr.contains("Error while opening encoder"))
+}
+
+/// Shortest wait for ffmpeg to finalize, extra wait per GB of output (moving
+/// the moov atom for faststart rewrites the whole file), and the cap on both
+const FINALIZE_BASE_TIMEOUT: Duration = Duration::from_secs(5);
+const FINALIZE_SECS_PER_GB: f64 = 5.0;
+const FINALIZE_MAX_GRACE: Duration = Duration::from_secs(120);
+
+/// Past the grace period ffmpeg is only killed once the output file hasn't
+/// changed for this long, and never waited on longer than the hard limit
+const FINALIZE_STALL_TIMEOUT: Duration = Duration::from_secs(5);
+const FINALIZE_HARD_LIMIT: Duration = Duration::from_secs(600);
+
+/// Without `-shortest` a live audio input keeps ffmpeg running after the
+/// video pipe closes, so it is interrupted this long after. One SIGINT makes
+/// it stop reading inputs, flush the encoders and write the trailer.
+const AUDIO_DRAIN_GRACE: Duration = Duration::from_secs(1);
+
+/// Decides when ffmpeg finalizing a file counts as hung. It gets a grace
+/// period scaled to the file size; after that it may keep going as long as
+/// the file keeps changing.
+struct FinalizeTimeout {
+    grace: Duration,
+    last_state: Option<(u64, SystemTime)>, // Output length and mtime at the last check
+    last_change: Duration, // Time since quitting at which `last_state` last changed
+}
+
+impl FinalizeTimeout {
+    fn new(file_len: u64) -> Self {
+        let extra = Duration::from_secs_f64(file_len as f64 / 1e9 * FINALIZE_SECS_PER_GB);
+        Self {
+            grace: (FINALIZE_BASE_TIMEOUT + extra).min(FINALIZE_MAX_GRACE),
+            last_state: None,
+            last_change: Duration::ZERO,
+        }
+    }
+
+    /// Whether to give up on ffmpeg, `elapsed` after quitting, given the output
+    /// file's current length and mtime (`None` if it can't be read)
+    fn expired(&mut self, elapsed: Duration, state: Option<(u64, SystemTime)>) -> bool {
+        if state != self.last_state {
+            self.last_state = state;
+            self.last_change = elapsed;
+        }
+        if elapsed >= FINALIZE_HARD_LIMIT {
+            return true;
+        }
+        elapsed >= self.grace && elapsed.saturating_sub(self.last_change) >= FINALIZE_STALL_TIMEOUT
+    }
+
+    /// Whether the output changed within the last stall window
+    fn still_writing(&self, elapsed: Duration) -> bool {
+        elapsed.saturating_sub(self.last_change) < FINALIZE_STALL_TIMEOUT
+    }
+}
+
+/// Length and mtime of a file, to tell whether ffmpeg is still writing it
+fn file_state(path: &Path) -> Option<(u64, SystemTime)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.len(), metadata.modified().ok()?))
+}
+
+/// Send quit signal to ffmpeg and wait for it to finalize `output`. Large
+/// files get longer before ffmpeg counts as hung and is killed, and it is
+/// never killed while it is still writing the file. With `live_audio`,
+/// ffmpeg is interrupted once it has had time to read the rest of the video.
+/// Errors if ffmpeg failed or had to be killed, in which case `output` may
+/// be unplayable.
+pub fn send_quit_and_wait(child: &mut Child, output: &Path, live_audio: bool) -> Result<()> {
     info!("Stopping ffmpeg process...");
 
     // Close stdin first to signal end of input
@@ -305,28 +1265,59 @@ pub fn send_quit_and_wait(child: &mut Child) -> Result<()> {
 
     // Wait for ffmpeg to finish processing and exit gracefully
     let start = Instant::now();
+    let file_len = file_state(output).map_or(0, |(len, _)| len);
+    let mut timeout = FinalizeTimeout::new(file_len);
+    let mut last_report = Duration::ZERO;
+    let mut interrupted = !live_audio;
+    let mut sent_interrupt = false;
+    let mut outcome = Ok(());
     loop {
         match child.try_wait() {
             Ok(Some(status)) => {
                 info!("ffmpeg exited with status: {:?}", status);
-                if !status.success() {
+                if sent_interrupt && exited_from_interrupt(status) {
+                    info!("ffmpeg finished the file after our interrupt");
+                } else if !status.success() {
                     error!("ffmpeg exited with error status: {:?}", status);
+                    outcome = Err(anyhow!("ffmpeg exited with {}", status));
                 }
                 break;
             }
             Ok(None) => {
-                if start.elapsed() > Duration::from_secs(5) {
-                    info!("ffmpeg didn't exit within 5s, force killing process");
+                let elapsed = start.elapsed();
+                if !interrupted && elapsed >= AUDIO_DRAIN_GRACE {
+                    interrupted = true;
+                    info!("ffmpeg still running after the video ended, interrupting its audio input");
+                    sent_interrupt = interrupt(child.id());
+                }
+                if timeout.expired(elapsed, file_state(output)) {
+                    warn!(
+                        "ffmpeg stopped making progress on {} after {:.0}s, force killing process",
+                        output.display(),
+                        elapsed.as_secs_f64()
+                    );
                     let _ = child.kill();
                     let _ = child.wait();
+                    outcome = Err(anyhow!("ffmpeg hung while finalizing and was killed"));
                     break;
                 }
+                if elapsed - last_report >= FINALIZE_BASE_TIMEOUT {
+                    last_report = elapsed;
+                    info!(
+                        "ffmpeg still finalizing {} ({:.1} MB) after {:.0}s{}",
+                        output.display(),
+                        file_len as f64 / 1e6,
+                        elapsed.as_secs_f64(),
+                        if timeout.still_writing(elapsed) { "" } else { ", file unchanged" }
+                    );
+                }
                 std::thread::sleep(Duration::from_millis(100));
             }
             Err(e) => {
                 error!("Error waiting for ffmpeg: {}", e);
                 let _ = child.kill();
                 let _ = child.wait();
+                outcome = Err(anyhow!("lost track of ffmpeg: {}", e));
                 break;
             }
         }
@@ -335,34 +1326,230 @@ pub fn send_quit_and_wait(child: &mut Child) -> Result<()> {
     // Give filesystem extra time to flush
     std::thread::sleep(Duration::from_millis(500));
     info!("ffmpeg process stopped");
+    outcome
+}
+
+/// ffmpeg writes the trailer and exits with 255 when a SIGINT stops it; a
+/// death by the signal itself counts the same
+fn exited_from_interrupt(status: std::process::ExitStatus) -> bool {
+    use std::os::unix::process::ExitStatusExt;
+    status.code() == Some(255) || status.signal() == Some(2)
+}
+
+/// ffprobe from the same install as `ffmpeg`, or else from PATH
+fn find_ffprobe(ffmpeg: &Path) -> Option<PathBuf> {
+    let sibling = ffmpeg.with_file_name("ffprobe");
+    if sibling.exists() {
+        return Some(sibling);
+    }
+    which::which("ffprobe").ok()
+}
+
+/// Check that a finished recording opens with ffprobe, has a video stream
+/// (and an audio stream with `has_audio`) and a non-zero duration. Catches
+/// empty files and missing moov atoms when they happen rather than at
+/// playback. Passes when ffprobe isn't installed.
+pub fn verify_output(ffmpeg: &Path, path: &Path, has_audio: bool) -> Result<()> {
+    let size = std::fs::metadata(path)
+        .with_context(|| format!("{} is missing", path.display()))?
+        .len();
+    if size == 0 {
+        return Err(anyhow!("the file is empty"));
+    }
+    let Some(ffprobe) = find_ffprobe(ffmpeg) else {
+        warn!("ffprobe not found next to {} or on PATH; not verifying {}", ffmpeg.display(), path.display());
+        return Ok(());
+    };
+    let output = Command::new(ffprobe)
+        .args(["-v", "error", "-show_entries", "format=duration:stream=codec_type", "-of", "default=noprint_wrappers=1"])
+        .arg(path)
+        .output()
+        .context("failed to run ffprobe")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr.lines().rev().find(|l| !l.trim().is_empty()).unwrap_or("no output");
+        return Err(anyhow!("ffprobe can't read it: {}", reason.trim()));
+    }
+
+    let (mut video, mut audio, mut duration) = (false, false, None);
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        match line.trim().split_once('=') {
+            Some(("codec_type", "video")) => video = true,
+            Some(("codec_type", "audio")) => audio = true,
+            Some(("duration", value)) => duration = value.parse::<f64>().ok(), // "N/A" for some live layouts
+            _ => {}
+        }
+    }
+    if !video {
+        return Err(anyhow!("it has no video stream"));
+    }
+    if has_audio && !audio {
+        return Err(anyhow!("it has no audio stream"));
+    }
+    if duration.is_some_and(|secs| secs <= 0.0) {
+        return Err(anyhow!("it has no duration"));
+    }
     Ok(())
 }
 
-/// Build output file path for recording
+/// `-movflags` for a recording written to `path`: its layout's flags, plus
+/// `use_metadata_tags` when it has container tags. `None` for containers
+/// other than MP4/MOV.
+pub fn output_movflags(layout: Mp4Layout, tagged: bool, path: &Path) -> Option<String> {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let mut movflags: Vec<&str> = layout.movflags().into_iter().collect();
+    if tagged {
+        movflags.push("+use_metadata_tags");
+    }
+    (!movflags.is_empty() && matches!(ext, "mp4" | "mov")).then(|| movflags.concat())
+}
+
+/// Embed chapter markers into a finished recording.
+/// Writes an FFMETADATA chapters file next to the output and remuxes it in with
+/// stream copy, which QuickTime shows as chapters. The file keeps its own
+/// tags, and `movflags` should be the ones it was recorded with. If the remux
+/// fails the chapters file is kept as a sidecar.
+pub fn write_chapters(
+    ffmpeg: &Path,
+    output_path: &Path,
+    markers: &[Duration],
+    total: Duration,
+    movflags: Option<&str>,
+) -> Result<()> {
+    if markers.is_empty() {
+        return Ok(());
+    }
+
+    let chapters_path = output_path.with_extension("chapters.txt");
+    std::fs::write(&chapters_path, chapters_metadata(markers, total))
+        .with_context(|| format!("failed to write chapters file: {}", chapters_path.display()))?;
+
+    let extension = output_path.extension().map(|e| e.to_string_lossy().into_owned()).unwrap_or_default();
+    let remux_path = output_path.with_extension(format!("chapters.{}", extension));
+    let mut cmd = Command::new(ffmpeg);
+    cmd.args(["-hide_banner", "-loglevel", "error", "-y", "-i"])
+        .arg(output_path)
+        .arg("-i")
+        .arg(&chapters_path)
+        .args(["-map", "0", "-map_metadata", "0", "-map_chapters", "1"])
+        .args(["-codec", "copy"]);
+    if let Some(flags) = movflags {
+        cmd.arg("-movflags").arg(flags);
+    }
+    let status = cmd
+        .arg(&remux_path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .with_context(|| "failed to spawn ffmpeg for chapter remux")?;
+
+    if !status.success() {
+        let _ = std::fs::remove_file(&remux_path);
+        return Err(anyhow!(
+            "chapter remux exited with {}; markers kept in {}",
+            status,
+            chapters_path.display()
+        ));
+    }
+
+    std::fs::rename(&remux_path, output_path)
+        .with_context(|| format!("failed to replace {}", output_path.display()))?;
+    let _ = std::fs::remove_file(&chapters_path);
+    info!("Embedded {} chapter markers into {}", markers.len(), output_path.display());
+    Ok(())
+}
+
+/// Markers in `[start, end)` of a recording, rebased onto a file that
+/// starts at `start`; `None` takes every marker after it
+pub fn file_markers(markers: &[Duration], start: Duration, end: Option<Duration>) -> Vec<Duration> {
+    markers.iter()
+        .filter(|m| **m >= start && end.is_none_or(|end| **m < end))
+        .map(|m| *m - start)
+        .collect()
+}
+
+/// FFMETADATA with one chapter from the start and one per marker,
+/// each running until the next (the last one until `total`)
+fn chapters_metadata(markers: &[Duration], total: Duration) -> String {
+    let mut starts: Vec<u128> = vec![0];
+    starts.extend(markers.iter().map(|m| m.as_millis()).filter(|&ms| ms > 0));
+    starts.sort_unstable();
+    starts.dedup();
+    let end_ms = total.as_millis().max(starts[starts.len() - 1] + 1);
+
+    let mut out = String::from(";FFMETADATA1\n");
+    for (i, start) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).copied().unwrap_or(end_ms);
+        let title = if i == 0 { "Start".to_string() } else { format!("Marker {}", i) };
+        out.push_str(&format!(
+            "[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+            start, end, title
+        ));
+    }
+    out
+}
+
+/// Size of the frames piped to ffmpeg for a window whose capture is
+/// `width`x`height`: its crop, or the canvas, made even per `even_size`
+pub fn stream_size(config: &RecordingConfig, info: &WindowInfo, width: usize, height: usize) -> (usize, usize) {
+    let (width, height) = match config.canvas {
+        Some(canvas) => canvas.stream_size(config.rotation),
+        None => FrameCrop::new(config.crop, info, width, height).map_or((width, height), |crop| crop.stream_size()),
+    };
+    config.even_size.apply(width, height)
+}
+
+/// Build output file path for recording, creating its folder
 pub fn build_output_path(
     info: &WindowInfo,
     output_dir: Option<&PathBuf>,
+    folder_template: Option<&str>,
     custom_filename: Option<&str>,
+    extension: &str,
 ) -> Result<PathBuf> {
+    let path = planned_output_path(info, output_dir, folder_template, custom_filename, extension);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create output directory: {}", dir.display()))?;
+    }
+    Ok(path)
+}
+
+/// A custom file name as it will be written: tokens expanded, then the
+/// characters file names can't contain removed. May come out empty.
+pub fn sanitize_custom_filename(custom_name: &str, info: &WindowInfo) -> String {
+    sanitize_filename::sanitize_with_options(
+        expand_path_tokens(custom_name, info, local_date()),
+        sanitize_filename::Options {
+            truncate: true,
+            ..Default::default()
+        },
+    )
+}
+
+/// Where `build_output_path` would put a recording started now, without
+/// touching the file system
+pub fn planned_output_path(
+    info: &WindowInfo,
+    output_dir: Option<&PathBuf>,
+    folder_template: Option<&str>,
+    custom_filename: Option<&str>,
+    extension: &str,
+) -> PathBuf {
     let ts = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap_or(Duration::from_secs(0))
         .as_secs();
+    let date = local_date();
 
     // Use custom filename or generate default
     let filename = if let Some(custom_name) = custom_filename {
-        // Sanitize custom filename and ensure .mp4 extension
-        let sanitized = sanitize_filename::sanitize_with_options(
-            custom_name,
-            sanitize_filename::Options {
-                truncate: true,
-                ..Default::default()
-            },
-        );
-        if sanitized.ends_with(".mp4") {
+        // Sanitize custom filename and ensure the container's extension
+        let sanitized = sanitize_custom_filename(custom_name, info);
+        if sanitized.ends_with(&format!(".{}", extension)) {
             sanitized
         } else {
-            format!("{}_{}.mp4", sanitized, ts)
+            format!("{}_{}.{}", sanitized, ts, extension)
         }
     } else {
         // Default auto-generated filename
@@ -374,25 +1561,597 @@ pub fn build_output_path(
             },
         );
         format!(
-            "recording_{}_{}_{}.mp4",
-            info.window_id, sanitized_title, ts
+            "recording_{}_{}_{}.{}",
+            info.window_id, sanitized_title, ts, extension
         )
     };
 
-    let base_dir = output_dir
+    let mut base_dir = output_dir
         .map(|d| d.to_path_buf())
         .or_else(|| std::env::current_dir().ok())
         .unwrap_or_else(|| PathBuf::from("."));
 
-    std::fs::create_dir_all(&base_dir)
-        .with_context(|| format!("failed to create output directory: {}", base_dir.display()))?;
+    // Dated (or per-app) subfolders; each `/`-separated part is sanitized so
+    // the template can't climb out of the output directory
+    if let Some(template) = folder_template {
+        for part in expand_path_tokens(template, info, date).split('/') {
+            let part = sanitize_filename::sanitize(part);
+            if !part.is_empty() && part != "." && part != ".." {
+                base_dir.push(part);
+            }
+        }
+    }
+
+    base_dir.join(filename)
+}
+
+/// Tokens `expand_path_tokens` replaces, for display in settings
+pub const PATH_TOKENS: &str = "{date} {year} {month} {day} {app} {bundle_id}";
+
+/// Replace date and window tokens in a file name or folder template. Dates
+/// are local: `{date}` is YYYY-MM-DD, `{year}`/`{month}`/`{day}` its parts,
+/// `{app}` is the window's owner and `{bundle_id}` its bundle identifier
+/// (the owner's name for processes without one). `{counter}` is left for
+/// `output_path_with_counter`.
+pub fn expand_path_tokens(template: &str, info: &WindowInfo, (year, month, day): (i32, u32, u32)) -> String {
+    template
+        .replace("{date}", &format!("{:04}-{:02}-{:02}", year, month, day))
+        .replace("{year}", &format!("{:04}", year))
+        .replace("{month}", &format!("{:02}", month))
+        .replace("{day}", &format!("{:02}", day))
+        .replace("{app}", &info.owner_name)
+        .replace("{bundle_id}", info.bundle_id.as_deref().unwrap_or(&info.owner_name))
+}
+
+/// Today's date as (year, month 1-12, day 1-31): local on macOS, UTC elsewhere
+fn local_date() -> (i32, u32, u32) {
+    #[cfg(target_os = "macos")]
+    {
+        macos::local_date()
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        let days = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or(Duration::from_secs(0))
+            .as_secs() as i64
+            / 86_400;
+        // Days since 1970-01-01 to a civil date, per Howard Hinnant's algorithm
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+        let year = (yoe + era * 400 + i64::from(month <= 2)) as i32;
+        (year, month, day)
+    }
+}
+
+/// The ffmpeg process currently receiving frames and the file it writes.
+/// A size-based split swaps in a new process for the next file.
+pub struct OutputSegment {
+    pub child: Child,
+    pub path: PathBuf, // With `segment_seconds`, the name the numbered files derive from
+    pub started_at: Instant,
+    pub live_audio: bool, // ffmpeg reads audio that only ends when interrupted, see `StreamEnd::Video`
+    pub segment_list: Option<PathBuf>, // ffmpeg's segment muxer cuts the output into numbered files and lists the finished ones here
+    pub earlier_files: Vec<PathBuf>, // Files of this recording that size splits finished before this one
+}
+
+impl OutputSegment {
+    /// The file ffmpeg is writing now and where it starts in this process's
+    /// output: `path` itself, or the numbered file after the last one the
+    /// segment muxer has listed as finished
+    pub fn current_file(&self) -> (PathBuf, Duration) {
+        let Some(list) = &self.segment_list else {
+            return (self.path.clone(), Duration::ZERO);
+        };
+        let finished = read_segment_list(list);
+        let start = finished.last().map_or(Duration::ZERO, |&(_, end)| end);
+        (segment_file(&self.path, finished.len() as u32), start)
+    }
+
+    /// The last file written and where it starts, once ffmpeg has exited and
+    /// so listed every file it wrote
+    pub fn last_file(&self) -> (PathBuf, Duration) {
+        let Some(list) = &self.segment_list else {
+            return (self.path.clone(), Duration::ZERO);
+        };
+        let finished = read_segment_list(list);
+        match finished.last() {
+            Some(&(start, _)) => (segment_file(&self.path, finished.len() as u32 - 1), start),
+            None => (segment_file(&self.path, 0), Duration::ZERO),
+        }
+    }
+
+    /// Every file the recording wrote, in order, once ffmpeg has exited: the
+    /// earlier split files, then `path` or each numbered file in the list
+    pub fn files(&self) -> Vec<PathBuf> {
+        let mut files = self.earlier_files.clone();
+        match &self.segment_list {
+            Some(list) => {
+                let count = read_segment_list(list).len().max(1) as u32;
+                files.extend((0..count).map(|index| segment_file(&self.path, index)));
+            }
+            None => files.push(self.path.clone()),
+        }
+        files
+    }
+}
+
+/// Where the segment muxer lists the files it finished for `path`. Kept out
+/// of the output folder; the name only needs to be unique per output path.
+pub fn segment_list_path(path: &Path) -> PathBuf {
+    use std::hash::{DefaultHasher, Hash, Hasher};
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    std::env::temp_dir().join(format!("screencast-segments-{:016x}.csv", hasher.finish()))
+}
+
+/// Start and end times of each file in a segment muxer CSV list, in order.
+/// A missing list means no file has been finished yet.
+fn read_segment_list(path: &Path) -> Vec<(Duration, Duration)> {
+    let Ok(data) = std::fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    data.lines()
+        .filter_map(|line| {
+            // The times are the last two fields; the quoted name before them may contain commas
+            let mut fields = line.rsplitn(3, ',');
+            let end = fields.next()?.trim().parse::<f64>().ok()?;
+            let start = fields.next()?.trim().parse::<f64>().ok()?;
+            Some((
+                Duration::try_from_secs_f64(start).ok()?,
+                Duration::try_from_secs_f64(end).ok()?,
+            ))
+        })
+        .collect()
+}
+
+/// Measurements published by a recording's capture loop
+#[derive(Clone, Debug, Default)]
+pub struct CaptureStats {
+    pub capture_fps: Option<f64>, // Highest rate window captures can sustain, from recent capture times
+    pub health: CaptureHealth,
+    pub last_capture: Option<Instant>, // Last successful window capture, as of the last publish
+    pub output_bytes: Option<u64>, // Size of the file being written; None if it can't be read
+    pub output_grew_at: Option<Instant>, // Last publish that saw the file grow
+}
+
+/// A recording's most recent frame as piped to ffmpeg: cropped or fitted to
+/// the stream size, before rotation, premultiplied like captures
+#[derive(Clone)]
+pub struct LatestFrame {
+    pub rgba: Arc<Vec<u8>>,
+    pub width: usize,
+    pub height: usize,
+}
 
-    Ok(base_dir.join(filename))
+/// Whether a recording is getting fresh frames from the window
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CaptureHealth {
+    #[default]
+    Fresh, // Every recent capture succeeded
+    Stale, // Some captures failed; the last good frame is being repeated
+    Failing, // No successful capture for a while (window closed or hidden)
 }
 
-/// Nearest-neighbor resize of RGBA buffer to a fixed size
+impl CaptureHealth {
+    /// Health for a window of capture attempts, given how long captures have
+    /// been failing without a single success
+    fn from_attempts(attempts: u32, failures: u32, failing_for: Option<Duration>) -> Self {
+        if failing_for.is_some_and(|d| d >= CAPTURE_FAILING_AFTER) {
+            CaptureHealth::Failing
+        } else if attempts > 0 && failures > 0 {
+            CaptureHealth::Stale
+        } else {
+            CaptureHealth::Fresh
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            CaptureHealth::Fresh => "Capturing fresh frames",
+            CaptureHealth::Stale => "Some captures failed; repeating the last frame",
+            CaptureHealth::Failing => "Captures keep failing; recording the last good frame",
+        }
+    }
+}
+
+/// Shared handles to a running recording, as returned by `start_ffmpeg_for_window`
+pub struct RecordingHandles {
+    pub segment: Arc<Mutex<OutputSegment>>,
+    pub stop_signal: Arc<AtomicBool>,
+    pub stats: Arc<Mutex<CaptureStats>>,
+    pub stderr_log: Arc<Mutex<OutputLog>>,
+    pub encoder: VideoEncoder, // The encoder that survived the fallback cascade
+    pub output_claim: OutputClaim, // Keeps other recordings off this output until dropped
+    pub latest_frame: Arc<Mutex<Option<LatestFrame>>>, // Published about once a second
+    pub verify: bool, // Check the last file with ffprobe once it is finalized
+    pub has_audio: bool, // The file should have an audio stream
+    pub markers: Arc<Mutex<Vec<Duration>>>, // Chapter markers, relative to the first file's start
+    pub movflags: Option<String>, // What the files were written with, for the chapter remux
+    pub emitter: Option<thread::JoinHandle<()>>, // Capture loop feeding ffmpeg; exits on `stop_signal`
+}
+
+impl RecordingHandles {
+    /// Stop a recording that never got going, and delete what it wrote
+    fn discard(self) {
+        self.stop_signal.store(true, Ordering::Relaxed);
+        let mut segment = self.segment.lock();
+        let _ = segment.child.kill();
+        let _ = segment.child.wait();
+        let _ = std::fs::remove_file(segment.current_file().0);
+        if let Some(list) = &segment.segment_list {
+            let _ = std::fs::remove_file(list);
+        }
+    }
+}
+
+/// Base output paths of recordings that are starting or running in this
+/// process, so that two of them never write the same file
+static CLAIMED_OUTPUTS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// A recording's hold on its base output path; released when dropped
+pub struct OutputClaim(PathBuf);
+
+impl OutputClaim {
+    /// Claim `path`, or its first `name-N.ext` variant that no other
+    /// recording holds
+    pub(crate) fn new(path: &Path) -> Self {
+        let mut claimed = CLAIMED_OUTPUTS.lock();
+        let path = (1..)
+            .map(|n| disambiguated_path(path, n))
+            .find(|candidate| !claimed.contains(candidate))
+            .expect("some numbered variant is free");
+        claimed.push(path.clone());
+        Self(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for OutputClaim {
+    fn drop(&mut self) {
+        CLAIMED_OUTPUTS.lock().retain(|path| *path != self.0);
+    }
+}
+
+/// `path` for `n == 1`, otherwise `stem-N.ext`
+fn disambiguated_path(path: &Path, n: u32) -> PathBuf {
+    if n <= 1 {
+        return path.to_path_buf();
+    }
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    match path.extension() {
+        Some(ext) => path.with_file_name(format!("{}-{}.{}", stem, n, ext.to_string_lossy())),
+        None => path.with_file_name(format!("{}-{}", stem, n)),
+    }
+}
+
+/// Path of the `counter`-th file of a recording: a `{counter}` token in the
+/// file name is replaced, otherwise files after the first get a `_NNN` suffix
+pub fn output_path_with_counter(path: &Path, counter: u32) -> PathBuf {
+    let has_token = path.file_name().is_some_and(|n| n.to_string_lossy().contains("{counter}"));
+    if counter <= 1 && !has_token {
+        return path.to_path_buf();
+    }
+    numbered_path(path, &format!("{:03}", counter))
+}
+
+/// Path of the `index`-th file (from 0) the segment muxer writes for `path`
+pub fn segment_file(path: &Path, index: u32) -> PathBuf {
+    numbered_path(path, &format!("{:03}", index))
+}
+
+/// Output pattern for the segment muxer, with `%` in the path escaped
+fn segment_pattern(path: &Path) -> PathBuf {
+    numbered_path(Path::new(&path.to_string_lossy().replace('%', "%%")), "%03d")
+}
+
+/// `path` with `number` in place of its `{counter}` token, or appended to the
+/// file stem as `_number`
+fn numbered_path(path: &Path, number: &str) -> PathBuf {
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    if name.contains("{counter}") {
+        return path.with_file_name(name.replace("{counter}", number));
+    }
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    match path.extension() {
+        Some(ext) => path.with_file_name(format!("{}_{}.{}", stem, number, ext.to_string_lossy())),
+        None => path.with_file_name(format!("{}_{}", stem, number)),
+    }
+}
+
+/// Everything the emitter needs to respawn ffmpeg for the next file of a size split
+struct SplitContext {
+    ffmpeg: PathBuf,
+    width: usize,
+    height: usize,
+    fps: i32,
+    bitrate_kbps: i32,
+    encoder: VideoEncoder,
+    config: RecordingConfig,
+    base_path: PathBuf,
+    limit_bytes: u64,
+    stderr_log: Arc<Mutex<OutputLog>>, // Shared by all files of the recording
+    markers: Arc<Mutex<Vec<Duration>>>, // Each finished file gets the ones that fall into it
+    recording_started: Instant, // What `markers` are relative to
+}
+
+impl SplitContext {
+    /// Estimated output bytes per second from the video and audio bitrates
+    fn bytes_per_sec(&self) -> f64 {
+        let track_kbps = if self.config.audio_codec.uses_bitrate() {
+            self.config.audio_bitrate_kbps
+        } else {
+            1536 // Lossless stereo 48 kHz, roughly
+        };
+        let tracks = match &self.config.audio_input_device {
+            Some(_) if self.config.second_audio_input_device.is_some() => 2,
+            Some(_) => 1,
+            None => 0,
+        };
+        (self.bitrate_kbps.max(0) + track_kbps * tracks) as f64 * 1000.0 / 8.0
+    }
+}
+
+/// Spawn ffmpeg for the next file of a split, swap it into the shared segment
+/// and finalize the previous process in the background. Returns the new stdin.
+fn start_next_segment(
+    segment: &Arc<Mutex<OutputSegment>>,
+    split: &SplitContext,
+    counter: u32,
+    audio_pipe: Option<&PcmCapture>,
+) -> Result<ChildStdin> {
+    let path = output_path_with_counter(&split.base_path, counter);
+    // The current ffmpeg keeps reading its pipe until its stdin closes
+    if let Some(capture) = audio_pipe {
+        capture.new_pipe().context("failed to create the next audio pipe")?;
+    }
+    let mut child = spawn_ffmpeg_checked(
+        &split.ffmpeg,
+        split.width,
+        split.height,
+        split.fps,
+        split.bitrate_kbps,
+        &path,
+        split.encoder,
+        &split.config,
+        audio_pipe,
+    )
+    .context("failed to spawn ffmpeg for next split file")?;
+    log_ffmpeg_stderr(&mut child, split.stderr_log.clone());
+    let stdin = child
+        .stdin
+        .take()
+        .ok_or_else(|| anyhow!("ffmpeg for next split file has no stdin"))?;
+
+    let mut segment = segment.lock();
+    let mut earlier_files = std::mem::take(&mut segment.earlier_files);
+    earlier_files.push(segment.path.clone());
+    let previous = std::mem::replace(
+        &mut *segment,
+        OutputSegment {
+            child,
+            path: path.clone(),
+            started_at: Instant::now(),
+            live_audio: split.config.audio_outlives_video(),
+            segment_list: None,
+            earlier_files,
+        },
+    );
+    drop(segment);
+    info!("Split recording: continuing in {}", path.display());
+
+    let start = previous.started_at.saturating_duration_since(split.recording_started);
+    let duration = previous.started_at.elapsed();
+    let markers = file_markers(&split.markers.lock(), start, Some(start + duration));
+    let ffmpeg = split.ffmpeg.clone();
+    let movflags = output_movflags(split.config.mp4_layout, !split.config.source_tags.is_empty(), &previous.path);
+    thread::spawn(move || {
+        let mut previous = previous;
+        let _ = send_quit_and_wait(&mut previous.child, &previous.path, previous.live_audio);
+        if let Err(e) = write_chapters(&ffmpeg, &previous.path, &markers, duration, movflags.as_deref()) {
+            error!("Failed to write chapter markers for {}: {}", previous.path.display(), e);
+        }
+        info!("Finalized split file {}", previous.path.display());
+    });
+
+    Ok(stdin)
+}
+
+/// The most recent lines ffmpeg wrote to stderr; older lines are dropped once
+/// `capacity` is reached
+#[derive(Clone, Debug, Default)]
+pub struct OutputLog {
+    lines: VecDeque<String>,
+    capacity: usize,
+    disk_full: bool, // ffmpeg reported running out of space, even if that line has been dropped
+}
+
+impl OutputLog {
+    pub fn new(capacity: usize) -> Self {
+        Self { lines: VecDeque::with_capacity(capacity), capacity, disk_full: false }
+    }
+
+    pub fn push(&mut self, line: String) {
+        self.disk_full |= is_disk_full_error(&line);
+        if self.capacity == 0 {
+            return;
+        }
+        if self.lines.len() == self.capacity {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+    }
+
+    /// Oldest first
+    pub fn lines(&self) -> impl Iterator<Item = &str> {
+        self.lines.iter().map(String::as_str)
+    }
+
+    pub fn last(&self) -> Option<&str> {
+        self.lines.back().map(String::as_str)
+    }
+
+    /// Whether ffmpeg failed to write because the disk (or quota) is full
+    pub fn disk_full(&self) -> bool {
+        self.disk_full
+    }
+}
+
+/// ffmpeg's messages for ENOSPC and EDQUOT, e.g.
+/// "av_interleaved_write_frame(): No space left on device"
+fn is_disk_full_error(line: &str) -> bool {
+    line.contains("No space left on device") || line.contains("Disk quota exceeded")
+}
+
+/// Why a recording into `path` stopped when the disk filled up
+fn disk_full_message(path: &Path, layout: Mp4Layout) -> String {
+    let mut message = format!("The disk is full; recording stopped, keeping what fit in {}", path.display());
+    if layout != Mp4Layout::Fragmented && path.extension().is_some_and(|ext| ext == "mp4" || ext == "mov") {
+        message.push_str(". The file may not play: the fragmented MP4 layout keeps partial recordings playable");
+    }
+    message
+}
+
+/// Write one whole frame to ffmpeg's stdin, blocking until ffmpeg has taken
+/// all of it, so stopping between frames never leaves it a partial frame.
+/// On failure returns why, from ffmpeg's last words in `log`; `disk_full`
+/// describes the failure when those say the disk filled up.
+fn write_frame(
+    writer: &mut impl Write,
+    frame: &[u8],
+    log: &Mutex<OutputLog>,
+    disk_full: impl FnOnce() -> String,
+) -> std::result::Result<(), String> {
+    let Err(e) = writer.write_all(frame) else {
+        return Ok(());
+    };
+    let reason = if e.kind() == std::io::ErrorKind::BrokenPipe {
+        "ffmpeg exited unexpectedly (broken pipe)".to_string()
+    } else {
+        format!("failed to write frame to ffmpeg: {}", e)
+    };
+    let log = log.lock();
+    if log.disk_full() {
+        return Err(disk_full());
+    }
+    Err(match log.last() {
+        Some(line) => format!("{}: {}", reason, line),
+        None => reason,
+    })
+}
+
+/// The emitter's `write_frame`: when the frame can't be sent, reports the
+/// recording of `window_id` as failed and returns false, as the emitter has
+/// to stop. Whoever holds the recording reaps it on `Failed`.
+pub(crate) fn emit_frame(
+    writer: &mut impl Write,
+    frame: &[u8],
+    log: &Mutex<OutputLog>,
+    disk_full: impl FnOnce() -> String,
+    events: &EventBus,
+    window_id: u64,
+) -> bool {
+    let Err(reason) = write_frame(writer, frame, log, disk_full) else {
+        return true;
+    };
+    error!("Recording of window {} failed: {}", window_id, reason);
+    events.emit(RecordingEvent::Failed { window_id, error: reason });
+    false
+}
+
+/// Log ffmpeg stderr in background (single reader) and keep the recent lines
+/// in `log`. The reader ends when ffmpeg exits and closes stderr.
+fn log_ffmpeg_stderr(child: &mut Child, log: Arc<Mutex<OutputLog>>) {
+    if let Some(stderr) = child.stderr.take() {
+        std::thread::spawn(move || {
+            let reader = BufReader::new(stderr);
+            for line in reader.lines().map_while(|l| l.ok()) {
+                let low = line.to_ascii_lowercase();
+                if low.contains("error") || low.contains("warning") {
+                    error!("ffmpeg: {}", line);
+                } else if line.contains("Stream") || line.contains("audio") || line.contains("Audio") {
+                    info!("ffmpeg: {}", line);
+                } else {
+                    debug!("ffmpeg: {}", line);
+                }
+                log.lock().push(line);
+            }
+        });
+    }
+}
+
+/// Bits spent per pixel of every frame at `bitrate_kbps`
+pub fn bits_per_pixel(bitrate_kbps: i32, width: usize, height: usize, fps: i32) -> f64 {
+    let pixels_per_sec = width.max(1) as f64 * height.max(1) as f64 * fps.max(1) as f64;
+    bitrate_kbps as f64 * 1000.0 / pixels_per_sec
+}
+
+/// Rough H.264 quality of a bits-per-pixel figure for screen content
+pub fn bpp_quality(bpp: f64) -> &'static str {
+    match bpp {
+        b if b < 0.04 => "low",
+        b if b < 0.08 => "fair",
+        b if b < 0.15 => "good",
+        b if b < 0.3 => "high",
+        _ => "very high (large files)",
+    }
+}
+
+/// Bitrate giving screen content good quality at this size and rate: 0.1
+/// bits per pixel, rounded to 500 kbps within what the bitrate field allows
+pub fn suggest_bitrate(width: usize, height: usize, fps: i32) -> i32 {
+    const TARGET_BPP: f64 = 0.1;
+    let pixels_per_sec = width as f64 * height as f64 * fps.max(1) as f64;
+    let kbps = pixels_per_sec * TARGET_BPP / 1000.0;
+    ((kbps / 500.0).round() as i32 * 500).clamp(500, 50000)
+}
+
+/// Target size for a `src_w`x`src_h` image given an optional width and height.
+/// With `keep_aspect`, a missing side is derived from the source ratio and
+/// when both are given the result fits inside them; otherwise missing sides
+/// keep the source size and the image may be stretched.
+pub fn scaled_size(
+    src_w: usize,
+    src_h: usize,
+    width: Option<usize>,
+    height: Option<usize>,
+    keep_aspect: bool,
+) -> (usize, usize) {
+    let (src_w, src_h) = (src_w.max(1), src_h.max(1));
+    if !keep_aspect {
+        return (width.unwrap_or(src_w).max(1), height.unwrap_or(src_h).max(1));
+    }
+    let for_width = |w: usize| {
+        let h = (src_h as f64 * w as f64 / src_w as f64).round() as usize;
+        (w.max(1), h.max(1))
+    };
+    let for_height = |h: usize| {
+        let w = (src_w as f64 * h as f64 / src_h as f64).round() as usize;
+        (w.max(1), h.max(1))
+    };
+    match (width, height) {
+        (None, None) => (src_w, src_h),
+        (Some(w), None) => for_width(w),
+        (None, Some(h)) => for_height(h),
+        // Whichever side is the tighter fit decides the scale
+        (Some(w), Some(h)) if w * src_h <= h * src_w => for_width(w),
+        (Some(_), Some(h)) => for_height(h),
+    }
+}
+
+/// Nearest-neighbor resize of RGBA buffer to a fixed size. A zero-sized or
+/// truncated source yields a zeroed buffer of the destination size.
 fn resize_rgba_nn(src: &[u8], sw: usize, sh: usize, dw: usize, dh: usize) -> Vec<u8> {
-    if sw == 0 || sh == 0 || dw == 0 || dh == 0 {
+    if sw == 0 || sh == 0 || dw == 0 || dh == 0 || src.len() < sw * sh * 4 {
         return vec![0u8; dw.saturating_mul(dh).saturating_mul(4)];
     }
     let mut dst = vec![0u8; dw * dh * 4];
@@ -415,33 +2174,214 @@ fn resize_rgba_nn(src: &[u8], sw: usize, sh: usize, dw: usize, dh: usize) -> Vec
     dst
 }
 
-/// Start ffmpeg process for window recording
+/// Scale an RGBA frame to fit a `cw`x`ch` canvas without distorting it and
+/// center it there on an opaque `background`
+fn letterbox_rgba(src: &[u8], sw: usize, sh: usize, cw: usize, ch: usize, background: [u8; 3]) -> Vec<u8> {
+    let scale = (cw as f64 / sw.max(1) as f64).min(ch as f64 / sh.max(1) as f64);
+    let fw = ((sw as f64 * scale).round() as usize).clamp(1, cw.max(1));
+    let fh = ((sh as f64 * scale).round() as usize).clamp(1, ch.max(1));
+    let fitted = resize_rgba_nn(src, sw, sh, fw, fh);
+    let mut canvas = [background[0], background[1], background[2], 255].repeat(cw * ch);
+    let (left, top) = ((cw - fw) / 2, (ch - fh) / 2);
+    for y in 0..fh {
+        let dst = ((top + y) * cw + left) * 4;
+        canvas[dst..dst + fw * 4].copy_from_slice(&fitted[y * fw * 4..(y + 1) * fw * 4]);
+    }
+    canvas
+}
+
+/// Crop or pad an RGBA frame to `dw`x`dh` without resampling: pixels keep
+/// their positions, and missing columns and rows repeat the last ones. A
+/// zero-sized or truncated source yields a zeroed buffer of the destination size.
+fn crop_or_pad_rgba(src: &[u8], sw: usize, sh: usize, dw: usize, dh: usize) -> Vec<u8> {
+    if sw == 0 || sh == 0 || src.len() < sw * sh * 4 {
+        return vec![0u8; dw.saturating_mul(dh).saturating_mul(4)];
+    }
+    let copied = sw.min(dw) * 4;
+    let mut dst = vec![0u8; dw * dh * 4];
+    for y in 0..dh {
+        let src_row = &src[y.min(sh - 1) * sw * 4..][..sw * 4];
+        let dst_row = &mut dst[y * dw * 4..(y + 1) * dw * 4];
+        dst_row[..copied].copy_from_slice(&src_row[..copied]);
+        let last = &src_row[copied - 4..copied];
+        for pixel in dst_row[copied..].chunks_exact_mut(4) {
+            pixel.copy_from_slice(last);
+        }
+    }
+    dst
+}
+
+/// Bring a `w`x`h` frame to the stream size: letterboxed onto the canvas
+/// background when there is one, cropped or padded when it's only off by the
+/// odd pixel `EvenSize` rounded away, otherwise stretched
+fn fit_to_stream(src: &[u8], w: usize, h: usize, stream: (usize, usize), canvas: Option<Canvas>) -> Vec<u8> {
+    match canvas {
+        Some(canvas) => letterbox_rgba(src, w, h, stream.0, stream.1, canvas.background),
+        None if w.abs_diff(stream.0) <= 1 && h.abs_diff(stream.1) <= 1 => {
+            crop_or_pad_rgba(src, w, h, stream.0, stream.1)
+        }
+        None => resize_rgba_nn(src, w, h, stream.0, stream.1),
+    }
+}
+
+/// Copy the `(x, y, width, height)` rectangle out of an RGBA frame `sw` pixels
+/// wide. Rows the source doesn't have stay zeroed.
+fn crop_rgba(src: &[u8], sw: usize, (x, y, w, h): (usize, usize, usize, usize)) -> Vec<u8> {
+    let mut dst = vec![0u8; w * h * 4];
+    for row in 0..h {
+        let start = ((y + row) * sw + x) * 4;
+        if let Some(line) = src.get(start..start + w * 4) {
+            dst[row * w * 4..(row + 1) * w * 4].copy_from_slice(line);
+        }
+    }
+    dst
+}
+
+/// Cuts a recording's `CropRegion` out of each captured frame
+#[derive(Clone, Copy)]
+struct FrameCrop {
+    region: CropRegion,
+    scale: f64, // Pixels per window point
+    reference: (usize, usize), // Size of the first frame
+}
+
+impl FrameCrop {
+    /// Crop for a recording of `info` whose first frame is `width`x`height`
+    fn new(region: Option<CropRegion>, info: &WindowInfo, width: usize, height: usize) -> Option<Self> {
+        region.map(|region| FrameCrop {
+            region,
+            scale: width as f64 / info.width.max(1) as f64,
+            reference: (width, height),
+        })
+    }
+
+    /// Size of the cropped first frame, which the stream keeps for the whole recording
+    fn stream_size(&self) -> (usize, usize) {
+        let (_, _, w, h) = self.region.pixel_rect(self.reference.0, self.reference.1, self.scale, self.reference);
+        (w, h)
+    }
+
+    fn apply(&self, buffer: &[u8], width: usize, height: usize) -> (Vec<u8>, usize, usize) {
+        let rect = self.region.pixel_rect(width, height, self.scale, self.reference);
+        (crop_rgba(buffer, width, rect), rect.2, rect.3)
+    }
+}
+
+/// How long to pause captures after an unchanged frame when skipping static frames
+const STATIC_CAPTURE_BACKOFF: Duration = Duration::from_millis(200);
+
+/// How long captures have to fail without a success before health turns to `Failing`
+const CAPTURE_FAILING_AFTER: Duration = Duration::from_secs(3);
+
+/// Start ffmpeg process for window recording.
+/// `config` carries the resolved fps/bitrate for this recording. `events` gets
+/// `Started` or `Failed` for this call, and `Failed` again if the frame pipe
+/// to ffmpeg breaks mid-recording.
 pub fn start_ffmpeg_for_window(
     ffmpeg: &PathBuf,
     info: &WindowInfo,
-    fps: i32,
-    bitrate_kbps: i32,
     output_dir: Option<&PathBuf>,
     custom_filename: Option<&str>,
     config: &crate::recorder::RecordingConfig,
-) -> Result<(Child, Arc<AtomicBool>, PathBuf)> {
-    let out_path = build_output_path(info, output_dir, custom_filename)?;
+    events: &EventBus,
+    cancel: &AtomicBool,
+) -> std::result::Result<RecordingHandles, RecorderError> {
+    let result = spawn_recording(ffmpeg, info, output_dir, custom_filename, config, events, cancel)
+        .and_then(|handles| {
+            // Canceled after the last probe: drop the recording before anyone hears of it
+            if cancel.load(Ordering::Relaxed) {
+                info!("Start of recording for window {} canceled", info.window_id);
+                handles.discard();
+                return Err(RecorderError::Canceled);
+            }
+            Ok(handles)
+        });
+    match &result {
+        Ok(handles) => events.emit(RecordingEvent::Started {
+            window_id: info.window_id,
+            path: handles.segment.lock().current_file().0,
+        }),
+        // Whoever canceled already knows; it isn't a failure to retry
+        Err(RecorderError::Canceled) => {}
+        Err(e) => events.emit(RecordingEvent::Failed {
+            window_id: info.window_id,
+            error: e.to_string(),
+        }),
+    }
+    result
+}
+
+fn spawn_recording(
+    ffmpeg: &PathBuf,
+    info: &WindowInfo,
+    output_dir: Option<&PathBuf>,
+    custom_filename: Option<&str>,
+    config: &crate::recorder::RecordingConfig,
+    events: &EventBus,
+    cancel: &AtomicBool,
+) -> std::result::Result<RecordingHandles, RecorderError> {
+    if !ffmpeg.exists() {
+        return Err(RecorderError::FfmpegMissing);
+    }
+    let config = &config.with_source_tags(info);
+    let fps = config.fps.max(1);
+    let bitrate_kbps = config.bitrate_kbps();
+    if config.audio_input_device.is_some() {
+        if let Some(warning) = config.audio_codec.container_warning() {
+            warn!("{}", warning);
+        }
+    }
+    let base_path = build_output_path(info, output_dir, config.folder_template.as_deref(), custom_filename, config.container_extension())
+        .map_err(RecorderError::Output)?;
+    // A custom name without tokens can match another recording's file
+    let output_claim = OutputClaim::new(&base_path);
+    if output_claim.path() != base_path {
+        warn!(
+            "{} is already being recorded to; writing {} instead",
+            base_path.display(),
+            output_claim.path().display()
+        );
+    }
+    let base_path = output_claim.path().to_path_buf();
+    // The segment muxer numbers its own files from the base name
+    let out_path = match config.segment_seconds {
+        Some(_) => base_path.clone(),
+        None => output_path_with_counter(&base_path, 1),
+    };
+    let first_file = match config.segment_seconds {
+        Some(_) => {
+            // A list left by a crashed run would make finished files of the new ones
+            let _ = std::fs::remove_file(segment_list_path(&out_path));
+            segment_file(&out_path, 0)
+        }
+        None => out_path.clone(),
+    };
     info!(
         "Recording window {} ({}x{}) -> {}",
         info.window_id,
         info.width,
         info.height,
-        out_path.display()
+        first_file.display()
     );
 
     #[cfg(target_os = "macos")]
     {
+        if !macos::has_screen_capture_access() {
+            return Err(RecorderError::PermissionDenied);
+        }
+
         // First capture to discover actual size and seed a frame
-        let (mut actual_w, mut actual_h, mut last_frame) =
+        let (actual_w, actual_h, mut last_frame) =
             if let Some((buffer, w, h)) = macos::capture_window_image(info.window_id) {
                 info!("Detected actual window dimensions: {}x{}", w, h);
                 (w, h, Some(buffer))
             } else {
+                let still_listed = macos::list_windows()
+                    .map(|windows| windows.iter().any(|w| w.window_id == info.window_id))
+                    .unwrap_or(true);
+                if !still_listed {
+                    return Err(RecorderError::WindowGone(info.window_id));
+                }
                 warn!("Failed to capture window for dimensions; using stored values");
                 (
                     info.width.max(2) as usize,
@@ -450,27 +2390,80 @@ pub fn start_ffmpeg_for_window(
                 )
             };
 
-        // Enforce even dimensions for YUV420 encoders
-        if actual_w % 2 != 0 {
-            actual_w += 1;
+        // Frames go to ffmpeg at the canvas size (transposed for a quarter
+        // turn), or else the window's own size or its crop's, cropped or
+        // padded to the even dimensions yuv420p needs so the encoder never
+        // resamples by a pixel
+        let frame_crop = FrameCrop::new(config.crop, info, actual_w, actual_h);
+        let (source_w, source_h) = frame_crop.map_or((actual_w, actual_h), |crop| crop.stream_size());
+        let canvas = config.canvas;
+        let (expected_w, expected_h) = stream_size(config, info, actual_w, actual_h);
+        info!("Fixed stream size: {}x{}", expected_w, expected_h);
+        if let Some((low, high)) = macos::mixed_display_scales(info) {
+            warn!(
+                "Window {} spans displays at {}x and {}x scale; the part on one of them will be resampled",
+                info.window_id, low, high
+            );
         }
-        if actual_h % 2 != 0 {
-            actual_h += 1;
+        if let Some(max) = config.max_capture_dimension {
+            let (rotated_w, rotated_h) = config.rotation.apply(expected_w, expected_h);
+            if let Some((w, h)) = config.output_size_for(rotated_w, rotated_h) {
+                if w.max(h) == max as usize && expected_w.max(expected_h) > max as usize {
+                    info!(
+                        "Window {} is {}x{}, above the {} px limit; encoding at {}x{}",
+                        info.window_id, expected_w, expected_h, max, w, h
+                    );
+                }
+            }
         }
 
-        let expected_w = actual_w;
-        let expected_h = actual_h;
-        info!("Fixed stream size: {}x{}", expected_w, expected_h);
-
-        // Normalize the seeded frame if it doesn't match expected size
+        // Normalize the seeded frame if it doesn't match the window's size
         if let Some(ref buf) = last_frame {
             // We know the real w,h from the capture above; if mismatch, normalize
             if let Some((_, w, h)) = macos::capture_window_image(info.window_id) {
-                if w != expected_w || h != expected_h {
-                    last_frame = Some(resize_rgba_nn(buf, w, h, expected_w, expected_h));
+                if w != actual_w || h != actual_h {
+                    last_frame = Some(resize_rgba_nn(buf, w, h, actual_w, actual_h));
                 }
             }
         }
+        if let Some(crop) = frame_crop {
+            last_frame = last_frame.map(|buf| crop.apply(&buf, actual_w, actual_h).0);
+        }
+        if (source_w, source_h) != (expected_w, expected_h) {
+            last_frame = last_frame.map(|buf| fit_to_stream(&buf, source_w, source_h, (expected_w, expected_h), canvas));
+        }
+        // Previews share the frame itself; a new capture replaces the Arc
+        let mut last_frame = last_frame.map(Arc::new);
+
+        // cpal audio is opened before ffmpeg so each spawn below, including the
+        // encoder fallbacks, finds its pipe waiting
+        // Devices ffmpeg opens itself are looked up by name when it's started;
+        // fail here, naming a missing one, instead of recording another input
+        let avfoundation_inputs = config.audio_input_device.iter()
+            .filter(|_| config.audio_backend != AudioBackend::Cpal)
+            .chain(config.audio_input_device.as_ref().and(config.second_audio_input_device.as_ref()));
+        for device_id in avfoundation_inputs {
+            get_ffmpeg_device_index(device_id).map_err(RecorderError::Audio)?;
+        }
+        let audio_pipe = match (&config.audio_input_device, config.audio_backend) {
+            (Some(device_id), AudioBackend::Cpal) => Some(
+                PcmCapture::start(device_id, &info.window_id.to_string())
+                    .map_err(RecorderError::Audio)?,
+            ),
+            _ => None,
+        };
+
+        // Checked between encoder probes: kill the probe and drop its partial file
+        let abort_if_canceled = |child: &mut Child| {
+            if !cancel.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+            info!("Start of recording for window {} canceled", info.window_id);
+            let _ = child.kill();
+            let _ = child.wait();
+            let _ = std::fs::remove_file(&first_file);
+            Err(RecorderError::Canceled)
+        };
 
         // Use encoder from config
         let mut encoder = config.encoder;
@@ -482,35 +2475,44 @@ pub fn start_ffmpeg_for_window(
             bitrate_kbps,
             &out_path,
             encoder,
-            config.audio_input_device.clone(),
+            config,
+            audio_pipe.as_ref(),
         )
-        .context("failed to spawn ffmpeg (hardware)")?;
+        .context("failed to spawn ffmpeg (hardware)")
+        .map_err(RecorderError::Encoder)?;
 
-        // If ffmpeg exits early or has VideoToolbox errors, fall back to libx264
-        thread::sleep(Duration::from_millis(250));
-        if let Ok(Some(status)) = child.try_wait() {
-            error!("Hardware encoder process exited immediately: {:?}", status);
-            encoder = VideoEncoder::Libx264;
-            child = spawn_ffmpeg_checked(
-                ffmpeg,
-                expected_w,
-                expected_h,
-                fps,
-                bitrate_kbps,
-                &out_path,
-                encoder,
-                config.audio_input_device.clone(),
-            )
-            .context("failed to spawn ffmpeg (libx264 fallback)")?;
-            info!(
-                "Using software encoder (libx264) for window {}",
-                info.window_id
-            );
-        } else if is_videotoolbox_error(&mut child) {
-            error!("VideoToolbox encoder failed, trying fallback configuration");
-            // Kill the failed process
-            let _ = child.kill();
-            encoder = VideoEncoder::H264VideoToolboxFallback;
+        // If ffmpeg exits early, step down the fallback ladder unless the
+        // user wants exactly the encoder they picked
+        loop {
+            thread::sleep(Duration::from_millis(250));
+            abort_if_canceled(&mut child)?;
+            let Ok(Some(status)) = child.try_wait() else {
+                info!("{} started OK for window {}", encoder.label(), info.window_id);
+                break;
+            };
+            let mut stderr = String::new();
+            if let Some(pipe) = child.stderr.as_mut() {
+                let _ = std::io::Read::read_to_string(pipe, &mut stderr);
+            }
+            let reason = stderr.lines().rev().find(|l| !l.trim().is_empty()).unwrap_or("no output");
+            let session_error = is_videotoolbox_session_error(&stderr);
+            let next = fallback_encoder(encoder, session_error).filter(|_| config.encoder_fallback);
+            let Some(next) = next else {
+                let _ = std::fs::remove_file(&first_file);
+                // Say when it's VideoToolbox refusing a session, which fallback would work around
+                let hint = if session_error {
+                    " (VideoToolbox couldn't open an encoding session)"
+                } else {
+                    ""
+                };
+                let fallback = if config.encoder_fallback { "" } else { " and encoder fallback is off" };
+                return Err(RecorderError::Encoder(anyhow!(
+                    "{} exited with {}{}{}: {}",
+                    encoder.label(), status, fallback, hint, reason
+                )));
+            };
+            error!("{} exited immediately with {}: {}; trying {}", encoder.label(), status, reason, next.label());
+            encoder = next;
             child = spawn_ffmpeg_checked(
                 ffmpeg,
                 expected_w,
@@ -519,69 +2521,81 @@ pub fn start_ffmpeg_for_window(
                 bitrate_kbps,
                 &out_path,
                 encoder,
-                config.audio_input_device.clone(),
+                config,
+                audio_pipe.as_ref(),
             )
-            .context("failed to spawn ffmpeg (VideoToolbox fallback)")?;
-            
-            // Check if fallback also fails
-            thread::sleep(Duration::from_millis(250));
-            if let Ok(Some(status)) = child.try_wait() {
-                error!("VideoToolbox fallback also failed: {:?}, using libx264", status);
-                encoder = VideoEncoder::Libx264;
-                child = spawn_ffmpeg_checked(
-                    ffmpeg,
-                    expected_w,
-                    expected_h,
-                    fps,
-                    bitrate_kbps,
-                    &out_path,
-                    encoder,
-                    config.audio_input_device.clone(),
-                )
-                .context("failed to spawn ffmpeg (libx264 fallback)")?;
-                info!(
-                    "Using software encoder (libx264) for window {}",
-                    info.window_id
-                );
-            } else {
-                info!(
-                    "Using VideoToolbox fallback encoder for window {}",
-                    info.window_id
-                );
-            }
-        } else {
-            info!("Hardware encoder started OK for window {}", info.window_id);
-        }
-
-        // Log ffmpeg stderr in background (single reader)
-        if let Some(stderr) = child.stderr.take() {
-            std::thread::spawn(move || {
-                let reader = BufReader::new(stderr);
-                for line in reader.lines().filter_map(|l| l.ok()) {
-                    let low = line.to_ascii_lowercase();
-                    if low.contains("error") || low.contains("warning") {
-                        error!("ffmpeg: {}", line);
-                    } else if line.contains("Stream") || line.contains("audio") || line.contains("Audio") {
-                        info!("ffmpeg: {}", line);
-                    } else {
-                        debug!("ffmpeg: {}", line);
-                    }
-                }
+            .with_context(|| format!("failed to spawn ffmpeg ({})", encoder.label()))
+            .map_err(RecorderError::Encoder)?;
+        }
+        abort_if_canceled(&mut child)?;
+        if encoder != config.encoder {
+            events.emit(RecordingEvent::EncoderFellBack {
+                window_id: info.window_id,
+                from: config.encoder,
+                to: encoder,
             });
         }
 
+        let stderr_log = Arc::new(Mutex::new(OutputLog::new(config.stderr_log_lines)));
+        log_ffmpeg_stderr(&mut child, stderr_log.clone());
+
         // Create stop signal for the capture/emitter thread
         let stop_signal = Arc::new(AtomicBool::new(false));
 
-        // Start window capture thread that feeds frames to ffmpeg
+        // Start window capture thread that feeds frames to ffmpeg, at the
+        // capture rate; ffmpeg repeats frames up to `fps` if that's lower
         let window_id = info.window_id;
-        let fps_i32 = fps;
-        let fps_u64 = fps as u64;
+        let fps_i32 = config.capture_rate();
+        let fps_u64 = fps_i32 as u64;
+        let throttle_captures = fps_i32 < fps;
         let stop_signal_clone = stop_signal.clone();
+        let skip_static_frames = config.skip_static_frames;
+        let stats = Arc::new(Mutex::new(CaptureStats::default()));
+        let stats_clone = stats.clone();
+        let latest_frame: Arc<Mutex<Option<LatestFrame>>> = Arc::new(Mutex::new(None));
+        let latest_frame_clone = latest_frame.clone();
+        let events = events.clone();
+        let stderr_log_clone = stderr_log.clone();
+        let mp4_layout = config.mp4_layout;
+
+        let started_at = Instant::now();
+        let markers = Arc::new(Mutex::new(Vec::new()));
+
+        // Size-based splitting respawns ffmpeg with the encoder that won above;
+        // time segments replace it
+        if config.segment_seconds.is_some() && config.split_size_mb.is_some() {
+            warn!("Splitting into time segments; ignoring the size limit");
+        }
+        let split = config.split_size_mb.filter(|_| config.segment_seconds.is_none()).map(|mb| SplitContext {
+            ffmpeg: ffmpeg.clone(),
+            width: expected_w,
+            height: expected_h,
+            fps,
+            bitrate_kbps,
+            encoder,
+            config: config.clone(),
+            base_path: base_path.clone(),
+            limit_bytes: mb.saturating_mul(1024 * 1024),
+            stderr_log: stderr_log.clone(),
+            markers: markers.clone(),
+            recording_started: started_at,
+        });
 
         // Take stdin so we can write frames
-        if let Some(stdin) = child.stdin.take() {
-            std::thread::spawn(move || {
+        let stdin = child.stdin.take();
+        let segment = Arc::new(Mutex::new(OutputSegment {
+            child,
+            path: out_path.clone(),
+            started_at,
+            live_audio: config.audio_outlives_video(),
+            segment_list: config.segment_seconds.map(|_| segment_list_path(&out_path)),
+            earlier_files: Vec::new(),
+        }));
+        let segment_clone = segment.clone();
+
+        let mut emitter = None;
+        if let Some(stdin) = stdin {
+            emitter = Some(std::thread::spawn(move || {
                 info!(
                     "Starting direct window capture for window {} at {} FPS",
                     window_id, fps_i32
@@ -594,12 +2608,19 @@ pub fn start_ffmpeg_for_window(
                 let mut frame_count: u64 = 0;
                 let start_time = Instant::now();
 
-                let mut writer = BufWriter::with_capacity(1 << 20, stdin); // 1 MiB buffer
+                // Frames go straight to the pipe, one write_all per frame: nothing is
+                // left buffered at stop, so ffmpeg only ever sees whole rawvideo frames
+                // and closing stdin needs no final flush that could block
+                let mut writer = stdin;
 
                 // Seed a first frame if missing
                 if last_frame.is_none() {
                     loop {
                         if let Some((buffer, w, h)) = macos::capture_window_image(window_id) {
+                            let (buffer, w, h) = match frame_crop {
+                                Some(crop) => crop.apply(&buffer, w, h),
+                                None => (buffer, w, h),
+                            };
                             let normalized = if w == expected_w && h == expected_h {
                                 buffer
                             } else {
@@ -607,9 +2628,9 @@ pub fn start_ffmpeg_for_window(
                                     "Initial capture {}x{} != expected {}x{}, normalizing",
                                     w, h, expected_w, expected_h
                                 );
-                                resize_rgba_nn(&buffer, w, h, expected_w, expected_h)
+                                fit_to_stream(&buffer, w, h, (expected_w, expected_h), canvas)
                             };
-                            last_frame = Some(normalized);
+                            last_frame = Some(Arc::new(normalized));
                             break;
                         }
                         if stop_signal_clone.load(Ordering::Relaxed) {
@@ -620,20 +2641,64 @@ pub fn start_ffmpeg_for_window(
                     }
                 }
 
-                // Track last different source size to avoid log spam
-                let mut last_src_w: usize = expected_w;
-                let mut last_src_h: usize = expected_h;
+                // Track last different source size to avoid log spam; the
+                // window's own size is expected, even when it's odd
+                let mut last_src_w: usize = source_w;
+                let mut last_src_h: usize = source_h;
+
+                let mut split = split;
+                let mut split_counter: u32 = 1;
+                let mut segment_start = Instant::now();
+                let mut next_capture = Instant::now();
+                
+                // Time spent in captures over the current one-second window
+                let mut capture_time = Duration::ZERO;
+                let mut captures: u32 = 0;
+                let mut capture_failures: u32 = 0;
+                let mut failing_since: Option<Instant> = None;
+                let mut last_capture: Option<Instant> = None;
+                let mut stats_window = Instant::now();
+                let mut last_output = PathBuf::new();
 
                 loop {
                     if stop_signal_clone.load(Ordering::Relaxed) {
                         break;
                     }
 
+                    // Stop feeding ffmpeg once it can't write; finishing the
+                    // recording then keeps whatever made it to disk
+                    if stderr_log_clone.lock().disk_full() {
+                        let reason = disk_full_message(&segment_clone.lock().path, mp4_layout);
+                        error!("Recording of window {} failed: {}", window_id, reason);
+                        events.emit(RecordingEvent::Failed { window_id, error: reason });
+                        return;
+                    }
+
+                    // 0) Start the next file once the estimated size crosses the split threshold
+                    let split_due = split.as_ref().is_some_and(|s| {
+                        segment_start.elapsed().as_secs_f64() * s.bytes_per_sec() >= s.limit_bytes as f64
+                    });
+                    if split_due {
+                        let ctx = split.as_ref().expect("split_due implies split settings");
+                        match start_next_segment(&segment_clone, ctx, split_counter + 1, audio_pipe.as_ref()) {
+                            Ok(next_stdin) => {
+                                split_counter += 1;
+                                // Dropping the previous writer closes the old ffmpeg's stdin
+                                writer = next_stdin;
+                                segment_start = Instant::now();
+                            }
+                            Err(e) => {
+                                error!("Failed to split recording, continuing in current file: {}", e);
+                                split = None;
+                            }
+                        }
+                    }
+
                     // 1) Emit frames that are due (handles back-pressure correctly)
                     while Instant::now() >= next_due {
                         if let Some(ref buf) = last_frame {
-                            if let Err(e) = writer.write_all(buf) {
-                                error!("Failed to write frame to ffmpeg: {}", e);
+                            let disk_full = || disk_full_message(&segment_clone.lock().path, mp4_layout);
+                            if !emit_frame(&mut writer, buf, &stderr_log_clone, disk_full, &events, window_id) {
                                 return;
                             }
                             frame_count += 1;
@@ -653,25 +2718,94 @@ pub fn start_ffmpeg_for_window(
                     }
 
                     // 2) Try to refresh last_frame with a new capture if we have time
-                    if let Some((buffer, w, h)) = macos::capture_window_image(window_id) {
-                        if w != expected_w || h != expected_h {
-                            if w != last_src_w || h != last_src_h {
+                    //    (backing off while the window is static, if enabled)
+                    let capture_due = Instant::now() >= next_capture;
+                    let capture_started = Instant::now();
+                    if let Some((buffer, w, h)) = capture_due.then(|| macos::capture_window_image(window_id)).flatten() {
+                        capture_time += capture_started.elapsed();
+                        captures += 1;
+                        // A crop that follows resizes keeps its size in points;
+                        // either way the result is scaled to the stream size
+                        let (buffer, w, h) = match frame_crop {
+                            Some(crop) => crop.apply(&buffer, w, h),
+                            None => (buffer, w, h),
+                        };
+                        let frame = if w != expected_w || h != expected_h {
+                            if (w != last_src_w || h != last_src_h) && canvas.is_some() {
+                                debug!("Fitting {}x{} frames into the {}x{} canvas", w, h, expected_w, expected_h);
+                            } else if w != last_src_w || h != last_src_h {
                                 warn!(
                                     "Captured frame size {}x{} doesn't match expected {}x{} — normalizing",
                                     w, h, expected_w, expected_h
                                 );
-                                last_src_w = w;
-                                last_src_h = h;
                             }
-                            let normalized = resize_rgba_nn(&buffer, w, h, expected_w, expected_h);
-                            last_frame = Some(normalized);
+                            last_src_w = w;
+                            last_src_h = h;
+                            fit_to_stream(&buffer, w, h, (expected_w, expected_h), canvas)
                         } else {
-                            last_frame = Some(buffer);
                             last_src_w = w;
                             last_src_h = h;
+                            buffer
+                        };
+                        // Below the output fps, capture only as often as frames are sent
+                        if throttle_captures {
+                            next_capture = capture_started + frame_interval;
                         }
-                    } else {
+                        if skip_static_frames && last_frame.as_deref() == Some(&frame) {
+                            next_capture = next_capture.max(Instant::now() + STATIC_CAPTURE_BACKOFF);
+                        }
+                        last_frame = Some(Arc::new(frame));
+                        last_capture = Some(Instant::now());
+                        failing_since = None;
+                    } else if capture_due {
                         debug!("Window capture returned None; reusing last frame");
+                        capture_failures += 1;
+                        failing_since.get_or_insert_with(Instant::now);
+                    }
+                    
+                    // Publish the sustainable capture rate, capture health and
+                    // output size once a second
+                    if stats_window.elapsed() >= Duration::from_secs(1) {
+                        let health = CaptureHealth::from_attempts(
+                            captures + capture_failures,
+                            capture_failures,
+                            failing_since.map(|t| t.elapsed()),
+                        );
+                        let (output, _) = segment_clone.lock().current_file();
+                        let output_bytes = std::fs::metadata(&output).ok().map(|m| m.len());
+                        let mut stats = stats_clone.lock();
+                        // A new file starts out smaller than the last one
+                        if output_bytes > stats.output_bytes || output != last_output {
+                            stats.output_grew_at = Some(Instant::now());
+                        }
+                        last_output = output;
+                        stats.output_bytes = output_bytes;
+                        if captures > 0 {
+                            let capture_fps = captures as f64 / capture_time.as_secs_f64().max(f64::EPSILON);
+                            if capture_fps < fps_i32 as f64 {
+                                debug!(
+                                    "Capture of window {} sustains ~{:.0} fps, below target {}",
+                                    window_id, capture_fps, fps_i32
+                                );
+                            }
+                            stats.capture_fps = Some(capture_fps);
+                        }
+                        if health != stats.health {
+                            debug!("Capture health of window {} is now {:?}", window_id, health);
+                        }
+                        stats.health = health;
+                        stats.last_capture = last_capture;
+                        drop(stats);
+                        // Previews show this frame instead of capturing again
+                        *latest_frame_clone.lock() = last_frame.as_ref().map(|buf| LatestFrame {
+                            rgba: buf.clone(),
+                            width: expected_w,
+                            height: expected_h,
+                        });
+                        capture_time = Duration::ZERO;
+                        captures = 0;
+                        capture_failures = 0;
+                        stats_window = Instant::now();
                     }
 
                     // 3) Sleep a little until the next due time to avoid busy-wait
@@ -682,9 +2816,8 @@ pub fn start_ffmpeg_for_window(
                     }
                 }
 
-                if let Err(e) = writer.flush() {
-                    error!("Failed to flush frames to ffmpeg: {}", e);
-                }
+                // Close the pipe so ffmpeg sees EOF and starts finalizing
+                drop(writer);
 
                 let total_elapsed = start_time.elapsed();
                 let effective_fps = if total_elapsed.as_secs_f64() > 0.0 {
@@ -697,22 +2830,169 @@ pub fn start_ffmpeg_for_window(
                     frame_count, total_elapsed.as_secs_f64(), effective_fps, fps_i32
                 );
                 info!("Window capture thread stopped for window {}", window_id);
-            });
+            }));
         }
 
         info!(
             "Recording {} (ID: {}) -> {}",
             info.window_title,
             info.window_id,
-            out_path.display()
+            first_file.display()
         );
-        return Ok((child, stop_signal, out_path));
+        return Ok(RecordingHandles {
+            segment,
+            stop_signal,
+            stats,
+            stderr_log,
+            encoder,
+            output_claim,
+            latest_frame,
+            verify: config.verify_recordings,
+            has_audio: config.audio_input_device.is_some(),
+            markers,
+            movflags: output_movflags(config.mp4_layout, !config.source_tags.is_empty(), &out_path),
+            emitter,
+        });
     }
 
     #[cfg(not(target_os = "macos"))]
     {
-        Err(anyhow!("Window capture is only supported on macOS"))
+        Err(RecorderError::Unsupported)
+    }
+}
+
+/// How fast an encoder got through a synthetic recording
+#[derive(Clone, Debug)]
+pub struct EncoderBenchmark {
+    pub encoder: VideoEncoder,
+    pub fps: f64, // Frames encoded per second of wall time
+    pub size_bytes: u64,
+}
+
+/// Feed `frames` synthetic `width`x`height` frames through `encoder` as fast
+/// as it takes them, with the command a recording would use minus audio.
+/// The output goes to a temporary file that is removed afterwards.
+pub fn benchmark_encoder(
+    ffmpeg: &Path,
+    encoder: VideoEncoder,
+    width: usize,
+    height: usize,
+    frames: usize,
+) -> Result<EncoderBenchmark> {
+    let extension = encoder.required_container().map_or("mp4", |c| c.extension());
+    let output = std::env::temp_dir()
+        .join(format!("screencast-benchmark-{}-{}.{}", std::process::id(), encoder.short_label(), extension));
+    let mut cmd = FfmpegCommandBuilder::new(
+        ffmpeg.to_path_buf(),
+        width,
+        height,
+        30,
+        6000,
+        output.clone(),
+        encoder,
+        None,
+    )
+    .build();
+    let mut child = cmd.stdin(Stdio::piped()).spawn().context("failed to spawn ffmpeg")?;
+
+    // A diagonal pattern twice as wide as the frame; each frame is a window
+    // into it that moves a little, so the encoder always has motion to code
+    let pattern_w = width * 2;
+    let pattern: Vec<u8> = (0..height)
+        .flat_map(|y| (0..pattern_w).flat_map(move |x| {
+            let v = ((x + y) % 256) as u8;
+            [v, v.wrapping_mul(3), 255 - v, 255]
+        }))
+        .collect();
+    let mut frame = vec![0u8; width * height * 4];
+
+    let started = Instant::now();
+    let mut stdin = child.stdin.take().context("ffmpeg has no stdin")?;
+    for i in 0..frames {
+        let offset = (i * 8) % width;
+        for y in 0..height {
+            let src = (y * pattern_w + offset) * 4;
+            frame[y * width * 4..(y + 1) * width * 4].copy_from_slice(&pattern[src..src + width * 4]);
+        }
+        if stdin.write_all(&frame).is_err() {
+            break; // ffmpeg gave up; its stderr says why
+        }
     }
+    drop(stdin);
+    let result = child.wait_with_output().context("failed to wait for ffmpeg")?;
+    let elapsed = started.elapsed();
+    let size_bytes = std::fs::metadata(&output).map_or(0, |m| m.len());
+    let _ = std::fs::remove_file(&output);
+    if !result.status.success() {
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        let reason = stderr.lines().rev().find(|l| !l.trim().is_empty()).unwrap_or("no output");
+        return Err(anyhow!("ffmpeg exited with {}: {}", result.status, reason));
+    }
+    Ok(EncoderBenchmark {
+        encoder,
+        fps: frames as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+        size_bytes,
+    })
+}
+
+/// Environment variable holding the pid of the app that spawned an encoder
+pub const OWNER_ENV: &str = "SCREENCAST_OWNER_PID";
+
+/// An encoder left running by an earlier run of the app that has since exited
+#[derive(Clone, Debug)]
+pub struct OrphanedEncoder {
+    pub pid: u32,
+    pub output: Option<PathBuf>, // Last argument of its command line
+}
+
+/// Encoders tagged with `OWNER_ENV` whose owning app is no longer running
+pub fn find_orphaned_encoders() -> Vec<OrphanedEncoder> {
+    let mut system = System::new();
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::All,
+        true,
+        ProcessRefreshKind::new()
+            .with_cmd(UpdateKind::Always)
+            .with_environ(UpdateKind::Always),
+    );
+    let prefix = format!("{}=", OWNER_ENV);
+    system
+        .processes()
+        .values()
+        .filter(|process| {
+            let owner = process.environ().iter().find_map(|var| {
+                var.to_str()?.strip_prefix(&prefix)?.parse::<u32>().ok()
+            });
+            owner.is_some_and(|owner| {
+                owner != std::process::id() && system.process(Pid::from_u32(owner)).is_none()
+            })
+        })
+        .map(|process| OrphanedEncoder {
+            pid: process.pid().as_u32(),
+            output: process.cmd().last().map(PathBuf::from),
+        })
+        .collect()
+}
+
+/// Ask orphaned encoders to stop. SIGINT lets ffmpeg write its trailer, so
+/// their files stay playable; returns how many were signalled.
+pub fn stop_orphaned_encoders(encoders: &[OrphanedEncoder]) -> usize {
+    encoders.iter()
+        .filter(|encoder| {
+            info!("Stopping orphaned ffmpeg {}", encoder.pid);
+            interrupt(encoder.pid)
+        })
+        .count()
+}
+
+/// Send SIGINT to a process, or kill it where signals aren't supported;
+/// false if it is already gone
+fn interrupt(pid: u32) -> bool {
+    let pid = Pid::from_u32(pid);
+    let mut system = System::new();
+    system.refresh_processes(ProcessesToUpdate::Some(&[pid]), true);
+    system.process(pid)
+        .is_some_and(|process| process.kill_with(Signal::Interrupt).unwrap_or_else(|| process.kill()))
 }
 
 /// Find ffmpeg executable in common locations
@@ -735,3 +3015,693 @@ pub fn find_ffmpeg() -> Option<PathBuf> {
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(cmd: &Command) -> Vec<String> {
+        cmd.get_args().map(|a| a.to_string_lossy().into_owned()).collect()
+    }
+
+    /// A builder recording audio from a pipe, so building looks up no devices
+    fn builder_with_audio() -> FfmpegCommandBuilder {
+        FfmpegCommandBuilder::new(
+            PathBuf::from("ffmpeg"),
+            640,
+            480,
+            30,
+            6000,
+            PathBuf::from("out.mp4"),
+            VideoEncoder::Libx264,
+            Some("Test Mic".to_string()),
+        )
+        .pcm_audio_input(Some((PathBuf::from("audio.pipe"), PcmFormat { sample_rate: 48000, channels: 2 })))
+    }
+
+    #[test]
+    fn shortest_is_omitted_by_default() {
+        let args = args(&builder_with_audio().build());
+        assert!(args.iter().any(|a| a == "-map"), "audio is mapped: {:?}", args);
+        assert!(!args.iter().any(|a| a == "-shortest"), "{:?}", args);
+    }
+
+    #[test]
+    fn shortest_is_added_when_requested() {
+        let args = args(&builder_with_audio().stream_end(StreamEnd::Shortest).build());
+        assert!(args.iter().any(|a| a == "-shortest"), "{:?}", args);
+    }
+
+    /// Every `-s` in a command: the rawvideo input's size, then the output's
+    fn sizes(args: &[String]) -> Vec<(usize, usize)> {
+        args.windows(2)
+            .filter(|pair| pair[0] == "-s")
+            .map(|pair| {
+                let (w, h) = pair[1].split_once('x').expect("WxH");
+                (w.parse().unwrap(), h.parse().unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn odd_sizes_always_encode_at_even_dimensions() {
+        let encoders = [
+            VideoEncoder::H264VideoToolbox,
+            VideoEncoder::H264VideoToolboxFallback,
+            VideoEncoder::Libx264,
+            VideoEncoder::HevcVideoToolbox,
+            VideoEncoder::ProResVideoToolbox,
+        ];
+        for encoder in encoders {
+            for (w, h) in [(641, 481), (1279, 720), (3, 5), (1, 1)] {
+                for rotation in [Rotation::None, Rotation::Cw90] {
+                    let cmd = FfmpegCommandBuilder::new(
+                        PathBuf::from("ffmpeg"), w, h, 30, 6000, PathBuf::from("out.mov"), encoder, None,
+                    )
+                    .rotation(rotation)
+                    .build();
+                    let sizes = sizes(&args(&cmd));
+                    assert_eq!(sizes.len(), 2, "{:?} {}x{}", encoder, w, h);
+                    // Frames are piped at the window's own size
+                    assert_eq!(sizes[0], (w, h));
+                    let (ow, oh) = sizes[1];
+                    let (rw, rh) = rotation.apply(w.max(2), h.max(2));
+                    assert!(ow % 2 == 0 && oh % 2 == 0, "{:?} {}x{} -> {}x{}", encoder, w, h, ow, oh);
+                    assert!(rw - ow <= 1 && rh - oh <= 1, "{:?} {}x{} -> {}x{}", encoder, w, h, ow, oh);
+                }
+            }
+        }
+
+        let scaled = builder_with_audio().output_size(Some((853, 481))).build();
+        assert_eq!(sizes(&args(&scaled))[1], (852, 480));
+    }
+
+    /// What a shell running `line` passes to `printf '%s\n'`, one argument per line
+    fn shell_args(line: &str) -> Vec<String> {
+        let output = Command::new("sh").arg("-c").arg(line).output().unwrap();
+        assert!(output.status.success(), "{}: {}", line, String::from_utf8_lossy(&output.stderr));
+        String::from_utf8(output.stdout).unwrap().lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn command_lines_survive_the_shell() {
+        let cmd = FfmpegCommandBuilder::new(
+            PathBuf::from("ffmpeg"),
+            641,
+            480,
+            30,
+            6000,
+            PathBuf::from("/Users/me/Screen Recordings/Safari — it's $HOME (1).mp4"),
+            VideoEncoder::Libx264,
+            None,
+        )
+        .rotation(Rotation::Cw180)
+        .build();
+        let built = args(&cmd);
+        assert!(built.iter().any(|a| a == "hflip,vflip"), "{:?}", built);
+
+        // The same arguments, echoed back by the shell instead of run by ffmpeg
+        let mut echo = Command::new("printf");
+        echo.arg("%s\n").args(cmd.get_args());
+        let line = command_line(&echo);
+        assert_eq!(shell_args(&line), built);
+    }
+
+    #[test]
+    fn shell_quote_only_quotes_what_needs_it() {
+        assert_eq!(shell_quote("-pix_fmt"), "-pix_fmt");
+        assert_eq!(shell_quote("scale=1280:-2,format=yuv420p"), "scale=1280:-2,format=yuv420p");
+        assert_eq!(shell_quote("a b"), "'a b'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+        assert_eq!(shell_quote(""), "''");
+    }
+
+    #[test]
+    fn rotation_swaps_sides_at_90_and_270() {
+        assert_eq!(Rotation::None.apply(1280, 721), (1280, 721));
+        assert_eq!(Rotation::Cw90.apply(1280, 721), (721, 1280));
+        assert_eq!(Rotation::Cw180.apply(1280, 721), (1280, 721));
+        assert_eq!(Rotation::Cw270.apply(1280, 721), (721, 1280));
+    }
+
+    #[test]
+    fn rotated_commands_transpose_then_size_the_output() {
+        for (rotation, filter, size) in [
+            (Rotation::Cw90, "transpose=clock", (720, 1280)),
+            (Rotation::Cw270, "transpose=cclock", (720, 1280)),
+            (Rotation::Cw180, "hflip,vflip", (1280, 720)),
+        ] {
+            let cmd = FfmpegCommandBuilder::new(
+                PathBuf::from("ffmpeg"), 1280, 721, 30, 6000, PathBuf::from("out.mp4"), VideoEncoder::Libx264, None,
+            )
+            .rotation(rotation)
+            .build();
+            let args = args(&cmd);
+            let vf = args.iter().position(|a| a == "-vf").expect("rotation filter");
+            assert_eq!(args[vf + 1], filter);
+            // The odd side is rounded to even after swapping, not before
+            assert_eq!(sizes(&args), [(1280, 721), size]);
+        }
+        let unrotated = args(&builder_with_audio().build());
+        assert!(!unrotated.iter().any(|a| a == "-vf"), "{:?}", unrotated);
+    }
+
+    #[test]
+    fn scaled_size_keeps_odd_ratios() {
+        // 1366x768 is 683:384; 562.2 rounds down
+        assert_eq!(scaled_size(1366, 768, Some(1000), None, true), (1000, 562));
+        assert_eq!(scaled_size(7, 3, None, Some(2), true), (5, 2));
+        assert_eq!(scaled_size(1, 3, Some(2), None, true), (2, 6));
+        // A box: the tighter side decides, the other fits inside it
+        assert_eq!(scaled_size(1920, 1080, Some(1000), Some(1000), true), (1000, 563));
+        assert_eq!(scaled_size(1080, 1920, Some(1000), Some(1000), true), (563, 1000));
+        // Nothing collapses to zero, even from a zero-sized source
+        assert_eq!(scaled_size(10000, 3, Some(100), None, true), (100, 1));
+        assert_eq!(scaled_size(0, 0, Some(4), None, true), (4, 4));
+    }
+
+    #[test]
+    fn scaled_size_stretches_without_the_lock() {
+        assert_eq!(scaled_size(1366, 768, Some(1000), None, false), (1000, 768));
+        assert_eq!(scaled_size(1366, 768, Some(640), Some(640), false), (640, 640));
+    }
+
+    #[test]
+    fn even_rounding_keeps_the_locked_ratio() {
+        for (sw, sh, width) in [(1366, 768, 1000), (1920, 1080, 1001), (1001, 333, 501), (3440, 1440, 1279)] {
+            let (w, h) = scaled_size(sw, sh, Some(width), None, true);
+            let cmd = FfmpegCommandBuilder::new(
+                PathBuf::from("ffmpeg"), sw, sh, 30, 6000, PathBuf::from("out.mp4"), VideoEncoder::Libx264, None,
+            )
+            .output_size(Some((w, h)))
+            .build();
+            let (ow, oh) = sizes(&args(&cmd))[1];
+            assert!(ow % 2 == 0 && oh % 2 == 0);
+            // Rounding each side to even moves the ratio by at most a pixel's worth per side
+            let error = (ow as f64 / oh as f64) / (sw as f64 / sh as f64) - 1.0;
+            let tolerance = 1.5 / ow.min(oh) as f64;
+            assert!(error.abs() <= tolerance, "{}x{} -> {}x{}, off by {:.4}", sw, sh, ow, oh, error);
+
+            for even in [EvenSize::Crop, EvenSize::Pad] {
+                let (ew, eh) = even.apply(w, h);
+                assert!(ew.abs_diff(w) <= 1 && eh.abs_diff(h) <= 1 && ew % 2 == 0 && eh % 2 == 0);
+            }
+        }
+    }
+
+    /// `w`x`h` frame whose pixel at (x, y) is `pixel(x, y)`
+    fn frame(w: usize, h: usize, pixel: impl Fn(usize, usize) -> [u8; 4]) -> Vec<u8> {
+        (0..h).flat_map(|y| (0..w).map(move |x| (x, y))).flat_map(|(x, y)| pixel(x, y)).collect()
+    }
+
+    fn pixel_at(buf: &[u8], w: usize, x: usize, y: usize) -> [u8; 4] {
+        let i = (y * w + x) * 4;
+        [buf[i], buf[i + 1], buf[i + 2], buf[i + 3]]
+    }
+
+    #[test]
+    fn resize_identity_keeps_the_frame() {
+        let src = frame(5, 3, |x, y| [x as u8, y as u8, 7, 255]);
+        assert_eq!(resize_rgba_nn(&src, 5, 3, 5, 3), src);
+    }
+
+    #[test]
+    fn resize_upscales_a_single_pixel() {
+        let red = [255, 0, 0, 255];
+        assert_eq!(resize_rgba_nn(&red, 1, 1, 4, 4), frame(4, 4, |_, _| red));
+    }
+
+    #[test]
+    fn resize_downscale_keeps_each_corner_in_its_quadrant() {
+        let quadrant = |x: usize, y: usize| [(x / 2) as u8 * 100, (y / 2) as u8 * 100, 0, 255];
+        let src = frame(4, 4, quadrant);
+        let dst = resize_rgba_nn(&src, 4, 4, 2, 2);
+        assert_eq!(pixel_at(&dst, 2, 0, 0), pixel_at(&src, 4, 0, 0));
+        assert_eq!(pixel_at(&dst, 2, 1, 0), quadrant(3, 0));
+        assert_eq!(pixel_at(&dst, 2, 0, 1), quadrant(0, 3));
+        assert_eq!(pixel_at(&dst, 2, 1, 1), quadrant(3, 3));
+    }
+
+    #[test]
+    fn resize_zero_or_truncated_source_gives_blank_frame() {
+        assert_eq!(resize_rgba_nn(&[], 0, 0, 3, 2), vec![0; 3 * 2 * 4]);
+        assert_eq!(resize_rgba_nn(&[1; 16], 2, 2, 0, 5), Vec::<u8>::new());
+        assert_eq!(resize_rgba_nn(&[9; 12], 2, 2, 2, 2), vec![0; 16]);
+    }
+
+    #[test]
+    fn crop_drops_the_odd_column_and_keeps_every_other_pixel() {
+        let coords = |x: usize, y: usize| [x as u8, y as u8, 7, 255];
+        let src = frame(101, 100, coords);
+        let dst = crop_or_pad_rgba(&src, 101, 100, 100, 100);
+        assert_eq!(dst, frame(100, 100, coords));
+        // Through the same path a recording takes when a window turns odd
+        assert_eq!(fit_to_stream(&src, 101, 100, (100, 100), None), dst);
+    }
+
+    #[test]
+    fn pad_repeats_the_last_column_and_row() {
+        let coords = |x: usize, y: usize| [x as u8, y as u8, 7, 255];
+        let src = frame(99, 99, coords);
+        let dst = crop_or_pad_rgba(&src, 99, 99, 100, 100);
+        assert_eq!(dst, frame(100, 100, |x, y| coords(x.min(98), y.min(98))));
+    }
+
+    #[test]
+    fn split_files_get_their_own_markers() {
+        let secs = Duration::from_secs;
+        let markers = [secs(5), secs(12), secs(30)];
+        assert_eq!(file_markers(&markers, secs(0), Some(secs(10))), vec![secs(5)]);
+        assert_eq!(file_markers(&markers, secs(10), Some(secs(30))), vec![secs(2)]);
+        assert_eq!(file_markers(&markers, secs(30), None), vec![secs(0)]);
+    }
+
+    #[test]
+    fn movflags_follow_layout_and_container() {
+        let mp4 = Path::new("a.mp4");
+        assert_eq!(output_movflags(Mp4Layout::Standard, false, mp4), None);
+        assert_eq!(output_movflags(Mp4Layout::Faststart, true, mp4).as_deref(), Some("faststart+use_metadata_tags"));
+        assert_eq!(output_movflags(Mp4Layout::Standard, true, Path::new("a.mov")).as_deref(), Some("+use_metadata_tags"));
+        assert_eq!(output_movflags(Mp4Layout::Faststart, true, Path::new("a.mkv")), None);
+    }
+
+    #[test]
+    fn segments_are_listed_for_the_recorder() {
+        let args = args(&builder_with_audio().segment_seconds(Some(60)).build());
+        let list = args.iter().position(|a| a == "-segment_list").expect("segment list");
+        assert_eq!(args[list + 1], segment_list_path(Path::new("out.mp4")).to_string_lossy());
+        assert_eq!(args[list + 2..list + 4], ["-segment_list_type", "csv"]);
+        assert_ne!(segment_list_path(Path::new("a/out.mp4")), segment_list_path(Path::new("b/out.mp4")));
+    }
+
+    #[test]
+    fn segment_files_come_from_the_list_not_the_folder() {
+        let dir = std::env::temp_dir().join(format!("screencast-segment-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("rec.mp4");
+        // Left over from an earlier recording with the same name
+        for index in 0..6 {
+            std::fs::write(segment_file(&path, index), b"old").unwrap();
+        }
+        let list = dir.join("list.csv");
+        let mut segment = OutputSegment {
+            child: Command::new("true").spawn().unwrap(),
+            path: path.clone(),
+            started_at: Instant::now(),
+            live_audio: false,
+            segment_list: Some(list.clone()),
+            earlier_files: Vec::new(),
+        };
+        let _ = segment.child.wait();
+
+        // Nothing finished yet
+        assert_eq!(segment.current_file(), (segment_file(&path, 0), Duration::ZERO));
+        assert_eq!(segment.last_file(), (segment_file(&path, 0), Duration::ZERO));
+        assert_eq!(segment.files(), [segment_file(&path, 0)]);
+
+        std::fs::write(&list, "rec_000.mp4,0.000000,60.033333\n\"rec,odd_001.mp4\",60.033333,120.000000\nnot a line\n").unwrap();
+        let secs = Duration::from_secs_f64;
+        assert_eq!(segment.current_file(), (segment_file(&path, 2), secs(120.0)));
+        assert_eq!(segment.last_file(), (segment_file(&path, 1), secs(60.033333)));
+        assert_eq!(segment.files(), [segment_file(&path, 0), segment_file(&path, 1)]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn split_files_are_listed_before_the_open_one() {
+        let path = Path::new("/tmp/rec_3.mp4");
+        let earlier_files = vec![PathBuf::from("/tmp/rec_1.mp4"), PathBuf::from("/tmp/rec_2.mp4")];
+        let mut segment = OutputSegment {
+            child: Command::new("true").spawn().unwrap(),
+            path: path.to_path_buf(),
+            started_at: Instant::now(),
+            live_audio: false,
+            segment_list: None,
+            earlier_files: earlier_files.clone(),
+        };
+        let _ = segment.child.wait();
+        assert_eq!(segment.files(), [&earlier_files[..], &[path.to_path_buf()]].concat());
+    }
+
+    #[test]
+    fn canvas_is_the_size_after_rotation() {
+        let canvas = Canvas { width: 1920, height: 1080, background: [0, 0, 0] };
+        assert_eq!(canvas.stream_size(Rotation::None), (1920, 1080));
+        assert_eq!(canvas.stream_size(Rotation::Cw180), (1920, 1080));
+        assert_eq!(canvas.stream_size(Rotation::Cw90), (1080, 1920));
+        assert_eq!(canvas.stream_size(Rotation::Cw270), (1080, 1920));
+
+        // Nothing rescales the canvas afterwards
+        let mut config = RecordingConfig {
+            output_width: Some(1280),
+            lock_aspect_ratio: true,
+            max_capture_dimension: Some(1000),
+            ..RecordingConfig::default()
+        };
+        assert_eq!(config.output_size_for(1920, 1080), Some((1000, 563)));
+        config.canvas = Some(canvas);
+        assert_eq!(config.output_size_for(1920, 1080), None);
+    }
+
+    #[test]
+    fn videotoolbox_session_errors_are_recognized() {
+        assert!(is_videotoolbox_session_error(
+            "[h264_videotoolbox @ 0x1] Error: cannot create compression session: -12903"
+        ));
+        assert!(is_videotoolbox_session_error(
+            "[hevc_videotoolbox @ 0x1] cannot prepare encoder: -12902"
+        ));
+        // Other failures of a VideoToolbox run, and session errors of others
+        assert!(!is_videotoolbox_session_error("[h264_videotoolbox @ 0x1] out.mp4: Permission denied"));
+        assert!(!is_videotoolbox_session_error("[libx264 @ 0x1] Error while opening encoder"));
+    }
+
+    #[test]
+    fn fallback_ladder_ends_in_libx264() {
+        use VideoEncoder::*;
+        assert_eq!(fallback_encoder(H264VideoToolbox, true), Some(H264VideoToolboxFallback));
+        assert_eq!(fallback_encoder(H264VideoToolbox, false), Some(Libx264));
+        assert_eq!(fallback_encoder(H264VideoToolboxFallback, true), Some(Libx264));
+        // Only H.264 has a conservative VideoToolbox variant
+        assert_eq!(fallback_encoder(HevcVideoToolbox, true), Some(Libx264));
+        assert_eq!(fallback_encoder(ProResVideoToolbox, false), Some(Libx264));
+        assert_eq!(fallback_encoder(Libx264, false), None);
+    }
+
+    /// A file `len` bytes long, last written `secs` after the epoch
+    fn file_at(len: u64, secs: u64) -> Option<(u64, SystemTime)> {
+        Some((len, UNIX_EPOCH + Duration::from_secs(secs)))
+    }
+
+    #[test]
+    fn finalize_grace_grows_with_the_file() {
+        assert_eq!(FinalizeTimeout::new(0).grace, FINALIZE_BASE_TIMEOUT);
+        assert_eq!(FinalizeTimeout::new(10_000_000_000).grace, Duration::from_secs(55));
+        assert_eq!(FinalizeTimeout::new(1_000_000_000_000).grace, FINALIZE_MAX_GRACE);
+    }
+
+    #[test]
+    fn finalize_gives_up_on_a_file_that_stopped_changing() {
+        let secs = Duration::from_secs_f64;
+        let mut timeout = FinalizeTimeout::new(0);
+        assert!(!timeout.expired(secs(0.1), file_at(1000, 1)));
+        assert!(!timeout.expired(secs(4.9), file_at(1000, 1)));
+        assert!(timeout.expired(secs(5.1), file_at(1000, 1)));
+
+        // An unreadable file counts as unchanged, too
+        let mut timeout = FinalizeTimeout::new(0);
+        assert!(!timeout.expired(secs(0.1), None));
+        assert!(timeout.expired(secs(5.1), None));
+    }
+
+    #[test]
+    fn finalize_waits_while_the_file_is_still_written() {
+        let mut timeout = FinalizeTimeout::new(0);
+        // Rewriting the moov atom: a new length and mtime every second
+        for second in 0..30 {
+            let elapsed = Duration::from_secs(second);
+            assert!(!timeout.expired(elapsed, file_at(1000 + second, second)), "killed at {}s", second);
+            assert!(timeout.still_writing(elapsed));
+        }
+        // Then it hangs: killed once it has been unchanged for the stall timeout
+        let last = file_at(1029, 29);
+        assert!(!timeout.expired(Duration::from_secs(33), last));
+        assert!(!timeout.still_writing(Duration::from_secs(34)));
+        assert!(timeout.expired(Duration::from_secs(34), last));
+    }
+
+    #[test]
+    fn finalize_never_waits_past_the_hard_limit() {
+        let mut timeout = FinalizeTimeout::new(u64::MAX);
+        let limit = FINALIZE_HARD_LIMIT.as_secs();
+        for second in (0..limit).step_by(2) {
+            assert!(!timeout.expired(Duration::from_secs(second), file_at(second, second)));
+        }
+        assert!(timeout.expired(FINALIZE_HARD_LIMIT, file_at(limit + 1, limit + 1)));
+    }
+
+    #[test]
+    fn quitting_waits_for_ffmpeg_to_finish_the_file() {
+        let dir = std::env::temp_dir().join(format!("screencast-quit-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let output = dir.join("out.mp4");
+        // Stands in for ffmpeg: writes its input to the file, plus a trailer once stdin closes
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg("cat > \"$0\"; sleep 0.3; echo moov >> \"$0\"")
+            .arg(&output)
+            .stdin(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.as_mut().unwrap().write_all(b"frames\n").unwrap();
+        send_quit_and_wait(&mut child, &output, false).unwrap();
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "frames\nmoov\n");
+
+        let mut failing = Command::new("sh")
+            .arg("-c")
+            .arg("cat > /dev/null; exit 1")
+            .stdin(Stdio::piped())
+            .spawn()
+            .unwrap();
+        assert!(send_quit_and_wait(&mut failing, &output, false).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn interrupt_exit_codes() {
+        use std::os::unix::process::ExitStatusExt;
+        assert!(exited_from_interrupt(std::process::ExitStatus::from_raw(255 << 8)));
+        assert!(exited_from_interrupt(std::process::ExitStatus::from_raw(2)));
+        assert!(!exited_from_interrupt(std::process::ExitStatus::from_raw(1 << 8)));
+    }
+
+    #[test]
+    fn same_name_recordings_get_their_own_files() {
+        // Claims are process-wide; a path of its own keeps other tests out
+        let path = std::env::temp_dir().join("screencast-claim-test/Recording.mp4");
+        let first = OutputClaim::new(&path);
+        let second = OutputClaim::new(&path);
+        assert_eq!(first.path(), path);
+        assert_eq!(second.path(), path.with_file_name("Recording-2.mp4"));
+
+        // A finished recording frees its name for the next one
+        drop(first);
+        assert_eq!(OutputClaim::new(&path).path(), path);
+    }
+
+    #[test]
+    fn simultaneous_starts_never_share_a_file() {
+        let path = std::env::temp_dir().join("screencast-claim-test/Simultaneous.mov");
+        let barrier = Arc::new(std::sync::Barrier::new(8));
+        let starts: Vec<_> = (0..8)
+            .map(|_| {
+                let (path, barrier) = (path.clone(), barrier.clone());
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    OutputClaim::new(&path)
+                })
+            })
+            .collect();
+        let claims: Vec<OutputClaim> = starts.into_iter().map(|t| t.join().unwrap()).collect();
+        let mut paths: Vec<&Path> = claims.iter().map(OutputClaim::path).collect();
+        paths.sort();
+        paths.dedup();
+        assert_eq!(paths.len(), 8);
+        assert!(paths.contains(&path.as_path()));
+    }
+
+    /// Takes at most `per_write` bytes per call, slowly, like a busy pipe
+    struct Trickle {
+        written: Vec<u8>,
+        per_write: usize,
+        writes: usize,
+    }
+
+    impl Write for Trickle {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            std::thread::sleep(Duration::from_micros(50));
+            let n = buf.len().min(self.per_write);
+            self.written.extend_from_slice(&buf[..n]);
+            self.writes += 1;
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Accepts `accept` bytes, then fails like a pipe whose reader exited
+    struct Hangup {
+        accept: usize,
+    }
+
+    impl Write for Hangup {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if self.accept == 0 {
+                return Err(std::io::ErrorKind::BrokenPipe.into());
+            }
+            let n = buf.len().min(self.accept);
+            self.accept -= n;
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn no_disk_full() -> String {
+        unreachable!("the disk isn't full")
+    }
+
+    #[test]
+    fn a_slow_writer_still_gets_whole_frames() {
+        let frame = frame(64, 4, |x, y| [x as u8, y as u8, 7, 255]);
+        let mut writer = Trickle { written: Vec::new(), per_write: 100, writes: 0 };
+        let log = Mutex::new(OutputLog::new(4));
+        for _ in 0..3 {
+            write_frame(&mut writer, &frame, &log, no_disk_full).unwrap();
+            assert_eq!(writer.written.len() % frame.len(), 0, "a frame was cut short");
+        }
+        assert_eq!(writer.written, frame.repeat(3));
+        assert!(writer.writes > 3, "each frame took several writes");
+    }
+
+    #[test]
+    fn a_blocked_pipe_gets_only_whole_frames() {
+        // Frames bigger than a pipe's buffer, read by something that starts late
+        let frame = vec![0x5a; 256 * 1024];
+        let mut reader = Command::new("sh")
+            .arg("-c")
+            .arg("sleep 0.2; wc -c")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut stdin = reader.stdin.take().unwrap();
+        let log = Mutex::new(OutputLog::new(4));
+        for _ in 0..3 {
+            write_frame(&mut stdin, &frame, &log, no_disk_full).unwrap();
+        }
+        drop(stdin);
+        let output = reader.wait_with_output().unwrap();
+        let received: usize = String::from_utf8_lossy(&output.stdout).trim().parse().unwrap();
+        assert_eq!(received, 3 * frame.len());
+    }
+
+    #[test]
+    fn a_closed_pipe_says_why_ffmpeg_stopped() {
+        let frame = vec![0; 1024];
+        let log = Mutex::new(OutputLog::new(4));
+        log.lock().push("Conversion failed!".to_string());
+        let reason = write_frame(&mut Hangup { accept: 100 }, &frame, &log, no_disk_full).unwrap_err();
+        assert_eq!(reason, "ffmpeg exited unexpectedly (broken pipe): Conversion failed!");
+
+        log.lock().push("av_interleaved_write_frame(): No space left on device".to_string());
+        let reason = write_frame(&mut Hangup { accept: 0 }, &frame, &log, || "disk full".to_string()).unwrap_err();
+        assert_eq!(reason, "disk full");
+    }
+
+    #[test]
+    fn output_log_keeps_only_the_newest_lines() {
+        let mut log = OutputLog::new(3);
+        assert_eq!(log.last(), None);
+        for n in 1..=5 {
+            log.push(format!("line {}", n));
+        }
+        assert_eq!(log.lines().collect::<Vec<_>>(), ["line 3", "line 4", "line 5"]);
+        assert_eq!(log.last(), Some("line 5"));
+
+        // Thousands of lines later it still holds three
+        for n in 6..=5000 {
+            log.push(format!("line {}", n));
+        }
+        assert_eq!(log.lines().count(), 3);
+        assert_eq!(log.lines().next(), Some("line 4998"));
+    }
+
+    #[test]
+    fn output_log_without_capacity_keeps_nothing() {
+        let mut log = OutputLog::new(0);
+        log.push("frame=1".to_string());
+        assert_eq!(log.lines().count(), 0);
+        assert_eq!(log.last(), None);
+    }
+
+    #[test]
+    fn disk_full_is_noticed_in_ffmpeg_stderr() {
+        // A stand-in for ffmpeg running out of space mid-recording, then
+        // printing enough to push that line out of the log
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg("echo 'frame=  120 fps= 30 size=    1024kB' >&2; \
+                  echo '[mp4 @ 0x1] av_interleaved_write_frame(): No space left on device' >&2; \
+                  echo 'Error writing trailer of out.mp4: No space left on device' >&2; \
+                  echo 'Conversion failed!' >&2; echo 'Exiting normally' >&2")
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let log = Arc::new(Mutex::new(OutputLog::new(2)));
+        log_ffmpeg_stderr(&mut child, log.clone());
+        child.wait().unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while log.lock().last() != Some("Exiting normally") && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        let log = log.lock();
+        assert!(log.disk_full());
+        assert!(!log.lines().any(is_disk_full_error), "the disk full lines were dropped");
+    }
+
+    #[test]
+    fn disk_full_errors_are_told_apart() {
+        assert!(is_disk_full_error("av_interleaved_write_frame(): No space left on device"));
+        assert!(is_disk_full_error("out.mp4: Disk quota exceeded"));
+        assert!(!is_disk_full_error("Error while decoding stream #0:0: Invalid data found"));
+
+        let mut log = OutputLog::new(4);
+        log.push("Conversion failed!".to_string());
+        assert!(!log.disk_full());
+    }
+
+    #[test]
+    fn disk_full_message_suggests_fragmented_mp4() {
+        let path = Path::new("/tmp/out.mp4");
+        assert!(disk_full_message(path, Mp4Layout::Standard).contains("fragmented"));
+        assert!(!disk_full_message(path, Mp4Layout::Fragmented).contains("fragmented"));
+        assert!(!disk_full_message(Path::new("/tmp/out.mkv"), Mp4Layout::Standard).contains("fragmented"));
+    }
+
+    #[test]
+    fn every_clamping_encoder_explains_its_limits() {
+        let (lowest, highest) = (*RateControl::BITRATE_RANGE_KBPS.start(), *RateControl::BITRATE_RANGE_KBPS.end());
+        let encoders = [
+            VideoEncoder::H264VideoToolbox,
+            VideoEncoder::H264VideoToolboxFallback,
+            VideoEncoder::HevcVideoToolbox,
+        ];
+        for encoder in encoders {
+            let (min, max) = encoder.bitrate_limits_kbps().unwrap();
+            // The settings can reach past the cap, so the warning can show
+            let capped = encoder.bitrate_adjustment(highest).unwrap();
+            assert!(capped.contains(&format!("caps bitrate at {} Mbps", max / 1000)), "{}", capped);
+            assert!(capped.ends_with(&format!("will use {} kbps", max)), "{}", capped);
+            if lowest < min {
+                let raised = encoder.bitrate_adjustment(lowest).unwrap();
+                assert!(raised.contains(&format!("needs at least {} kbps", min)), "{}", raised);
+            }
+            assert_eq!(encoder.bitrate_adjustment(max), None);
+        }
+        assert!(VideoEncoder::H264VideoToolboxFallback.bitrate_adjustment(lowest).is_some());
+    }
+
+    #[test]
+    fn encoders_without_limits_use_the_set_bitrate() {
+        for encoder in [VideoEncoder::Libx264, VideoEncoder::ProResVideoToolbox] {
+            assert_eq!(encoder.bitrate_adjustment(*RateControl::BITRATE_RANGE_KBPS.end()), None);
+        }
+    }
+}
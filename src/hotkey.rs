@@ -0,0 +1,96 @@
+use serde::{Deserialize, Serialize};
+
+/// A system-wide key combination, e.g. ⇧⌘7
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Hotkey {
+    pub key: char, // One of `Hotkey::KEYS`
+    pub command: bool,
+    pub shift: bool,
+    pub option: bool,
+    pub control: bool,
+}
+
+impl Default for Hotkey {
+    /// ⇧⌘7: next to the system's ⇧⌘3/4/5 screenshot shortcuts without clashing
+    fn default() -> Self {
+        Self { key: '7', command: true, shift: true, option: false, control: false }
+    }
+}
+
+impl Hotkey {
+    /// Keys a hotkey can use
+    pub const KEYS: &'static str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+    /// Modifiers in the order macOS menus show them, then the key
+    pub fn label(&self) -> String {
+        let mut label = String::new();
+        for (held, symbol) in [(self.control, '⌃'), (self.option, '⌥'), (self.shift, '⇧'), (self.command, '⌘')] {
+            if held {
+                label.push(symbol);
+            }
+        }
+        label.push(self.key);
+        label
+    }
+
+    /// macOS virtual key code of `key` on an ANSI layout (kVK_ANSI_*)
+    pub fn key_code(&self) -> Option<u16> {
+        let code = match self.key.to_ascii_uppercase() {
+            'A' => 0, 'S' => 1, 'D' => 2, 'F' => 3, 'H' => 4, 'G' => 5, 'Z' => 6,
+            'X' => 7, 'C' => 8, 'V' => 9, 'B' => 11, 'Q' => 12, 'W' => 13, 'E' => 14,
+            'R' => 15, 'Y' => 16, 'T' => 17, '1' => 18, '2' => 19, '3' => 20, '4' => 21,
+            '6' => 22, '5' => 23, '9' => 25, '7' => 26, '8' => 28, '0' => 29, 'O' => 31,
+            'U' => 32, 'I' => 34, 'P' => 35, 'L' => 37, 'J' => 38, 'K' => 40, 'N' => 45,
+            'M' => 46,
+            _ => return None,
+        };
+        Some(code)
+    }
+
+    /// Whether one of ⌘, ⌥ and ⌃ is held. Without one the hotkey would go
+    /// off on plain typing in every app, shift only adding capitals.
+    pub fn has_modifier(&self) -> bool {
+        self.command || self.option || self.control
+    }
+
+    /// Whether a key press is this hotkey; modifiers must match exactly. A
+    /// hotkey without `has_modifier` matches nothing.
+    pub fn matches(&self, key_code: u16, command: bool, shift: bool, option: bool, control: bool) -> bool {
+        self.has_modifier()
+            && self.key_code() == Some(key_code)
+            && self.command == command
+            && self.shift == shift
+            && self.option == option
+            && self.control == control
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY_7: u16 = 26;
+
+    #[test]
+    fn matches_only_the_exact_combination() {
+        let hotkey = Hotkey::default();
+        assert!(hotkey.matches(KEY_7, true, true, false, false));
+        assert!(!hotkey.matches(KEY_7, true, false, false, false));
+        assert!(!hotkey.matches(KEY_7, true, true, true, false));
+        assert!(!hotkey.matches(KEY_7 + 1, true, true, false, false));
+    }
+
+    #[test]
+    fn a_hotkey_without_command_option_or_control_matches_nothing() {
+        let bare = Hotkey { key: '7', command: false, shift: false, option: false, control: false };
+        assert!(!bare.has_modifier());
+        assert!(!bare.matches(KEY_7, false, false, false, false));
+
+        // Shift only adds capitals; it doesn't keep typing from firing it
+        let shifted = Hotkey { shift: true, ..bare };
+        assert!(!shifted.matches(KEY_7, false, true, false, false));
+
+        let option = Hotkey { option: true, ..bare };
+        assert!(option.matches(KEY_7, false, false, true, false));
+    }
+}
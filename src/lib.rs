@@ -0,0 +1,283 @@
+//! Window capture engine behind the Screen Recorder app.
+//!
+//! Embed it without the UI through [`ScreenRecorder`]:
+//!
+//! ```no_run
+//! use screencast::{list_windows, RecordingConfig, ScreenRecorder};
+//!
+//! let mut recorder = ScreenRecorder::new(RecordingConfig::new())?;
+//! let window = list_windows()?.into_iter().next().expect("no windows");
+//! recorder.start(&window)?;
+//! std::thread::sleep(std::time::Duration::from_secs(5));
+//! let path = recorder.stop(window.window_id);
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+pub mod audio;
+pub mod control;
+pub mod error;
+pub mod events;
+pub mod ffmpeg;
+pub mod hotkey;
+pub mod recorder;
+pub mod screenshot;
+pub mod settings;
+pub mod window;
+
+#[cfg(target_os = "macos")]
+pub mod macos;
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::mpsc::Receiver;
+
+use parking_lot::Mutex;
+
+pub use error::RecorderError;
+pub use events::RecordingEvent;
+pub use recorder::RecordingConfig;
+pub use window::WindowInfo;
+
+use ffmpeg::{find_ffmpeg, start_ffmpeg_for_window};
+use recorder::RecorderState;
+use window::WindowManager;
+
+/// All capturable on-screen windows
+pub fn list_windows() -> anyhow::Result<Vec<WindowInfo>> {
+    let mut manager = WindowManager::new();
+    manager.refresh()?;
+    Ok(manager.windows().to_vec())
+}
+
+/// Records windows to files with ffmpeg. Recordings still running when the
+/// recorder is dropped are stopped and finalized.
+pub struct ScreenRecorder {
+    ffmpeg: PathBuf,
+    config: RecordingConfig,
+    state: Mutex<RecorderState>, // Behind a lock so `&self` queries can reap failed recordings
+    events: Mutex<Receiver<RecordingEvent>>, // Own subscription, for `failure`
+    failures: Mutex<HashMap<u64, String>>,
+}
+
+impl ScreenRecorder {
+    /// Create a recorder using the ffmpeg found on PATH or in common install locations
+    pub fn new(config: RecordingConfig) -> Result<Self, RecorderError> {
+        let ffmpeg = find_ffmpeg().ok_or(RecorderError::FfmpegMissing)?;
+        Ok(Self::with_ffmpeg(ffmpeg, config))
+    }
+
+    pub fn with_ffmpeg(ffmpeg: PathBuf, config: RecordingConfig) -> Self {
+        let state = RecorderState::new();
+        let events = Mutex::new(state.events().subscribe());
+        Self {
+            ffmpeg,
+            config,
+            state: Mutex::new(state),
+            events,
+            failures: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Events of every recording started after subscribing
+    pub fn subscribe(&self) -> Receiver<RecordingEvent> {
+        self.state.lock().events().subscribe()
+    }
+
+    /// Settings used by the next `start`; running recordings keep theirs
+    pub fn config_mut(&mut self) -> &mut RecordingConfig {
+        &mut self.config
+    }
+
+    pub fn list_windows(&self) -> anyhow::Result<Vec<WindowInfo>> {
+        list_windows()
+    }
+
+    /// Start recording a window into `config.output_dir`. Does nothing if the
+    /// window is already being recorded; one that failed is started again.
+    pub fn start(&mut self, window: &WindowInfo) -> Result<(), RecorderError> {
+        self.reap_failures();
+        if self.is_recording(window.window_id) {
+            return Ok(());
+        }
+        self.failures.lock().remove(&window.window_id);
+
+        let mut config = self.config.clone();
+        config.fps = config.resolve_fps(window);
+        let handles = start_ffmpeg_for_window(
+            &self.ffmpeg,
+            window,
+            config.output_dir.as_ref(),
+            None,
+            &config,
+            self.state.get_mut().events(),
+            &AtomicBool::new(false),
+        )?;
+        self.state.get_mut().start_recording(window.window_id, window.bounds(), handles);
+        Ok(())
+    }
+
+    /// Stop a recording and finalize its file. Returns the path of the last
+    /// file written, or `None` if the window wasn't being recorded.
+    pub fn stop(&mut self, window_id: u64) -> Option<PathBuf> {
+        let recording = self.state.get_mut().stop_recording(window_id)?;
+        Some(recording.finish(Some(&self.ffmpeg)))
+    }
+
+    pub fn stop_all(&mut self) -> Vec<PathBuf> {
+        self.state
+            .get_mut()
+            .stop_all()
+            .into_iter()
+            .map(|recording| recording.finish(Some(&self.ffmpeg)))
+            .collect()
+    }
+
+    /// Whether a window is being recorded; false once its recording failed
+    pub fn is_recording(&self, window_id: u64) -> bool {
+        self.reap_failures();
+        self.state.lock().is_recording(window_id)
+    }
+
+    /// Why a recording failed to start or stopped on its own (e.g. ffmpeg exited)
+    pub fn failure(&self, window_id: u64) -> Option<String> {
+        self.reap_failures();
+        self.failures.lock().get(&window_id).cloned()
+    }
+
+    /// Collect failures and finish the recordings they ended, keeping what
+    /// they wrote. Their ffmpeg has exited, but they'd count as running
+    /// until stopped.
+    fn reap_failures(&self) {
+        self.collect_failures();
+        let failed: Vec<u64> = self.failures.lock().keys().copied().collect();
+        let dead: Vec<_> = {
+            let mut state = self.state.lock();
+            failed.into_iter().filter_map(|id| state.stop_recording(id)).collect()
+        };
+        for recording in dead {
+            recording.finish(Some(&self.ffmpeg));
+        }
+    }
+
+    fn collect_failures(&self) {
+        let events = self.events.lock();
+        let mut failures = self.failures.lock();
+        for event in events.try_iter() {
+            if let RecordingEvent::Failed { window_id, error } = event {
+                failures.insert(window_id, error);
+            }
+        }
+    }
+}
+
+impl Drop for ScreenRecorder {
+    fn drop(&mut self) {
+        self.stop_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::{ChildStdin, Command, Stdio};
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    use ffmpeg::{emit_frame, OutputClaim, OutputLog, OutputSegment, RecordingHandles, VideoEncoder};
+    use window::WindowBounds;
+
+    const BOUNDS: WindowBounds = WindowBounds { x: 0, y: 0, width: 640, height: 480 };
+
+    fn recorder() -> ScreenRecorder {
+        ScreenRecorder::with_ffmpeg(PathBuf::from("ffmpeg"), RecordingConfig::new())
+    }
+
+    /// Handles of a recording whose stand-in ffmpeg has already exited, and
+    /// the pipe its frames would have gone to
+    fn dead_recording(name: &str) -> (RecordingHandles, ChildStdin) {
+        let mut child = Command::new("sh")
+            .args(["-c", "exit 1"])
+            .stdin(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stdin = child.stdin.take().unwrap();
+        child.wait().unwrap();
+        let path = std::env::temp_dir()
+            .join(format!("screencast-reap-test-{}", std::process::id()))
+            .join(format!("{}.mp4", name));
+        let segment = OutputSegment {
+            child,
+            path: path.clone(),
+            started_at: Instant::now(),
+            live_audio: false,
+            segment_list: None,
+            earlier_files: Vec::new(),
+        };
+        let handles = RecordingHandles {
+            segment: Arc::new(Mutex::new(segment)),
+            stop_signal: Arc::new(AtomicBool::new(false)),
+            stats: Default::default(),
+            stderr_log: Arc::new(Mutex::new(OutputLog::new(4))),
+            encoder: VideoEncoder::Libx264,
+            output_claim: OutputClaim::new(&path),
+            latest_frame: Default::default(),
+            verify: false,
+            has_audio: false,
+            markers: Default::default(),
+            movflags: None,
+            emitter: None,
+        };
+        (handles, stdin)
+    }
+
+    #[test]
+    fn a_failed_recording_stops_counting_as_running() {
+        let mut recorder = recorder();
+        let events = recorder.subscribe();
+        let (handles, _stdin) = dead_recording("failed");
+        recorder.state.get_mut().start_recording(7, BOUNDS, handles);
+        assert!(recorder.is_recording(7));
+
+        let error = "ffmpeg exited unexpectedly (broken pipe)".to_string();
+        recorder.state.lock().events().emit(RecordingEvent::Failed { window_id: 7, error: error.clone() });
+        assert!(!recorder.is_recording(7), "a dead recording was reported as running");
+        assert_eq!(recorder.failure(7), Some(error));
+        // Reaped like a stop: the file is finished and subscribers hear of it
+        assert!(events.try_iter().any(|e| matches!(e, RecordingEvent::Stopped { window_id: 7, .. })));
+        assert_eq!(recorder.stop(7), None);
+    }
+
+    #[test]
+    fn a_closed_pipe_fails_the_recording_and_clears_it() {
+        let mut recorder = recorder();
+        let events = recorder.subscribe();
+        let (mut handles, mut stdin) = dead_recording("closed-pipe");
+        // The emitter's frame loop, sending into the pipe ffmpeg closed by exiting
+        let bus = recorder.state.get_mut().events().clone();
+        let log = handles.stderr_log.clone();
+        log.lock().push("Conversion failed!".to_string());
+        handles.emitter = Some(std::thread::spawn(move || {
+            let frame = vec![0u8; 640 * 480 * 4];
+            while emit_frame(&mut stdin, &frame, &log, String::new, &bus, 9) {}
+        }));
+        recorder.state.get_mut().start_recording(9, BOUNDS, handles);
+        assert!(recorder.state.get_mut().started_at(9).is_some());
+
+        let deadline = Instant::now() + std::time::Duration::from_secs(5);
+        while recorder.failure(9).is_none() {
+            assert!(Instant::now() < deadline, "the emitter never reported the closed pipe");
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        let failure = recorder.failure(9).unwrap();
+        assert!(failure.starts_with("ffmpeg exited unexpectedly (broken pipe)"), "{}", failure);
+        assert!(failure.ends_with("Conversion failed!"), "{}", failure);
+
+        // Failed went out, and nothing of the recording is left running
+        let events: Vec<_> = events.try_iter().collect();
+        assert!(events.iter().any(|e| matches!(e, RecordingEvent::Failed { window_id: 9, .. })));
+        assert!(events.iter().any(|e| matches!(e, RecordingEvent::Stopped { window_id: 9, .. })));
+        assert!(!recorder.is_recording(9));
+        assert_eq!(recorder.state.get_mut().started_at(9), None);
+    }
+}
@@ -4,12 +4,20 @@ use core_foundation::base::TCFType;
 use core_foundation::dictionary::{CFDictionary, CFDictionaryRef};
 use core_foundation::number::{CFNumber, CFNumberRef};
 use core_foundation::string::{CFString, CFStringRef};
-use core_graphics::geometry::CGRect;
+use core_graphics::geometry::{CGPoint, CGRect, CGSize};
 use core_foundation_sys::array::{CFArrayGetCount, CFArrayGetValueAtIndex};
 use core_foundation_sys::dictionary::CFDictionaryGetValueIfPresent;
+use objc::declare::ClassDecl;
+use objc::runtime::{Class, Object, Sel, BOOL, NO, YES};
+use objc::{class, msg_send, sel, sel_impl};
 use std::ffi::c_void;
+use std::sync::{Arc, Once};
 
-use crate::window::WindowInfo;
+use parking_lot::Mutex;
+use tracing::error;
+
+use crate::hotkey::Hotkey;
+use crate::window::{WindowBounds, WindowInfo};
 
 #[link(name = "CoreGraphics", kind = "framework")]
 extern "C" {
@@ -26,6 +34,8 @@ extern "C" {
     fn CGImageGetWidth(image: core_graphics::sys::CGImageRef) -> usize;
     fn CGImageGetHeight(image: core_graphics::sys::CGImageRef) -> usize;
     fn CGColorSpaceCreateDeviceRGB() -> core_graphics::sys::CGColorSpaceRef;
+    fn CGColorSpaceCreateWithName(name: CFStringRef) -> core_graphics::sys::CGColorSpaceRef;
+    static kCGColorSpaceSRGB: CFStringRef;
     fn CGColorSpaceRelease(space: core_graphics::sys::CGColorSpaceRef);
     fn CGBitmapContextCreate(
         data: *mut std::ffi::c_void,
@@ -42,6 +52,119 @@ extern "C" {
         image: core_graphics::sys::CGImageRef,
     );
     fn CGContextRelease(c: core_graphics::sys::CGContextRef);
+    fn CGGetDisplaysWithPoint(
+        point: CGPoint,
+        maxDisplays: u32,
+        displays: *mut u32,
+        matchingDisplayCount: *mut u32,
+    ) -> i32;
+    fn CGGetActiveDisplayList(maxDisplays: u32, activeDisplays: *mut u32, displayCount: *mut u32) -> i32;
+    fn CGSessionCopyCurrentDictionary() -> CFDictionaryRef;
+    fn CGMainDisplayID() -> u32;
+    fn CGDisplayCopyDisplayMode(display: u32) -> *mut c_void;
+    fn CGDisplayModeGetRefreshRate(mode: *mut c_void) -> f64;
+    fn CGDisplayModeGetWidth(mode: *mut c_void) -> usize;
+    fn CGDisplayModeGetPixelWidth(mode: *mut c_void) -> usize;
+    fn CGDisplayModeRelease(mode: *mut c_void);
+    fn CGDisplayBounds(display: u32) -> CGRect;
+    fn CGDisplayRegisterReconfigurationCallback(
+        callback: CGDisplayReconfigurationCallBack,
+        userInfo: *mut c_void,
+    ) -> i32;
+    fn CGColorCreateGenericRGB(red: f64, green: f64, blue: f64, alpha: f64) -> *mut c_void;
+    fn CGColorRelease(color: *mut c_void);
+    fn CGEventTapCreate(
+        tap: u32,
+        place: u32,
+        options: u32,
+        eventsOfInterest: u64,
+        callback: CGEventTapCallBack,
+        userInfo: *mut c_void,
+    ) -> *mut c_void;
+    fn CGEventTapEnable(tap: *mut c_void, enable: bool);
+    fn CGEventGetIntegerValueField(event: *mut c_void, field: u32) -> i64;
+    fn CGEventGetFlags(event: *mut c_void) -> u64;
+}
+
+type CGDisplayReconfigurationCallBack = extern "C" fn(display: u32, flags: u32, user_info: *mut c_void);
+
+/// Set in the flags of the reconfiguration callback made before a change
+const K_CG_DISPLAY_BEGIN_CONFIGURATION_FLAG: u32 = 1 << 0;
+
+type CGEventTapCallBack = extern "C" fn(
+    proxy: *mut c_void,
+    event_type: u32,
+    event: *mut c_void,
+    user_info: *mut c_void,
+) -> *mut c_void;
+
+#[link(name = "IOKit", kind = "framework")]
+extern "C" {
+    fn IOPSCopyPowerSourcesInfo() -> *const c_void;
+    fn IOPSGetProvidingPowerSourceType(snapshot: *const c_void) -> CFStringRef;
+}
+
+#[repr(C)]
+struct AudioObjectPropertyAddress {
+    selector: u32,
+    scope: u32,
+    element: u32,
+}
+
+type AudioObjectPropertyListenerProc = extern "C" fn(
+    object: u32,
+    address_count: u32,
+    addresses: *const AudioObjectPropertyAddress,
+    client_data: *mut c_void,
+) -> i32;
+
+#[link(name = "CoreAudio", kind = "framework")]
+extern "C" {
+    fn AudioObjectAddPropertyListener(
+        object: u32,
+        address: *const AudioObjectPropertyAddress,
+        listener: AudioObjectPropertyListenerProc,
+        client_data: *mut c_void,
+    ) -> i32;
+    fn AudioObjectSetPropertyData(
+        object: u32,
+        address: *const AudioObjectPropertyAddress,
+        qualifier_size: u32,
+        qualifier: *const c_void,
+        data_size: u32,
+        data: *const c_void,
+    ) -> i32;
+}
+
+#[link(name = "CoreFoundation", kind = "framework")]
+extern "C" {
+    fn CFRelease(cf: *const c_void);
+    fn CFMachPortCreateRunLoopSource(allocator: *const c_void, port: *mut c_void, order: isize) -> *mut c_void;
+    fn CFRunLoopGetCurrent() -> *mut c_void;
+    fn CFRunLoopAddSource(rl: *mut c_void, source: *mut c_void, mode: CFStringRef);
+    fn CFRunLoopRun();
+    static kCFRunLoopCommonModes: CFStringRef;
+}
+
+/// libc's `struct tm` as laid out on macOS
+#[repr(C)]
+struct Tm {
+    tm_sec: i32,
+    tm_min: i32,
+    tm_hour: i32,
+    tm_mday: i32,
+    tm_mon: i32,
+    tm_year: i32,
+    tm_wday: i32,
+    tm_yday: i32,
+    tm_isdst: i32,
+    tm_gmtoff: std::os::raw::c_long,
+    tm_zone: *const std::os::raw::c_char,
+}
+
+// libSystem
+extern "C" {
+    fn localtime_r(time: *const i64, result: *mut Tm) -> *mut Tm;
 }
 
 const K_CG_WINDOW_IMAGE_BOUNDS_IGNORE_FRAMING: u32 = 1 << 0;
@@ -52,18 +175,59 @@ const K_CG_WINDOW_LIST_OPTION_ON_SCREEN_ONLY: u32 = 1 << 0;
 const K_CG_WINDOW_LIST_OPTION_INCLUDING_WINDOW: u32 = 1 << 3;  // 0x08 - Include only this window
 const K_CG_WINDOW_LIST_EXCLUDE_DESKTOP_ELEMENTS: u32 = 1 << 4; // 0x10
 
+// Event tap constants for the global hotkey
+const K_CG_SESSION_EVENT_TAP: u32 = 1;
+const K_CG_HEAD_INSERT_EVENT_TAP: u32 = 0;
+const K_CG_EVENT_TAP_OPTION_LISTEN_ONLY: u32 = 1;
+const K_CG_EVENT_KEY_DOWN: u32 = 10;
+const K_CG_EVENT_TAP_DISABLED_BY_TIMEOUT: u32 = 0xFFFF_FFFE;
+const K_CG_KEYBOARD_EVENT_AUTOREPEAT: u32 = 8;
+const K_CG_KEYBOARD_EVENT_KEYCODE: u32 = 9;
+const K_CG_EVENT_FLAG_MASK_SHIFT: u64 = 1 << 17;
+const K_CG_EVENT_FLAG_MASK_CONTROL: u64 = 1 << 18;
+const K_CG_EVENT_FLAG_MASK_ALTERNATE: u64 = 1 << 19;
+const K_CG_EVENT_FLAG_MASK_COMMAND: u64 = 1 << 20;
+
+// Core Audio selectors for device hot-plug notifications
+const K_AUDIO_OBJECT_SYSTEM_OBJECT: u32 = 1;
+const K_AUDIO_OBJECT_PROPERTY_SCOPE_GLOBAL: u32 = u32::from_be_bytes(*b"glob");
+const K_AUDIO_OBJECT_PROPERTY_ELEMENT_MAIN: u32 = 0;
+const K_AUDIO_HARDWARE_PROPERTY_DEVICES: u32 = u32::from_be_bytes(*b"dev#");
+const K_AUDIO_HARDWARE_PROPERTY_DEFAULT_INPUT_DEVICE: u32 = u32::from_be_bytes(*b"dIn ");
+const K_AUDIO_HARDWARE_PROPERTY_RUN_LOOP: u32 = u32::from_be_bytes(*b"rnlp");
+
+// AppKit constants for the recording border overlay
+const NS_WINDOW_STYLE_MASK_BORDERLESS: u64 = 0;
+const NS_BACKING_STORE_BUFFERED: u64 = 2;
+const NS_STATUS_WINDOW_LEVEL: i64 = 25;
+const NS_WINDOW_COLLECTION_BEHAVIOR_CAN_JOIN_ALL_SPACES: u64 = 1 << 0;
+const NS_WINDOW_COLLECTION_BEHAVIOR_STATIONARY: u64 = 1 << 4;
+const NS_WINDOW_SHARING_NONE: u64 = 0;
+const NS_WINDOW_SHARING_READ_ONLY: u64 = 1;
+const NS_WINDOW_STYLE_MASK_TITLED: u64 = 1 << 0;
+const BORDER_WIDTH: f64 = 4.0;
+
+// AppKit constant and menu item tags for the menu bar status item; other
+// tags are the window id of a recording to stop
+const NS_VARIABLE_STATUS_ITEM_LENGTH: f64 = -1.0;
+const STATUS_TAG_HEADER: isize = -1;
+const STATUS_TAG_STOP_ALL: isize = -2;
+const STATUS_TAG_SHOW_APP: isize = -3;
+
 // Well-known dictionary keys
 fn cfstr(s: &'static str) -> CFString {
     CFString::from_static_string(s)
 }
 
-pub fn list_windows() -> Result<Vec<WindowInfo>> {
+/// On-screen normal windows, front to back, with the pid of their owner
+fn window_list() -> Result<Vec<(WindowInfo, i64)>> {
     let mask = K_CG_WINDOW_LIST_OPTION_ON_SCREEN_ONLY | K_CG_WINDOW_LIST_EXCLUDE_DESKTOP_ELEMENTS;
     let array_ref = unsafe { CGWindowListCopyWindowInfo(mask, 0) };
     if array_ref.is_null() {
         return Err(anyhow!("CGWindowListCopyWindowInfo returned null"));
     }
     let mut result = Vec::new();
+    let mut bundle_ids: std::collections::HashMap<i64, Option<String>> = std::collections::HashMap::new(); // Per owner, looked up once
 
     let count = unsafe { CFArrayGetCount(array_ref) } as isize;
     for idx in 0..count {
@@ -77,6 +241,7 @@ pub fn list_windows() -> Result<Vec<WindowInfo>> {
         let owner_name_key = cfstr("kCGWindowOwnerName");
         let name_key = cfstr("kCGWindowName");
         let layer_key = cfstr("kCGWindowLayer");
+        let owner_pid_key = cfstr("kCGWindowOwnerPID");
         let bounds_key = cfstr("kCGWindowBounds");
 
         let window_number: Option<i64> = unsafe {
@@ -112,6 +277,20 @@ pub fn list_windows() -> Result<Vec<WindowInfo>> {
             continue;
         }
 
+        let owner_pid: i64 = unsafe {
+            let mut out: *const c_void = std::ptr::null();
+            let found = CFDictionaryGetValueIfPresent(
+                dict.as_concrete_TypeRef(),
+                owner_pid_key.as_concrete_TypeRef() as *const c_void,
+                &mut out,
+            );
+            if found != 0 && !out.is_null() {
+                CFNumber::wrap_under_get_rule(out as CFNumberRef).to_i64()
+            } else {
+                None
+            }
+        }.unwrap_or(0);
+
         let owner_name: Option<String> = unsafe {
             let mut out: *const c_void = std::ptr::null();
             let found = CFDictionaryGetValueIfPresent(
@@ -214,7 +393,7 @@ pub fn list_windows() -> Result<Vec<WindowInfo>> {
             if owner.is_empty() && title.is_empty() {
                 continue;
             }
-            result.push(WindowInfo {
+            result.push((WindowInfo {
                 window_id: id as u64,
                 owner_name: owner,
                 window_title: title,
@@ -222,15 +401,111 @@ pub fn list_windows() -> Result<Vec<WindowInfo>> {
                 y: rect.origin.y as i32,
                 width: rect.size.width as i32,
                 height: rect.size.height as i32,
-            });
+                untitled_index: None, // Assigned by `WindowManager::refresh`
+                bundle_id: bundle_ids.entry(owner_pid).or_insert_with(|| bundle_identifier(owner_pid)).clone(),
+            }, owner_pid));
         }
     }
 
+    Ok(result)
+}
+
+/// Bundle identifier of the app running as `pid`, if it has one
+fn bundle_identifier(pid: i64) -> Option<String> {
+    objc::rc::autoreleasepool(|| unsafe {
+        let app: *mut Object = msg_send![
+            class!(NSRunningApplication),
+            runningApplicationWithProcessIdentifier: pid as i32
+        ];
+        if app.is_null() {
+            return None;
+        }
+        let id: *mut Object = msg_send![app, bundleIdentifier];
+        if id.is_null() {
+            return None;
+        }
+        let utf8: *const std::os::raw::c_char = msg_send![id, UTF8String];
+        (!utf8.is_null()).then(|| std::ffi::CStr::from_ptr(utf8).to_string_lossy().into_owned())
+    })
+}
+
+pub fn list_windows() -> Result<Vec<WindowInfo>> {
+    let mut result: Vec<WindowInfo> = window_list()?.into_iter().map(|(info, _)| info).collect();
+
     // Sort for stable display
     result.sort_by(|a, b| a.owner_name.cmp(&b.owner_name).then(a.window_title.cmp(&b.window_title)));
     Ok(result)
 }
 
+/// The frontmost normal window of another app (this app's windows are skipped)
+pub fn frontmost_window() -> Option<WindowInfo> {
+    let own_pid = std::process::id() as i64;
+    window_list()
+        .ok()?
+        .into_iter()
+        .find(|(info, pid)| *pid != own_pid && info.width > 1 && info.height > 1)
+        .map(|(info, _)| info)
+}
+
+/// Whether other apps' windows capture without content, the way they do
+/// while the screen recording permission is missing, or was revoked after
+/// this process last checked it. Samples a few windows; `None` when no
+/// other app has a window to try.
+pub fn captures_are_blank() -> Option<bool> {
+    let own_pid = std::process::id() as i64;
+    let probes: Vec<u64> = window_list()
+        .ok()?
+        .into_iter()
+        .filter(|(info, pid)| *pid != own_pid && info.width > 1 && info.height > 1)
+        .take(3)
+        .map(|(info, _)| info.window_id)
+        .collect();
+    if probes.is_empty() {
+        return None;
+    }
+    Some(probes.iter().all(|id| match capture_window_image(*id) {
+        Some((rgba, _, _)) => is_flat_image(&rgba),
+        None => true,
+    }))
+}
+
+/// A capture that is one flat color (or fully transparent), sampled sparsely
+fn is_flat_image(rgba: &[u8]) -> bool {
+    let mut pixels = rgba.chunks_exact(4).step_by(97);
+    let Some(first) = pixels.next() else {
+        return true;
+    };
+    pixels.all(|pixel| pixel == first)
+}
+
+/// Today's date in the local time zone as (year, month 1-12, day 1-31)
+pub fn local_date() -> (i32, u32, u32) {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64);
+    // `Tm` matches the C layout and is plain data, so all-zero is valid
+    let mut tm: Tm = unsafe { std::mem::zeroed() };
+    if unsafe { localtime_r(&now, &mut tm) }.is_null() {
+        return (1970, 1, 1);
+    }
+    (tm.tm_year + 1900, (tm.tm_mon + 1) as u32, tm.tm_mday as u32)
+}
+
+/// Whether this process can reach the window server and a display is
+/// active. Neither holds over SSH or on a Mac with every display off, and
+/// then no window can be opened or captured.
+pub fn has_display_session() -> bool {
+    unsafe {
+        let session = CGSessionCopyCurrentDictionary();
+        if session.is_null() {
+            return false;
+        }
+        CFRelease(session as *const c_void);
+        let mut count: u32 = 0;
+        CGGetActiveDisplayList(0, std::ptr::null_mut(), &mut count) == 0 && count > 0
+    }
+}
+
 pub fn has_screen_capture_access() -> bool {
     unsafe { CGPreflightScreenCaptureAccess() }
 }
@@ -239,6 +514,159 @@ pub fn request_screen_capture_access() -> bool {
     unsafe { CGRequestScreenCaptureAccess() }
 }
 
+/// Display containing the window's center, or the main display if none does
+pub fn display_for_window(info: &WindowInfo) -> u32 {
+    let center = CGPoint::new(
+        info.x as f64 + info.width as f64 / 2.0,
+        info.y as f64 + info.height as f64 / 2.0,
+    );
+    let mut display: u32 = 0;
+    let mut count: u32 = 0;
+    let err = unsafe { CGGetDisplaysWithPoint(center, 1, &mut display, &mut count) };
+    if err == 0 && count > 0 {
+        display
+    } else {
+        unsafe { CGMainDisplayID() }
+    }
+}
+
+/// Whether the window's display is currently showing extended dynamic range
+/// content. Captures from it are clamped to SDR, so HDR highlights clip.
+pub fn display_is_hdr(info: &WindowInfo) -> bool {
+    let display = display_for_window(info);
+    unsafe {
+        let screens: *mut Object = msg_send![class!(NSScreen), screens];
+        if screens.is_null() {
+            return false;
+        }
+        let count: usize = msg_send![screens, count];
+        for i in 0..count {
+            let screen: *mut Object = msg_send![screens, objectAtIndex: i];
+            let description: *mut Object = msg_send![screen, deviceDescription];
+            let number: *mut Object = msg_send![description, objectForKey: nsstring("NSScreenNumber")];
+            if number.is_null() {
+                continue;
+            }
+            let screen_display: u32 = msg_send![number, unsignedIntValue];
+            if screen_display == display {
+                let headroom: f64 = msg_send![screen, maximumExtendedDynamicRangeColorComponentValue];
+                return headroom > 1.0;
+            }
+        }
+    }
+    false
+}
+
+/// Lowest and highest backing scale of the displays the window overlaps,
+/// when they differ. A capture is rendered at one scale, so the part of the
+/// window on the other display comes out resampled and looks soft.
+pub fn mixed_display_scales(info: &WindowInfo) -> Option<(f64, f64)> {
+    let scales = display_scales(info);
+    let low = scales.iter().copied().fold(f64::INFINITY, f64::min);
+    let high = scales.iter().copied().fold(0.0, f64::max);
+    (high - low > 0.01).then_some((low, high))
+}
+
+/// Backing scale of each display the window overlaps
+pub fn display_scales(info: &WindowInfo) -> Vec<f64> {
+    let mut displays = [0u32; 16];
+    let mut count: u32 = 0;
+    let err = unsafe { CGGetActiveDisplayList(displays.len() as u32, displays.as_mut_ptr(), &mut count) };
+    if err != 0 {
+        return Vec::new();
+    }
+    let (left, top) = (info.x as f64, info.y as f64);
+    let (right, bottom) = (left + info.width as f64, top + info.height as f64);
+    displays[..(count as usize).min(displays.len())]
+        .iter()
+        .filter(|&&display| {
+            let bounds = unsafe { CGDisplayBounds(display) };
+            left < bounds.origin.x + bounds.size.width
+                && bounds.origin.x < right
+                && top < bounds.origin.y + bounds.size.height
+                && bounds.origin.y < bottom
+        })
+        .filter_map(|&display| scale_of(display))
+        .collect()
+}
+
+/// Pixels per point of a display's current mode
+fn scale_of(display: u32) -> Option<f64> {
+    unsafe {
+        let mode = CGDisplayCopyDisplayMode(display);
+        if mode.is_null() {
+            return None;
+        }
+        let (points, pixels) = (CGDisplayModeGetWidth(mode), CGDisplayModeGetPixelWidth(mode));
+        CGDisplayModeRelease(mode);
+        (points > 0).then(|| pixels as f64 / points as f64)
+    }
+}
+
+/// Refresh rate of a display in whole Hz.
+/// Some displays report 0 from CGDisplayModeGetRefreshRate; fall back to 60 for those.
+fn refresh_rate_of(display: u32) -> i32 {
+    let rate = unsafe {
+        let mode = CGDisplayCopyDisplayMode(display);
+        if mode.is_null() {
+            0.0
+        } else {
+            let rate = CGDisplayModeGetRefreshRate(mode);
+            CGDisplayModeRelease(mode);
+            rate
+        }
+    };
+    if rate > 0.0 { rate.round() as i32 } else { 60 }
+}
+
+/// Refresh rate of the display the window is on
+pub fn display_refresh_rate(info: &WindowInfo) -> i32 {
+    refresh_rate_of(display_for_window(info))
+}
+
+/// Refresh rate of the main display
+pub fn main_display_refresh_rate() -> i32 {
+    refresh_rate_of(unsafe { CGMainDisplayID() })
+}
+
+/// Call `on_change` (on the main thread) after displays are added, removed
+/// or change mode, refresh rate included. The callback stays registered for
+/// the rest of the process.
+pub fn listen_for_display_changes(on_change: impl Fn() + Send + 'static) -> Result<()> {
+    let on_change: Box<Box<dyn Fn() + Send>> = Box::new(Box::new(on_change));
+    let client_data = Box::into_raw(on_change) as *mut c_void;
+    let status = unsafe { CGDisplayRegisterReconfigurationCallback(displays_changed, client_data) };
+    if status != 0 {
+        // Never registered, so nothing else holds the callback
+        drop(unsafe { Box::from_raw(client_data as *mut Box<dyn Fn() + Send>) });
+        return Err(anyhow!("cannot watch display changes (CGError {})", status));
+    }
+    Ok(())
+}
+
+extern "C" fn displays_changed(_display: u32, flags: u32, client_data: *mut c_void) {
+    // Each change is reported once before and once after; only the new
+    // configuration is worth reading
+    if flags & K_CG_DISPLAY_BEGIN_CONFIGURATION_FLAG != 0 {
+        return;
+    }
+    let on_change = unsafe { &*(client_data as *const Box<dyn Fn() + Send>) };
+    on_change();
+}
+
+/// Largest capture `capture_window_image` allocates a buffer for, 256 MiB:
+/// a window spanning two 6K displays at full resolution still fits
+const MAX_CAPTURE_BYTES: usize = 8192 * 8192 * 4;
+
+/// Bytes of an RGBA buffer for a `width`x`height` image, or None when that
+/// overflows or exceeds `MAX_CAPTURE_BYTES`
+fn rgba_buffer_len(width: usize, height: usize) -> Option<usize> {
+    width
+        .checked_mul(height)?
+        .checked_mul(4)
+        .filter(|&len| len <= MAX_CAPTURE_BYTES)
+}
+
 pub fn capture_window_image(window_id: u64) -> Option<(Vec<u8>, usize, usize)> {
     // Capture the window image  
     let cg_null_rect = core_graphics::geometry::CGRect::new(
@@ -268,12 +696,25 @@ pub fn capture_window_image(window_id: u64) -> Option<(Vec<u8>, usize, usize)> {
         return None;
     }
     
-    // Create bitmap context to render the image into RGBA format
+    // Create bitmap context to render the image into RGBA format, unless
+    // the reported size is too big to allocate
+    let Some(len) = rgba_buffer_len(width, height) else {
+        error!("Window {} reports an image of {}x{}, too large to capture", window_id, width, height);
+        unsafe { CGImageRelease(image_ptr) };
+        return None;
+    };
     let bytes_per_row = width * 4;
-    let mut buffer = vec![0u8; bytes_per_row * height];
+    let mut buffer = vec![0u8; len];
     
     unsafe {
-        let color_space = CGColorSpaceCreateDeviceRGB();
+        // A named sRGB target makes CoreGraphics convert from the display's
+        // space (P3, or extended range on HDR screens) and clamp to SDR. Device
+        // RGB would copy those values through unconverted, which blows out
+        // highlights and oversaturates colors once ffmpeg treats them as sRGB.
+        let mut color_space = CGColorSpaceCreateWithName(kCGColorSpaceSRGB);
+        if color_space.is_null() {
+            color_space = CGColorSpaceCreateDeviceRGB();
+        }
         let ctx = CGBitmapContextCreate(
             buffer.as_mut_ptr() as *mut std::ffi::c_void,
             width,
@@ -307,3 +748,528 @@ pub fn capture_window_image(window_id: u64) -> Option<(Vec<u8>, usize, usize)> {
     Some((buffer, width, height))
 }
 
+
+/// Autoreleased NSString; empty if `s` contains a NUL
+unsafe fn nsstring(s: &str) -> *mut Object {
+    let s = std::ffi::CString::new(s).unwrap_or_default();
+    msg_send![class!(NSString), stringWithUTF8String: s.as_ptr()]
+}
+
+/// Put an image from `capture_window_image` (premultiplied RGBA) on the
+/// general pasteboard as TIFF, replacing its contents
+pub fn copy_image_to_clipboard(rgba: &[u8], width: usize, height: usize) -> Result<()> {
+    let len = width * height * 4;
+    if width == 0 || height == 0 || rgba.len() < len {
+        return Err(anyhow!("invalid image buffer"));
+    }
+    objc::rc::autoreleasepool(|| unsafe {
+        // NULL planes: the rep allocates its own buffer, filled below
+        let rep: *mut Object = msg_send![class!(NSBitmapImageRep), alloc];
+        let rep: *mut Object = msg_send![rep,
+            initWithBitmapDataPlanes: std::ptr::null_mut::<*mut u8>()
+            pixelsWide: width as i64
+            pixelsHigh: height as i64
+            bitsPerSample: 8i64
+            samplesPerPixel: 4i64
+            hasAlpha: YES
+            isPlanar: NO
+            colorSpaceName: nsstring("NSDeviceRGBColorSpace")
+            bytesPerRow: (width * 4) as i64
+            bitsPerPixel: 32i64];
+        if rep.is_null() {
+            return Err(anyhow!("failed to create bitmap"));
+        }
+        let data: *mut u8 = msg_send![rep, bitmapData];
+        std::ptr::copy_nonoverlapping(rgba.as_ptr(), data, len);
+        // Captures are converted to sRGB; tagged as device RGB, a paste would
+        // be shown in the display's space and not match the saved files
+        let srgb: *mut Object = msg_send![class!(NSColorSpace), sRGBColorSpace];
+        let tagged: *mut Object = msg_send![rep, bitmapImageRepByRetaggingWithColorSpace: srgb];
+        if tagged.is_null() {
+            let _: () = msg_send![rep, release];
+            return Err(anyhow!("failed to tag the bitmap as sRGB"));
+        }
+        let tiff: *mut Object = msg_send![tagged, TIFFRepresentation];
+
+        let pasteboard: *mut Object = msg_send![class!(NSPasteboard), generalPasteboard];
+        let _: i64 = msg_send![pasteboard, clearContents];
+        let written: BOOL = msg_send![pasteboard, setData: tiff forType: nsstring("public.tiff")];
+        let _: () = msg_send![rep, release];
+        if written == YES {
+            Ok(())
+        } else {
+            Err(anyhow!("the clipboard did not accept the image"))
+        }
+    })
+}
+
+/// Replace a file's Finder tags. Spotlight indexes them (kMDItemUserTags),
+/// so tagged recordings can be found and sorted later.
+pub fn set_finder_tags(path: &std::path::Path, tags: &[String]) -> Result<()> {
+    let path_str = path.to_str().ok_or_else(|| anyhow!("path is not valid UTF-8"))?;
+    objc::rc::autoreleasepool(|| unsafe {
+        let url: *mut Object = msg_send![class!(NSURL), fileURLWithPath: nsstring(path_str)];
+        let names: *mut Object = msg_send![class!(NSMutableArray), array];
+        for tag in tags {
+            let _: () = msg_send![names, addObject: nsstring(tag)];
+        }
+        let mut error: *mut Object = std::ptr::null_mut();
+        let set: BOOL = msg_send![url, setResourceValue: names forKey: nsstring("NSURLTagNamesKey") error: &mut error];
+        if set == YES {
+            Ok(())
+        } else {
+            Err(anyhow!("Finder did not accept the tags"))
+        }
+    })
+}
+
+/// Capture rate a window can sustain, from the average time of a few captures
+pub fn measure_capture_rate(window_id: u64, samples: u32) -> Option<f64> {
+    let started = std::time::Instant::now();
+    for _ in 0..samples.max(1) {
+        capture_window_image(window_id)?;
+    }
+    Some(samples.max(1) as f64 / started.elapsed().as_secs_f64().max(f64::EPSILON))
+}
+
+/// State behind the hotkey event tap, leaked for the lifetime of its thread
+struct HotkeyTap {
+    hotkey: Arc<Mutex<Option<Hotkey>>>,
+    on_press: Box<dyn Fn() + Send>,
+    port: *mut c_void,
+}
+
+extern "C" fn hotkey_tap_callback(
+    _proxy: *mut c_void,
+    event_type: u32,
+    event: *mut c_void,
+    user_info: *mut c_void,
+) -> *mut c_void {
+    let tap = unsafe { &*(user_info as *const HotkeyTap) };
+    if event_type == K_CG_EVENT_TAP_DISABLED_BY_TIMEOUT {
+        unsafe { CGEventTapEnable(tap.port, true) };
+        return event;
+    }
+    if event_type != K_CG_EVENT_KEY_DOWN {
+        return event;
+    }
+    let (key_code, repeat, flags) = unsafe {
+        (
+            CGEventGetIntegerValueField(event, K_CG_KEYBOARD_EVENT_KEYCODE),
+            CGEventGetIntegerValueField(event, K_CG_KEYBOARD_EVENT_AUTOREPEAT),
+            CGEventGetFlags(event),
+        )
+    };
+    let hit = tap.hotkey.lock().is_some_and(|hotkey| {
+        hotkey.matches(
+            key_code as u16,
+            flags & K_CG_EVENT_FLAG_MASK_COMMAND != 0,
+            flags & K_CG_EVENT_FLAG_MASK_SHIFT != 0,
+            flags & K_CG_EVENT_FLAG_MASK_ALTERNATE != 0,
+            flags & K_CG_EVENT_FLAG_MASK_CONTROL != 0,
+        )
+    });
+    if hit && repeat == 0 {
+        (tap.on_press)();
+    }
+    event // Listen-only: the event always goes on to the focused app
+}
+
+/// Watch key presses system-wide on a background run loop and call
+/// `on_press` (on that thread) whenever the current `hotkey` is hit. Fails if
+/// the event tap can't be created, usually because Input Monitoring
+/// permission is missing.
+pub fn spawn_hotkey_listener(
+    hotkey: Arc<Mutex<Option<Hotkey>>>,
+    on_press: impl Fn() + Send + 'static,
+) -> Result<()> {
+    let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || unsafe {
+        let on_press: Box<dyn Fn() + Send> = Box::new(on_press);
+        let tap = Box::into_raw(Box::new(HotkeyTap { hotkey, on_press, port: std::ptr::null_mut() }));
+        let port = CGEventTapCreate(
+            K_CG_SESSION_EVENT_TAP,
+            K_CG_HEAD_INSERT_EVENT_TAP,
+            K_CG_EVENT_TAP_OPTION_LISTEN_ONLY,
+            1 << K_CG_EVENT_KEY_DOWN,
+            hotkey_tap_callback,
+            tap as *mut c_void,
+        );
+        if port.is_null() {
+            drop(Box::from_raw(tap));
+            let _ = ready_tx.send(false);
+            return;
+        }
+        (*tap).port = port;
+        let source = CFMachPortCreateRunLoopSource(std::ptr::null(), port, 0);
+        CFRunLoopAddSource(CFRunLoopGetCurrent(), source, kCFRunLoopCommonModes);
+        CGEventTapEnable(port, true);
+        let _ = ready_tx.send(true);
+        CFRunLoopRun();
+    });
+    match ready_rx.recv() {
+        Ok(true) => Ok(()),
+        _ => Err(anyhow!("cannot listen for the hotkey; allow Input Monitoring in System Settings > Privacy & Security")),
+    }
+}
+
+/// Call `on_change` (on a Core Audio thread) whenever an audio device is
+/// connected or disconnected, or the default input changes. The listener
+/// stays registered for the rest of the process.
+pub fn listen_for_audio_device_changes(on_change: impl Fn() + Send + 'static) -> Result<()> {
+    let address = |selector| AudioObjectPropertyAddress {
+        selector,
+        scope: K_AUDIO_OBJECT_PROPERTY_SCOPE_GLOBAL,
+        element: K_AUDIO_OBJECT_PROPERTY_ELEMENT_MAIN,
+    };
+    unsafe {
+        // Deliver notifications on Core Audio's own thread instead of the
+        // main run loop, which winit doesn't always spin
+        let run_loop: *const c_void = std::ptr::null();
+        AudioObjectSetPropertyData(
+            K_AUDIO_OBJECT_SYSTEM_OBJECT,
+            &address(K_AUDIO_HARDWARE_PROPERTY_RUN_LOOP),
+            0,
+            std::ptr::null(),
+            std::mem::size_of::<*const c_void>() as u32,
+            &run_loop as *const _ as *const c_void,
+        );
+        
+        let on_change: Box<Box<dyn Fn() + Send>> = Box::new(Box::new(on_change));
+        let client_data = Box::into_raw(on_change) as *mut c_void;
+        for selector in [K_AUDIO_HARDWARE_PROPERTY_DEVICES, K_AUDIO_HARDWARE_PROPERTY_DEFAULT_INPUT_DEVICE] {
+            let status = AudioObjectAddPropertyListener(
+                K_AUDIO_OBJECT_SYSTEM_OBJECT,
+                &address(selector),
+                audio_devices_changed,
+                client_data,
+            );
+            if status != 0 {
+                return Err(anyhow!("cannot watch audio devices (OSStatus {})", status));
+            }
+        }
+    }
+    Ok(())
+}
+
+extern "C" fn audio_devices_changed(
+    _object: u32,
+    _address_count: u32,
+    _addresses: *const AudioObjectPropertyAddress,
+    client_data: *mut c_void,
+) -> i32 {
+    let on_change = unsafe { &*(client_data as *const Box<dyn Fn() + Send>) };
+    on_change();
+    0
+}
+
+/// Play a short system sound, as feedback while the app may be in the background
+pub fn play_sound(name: &'static str) {
+    objc::rc::autoreleasepool(|| unsafe {
+        let sound: *mut Object = msg_send![class!(NSSound), soundNamed: nsstring(name)];
+        if !sound.is_null() {
+            let _: BOOL = msg_send![sound, play];
+        }
+    });
+}
+
+/// Whether the Mac is currently running on battery
+pub fn on_battery_power() -> bool {
+    unsafe {
+        let info = IOPSCopyPowerSourcesInfo();
+        if info.is_null() {
+            return false;
+        }
+        let source = IOPSGetProvidingPowerSourceType(info);
+        let on_battery = !source.is_null()
+            && CFString::wrap_under_get_rule(source) == cfstr("Battery Power");
+        CFRelease(info);
+        on_battery
+    }
+}
+
+/// How many windows this app has open, of any kind
+pub fn app_window_count() -> usize {
+    unsafe {
+        let app: *mut Object = msg_send![class!(NSApplication), sharedApplication];
+        let windows: *mut Object = msg_send![app, windows];
+        msg_send![windows, count]
+    }
+}
+
+/// Ask for this app's titled windows to be left out of screen captures, or
+/// let them back in. Only honored by capture APIs that check the window's
+/// sharing type; ScreenCaptureKit ignores it on recent macOS. Overlays are
+/// borderless and stay hidden either way.
+pub fn set_app_windows_capturable(capturable: bool) {
+    let sharing = if capturable { NS_WINDOW_SHARING_READ_ONLY } else { NS_WINDOW_SHARING_NONE };
+    unsafe {
+        let app: *mut Object = msg_send![class!(NSApplication), sharedApplication];
+        let windows: *mut Object = msg_send![app, windows];
+        let count: usize = msg_send![windows, count];
+        for index in 0..count {
+            let window: *mut Object = msg_send![windows, objectAtIndex: index];
+            let style: u64 = msg_send![window, styleMask];
+            if style & NS_WINDOW_STYLE_MASK_TITLED != 0 {
+                let _: () = msg_send![window, setSharingType: sharing];
+            }
+        }
+    }
+}
+
+/// Click-through, always-on-top borderless window drawing a red border just
+/// outside a recorded window. AppKit requires this to live on the main thread.
+pub struct BorderOverlay {
+    window: *mut Object,
+    bounds: WindowBounds,
+}
+
+impl BorderOverlay {
+    pub fn new(bounds: WindowBounds) -> Option<Self> {
+        unsafe {
+            let window: *mut Object = msg_send![class!(NSWindow), alloc];
+            let window: *mut Object = msg_send![window,
+                initWithContentRect: overlay_frame(bounds)
+                styleMask: NS_WINDOW_STYLE_MASK_BORDERLESS
+                backing: NS_BACKING_STORE_BUFFERED
+                defer: NO];
+            if window.is_null() {
+                return None;
+            }
+
+            let clear: *mut Object = msg_send![class!(NSColor), clearColor];
+            let _: () = msg_send![window, setOpaque: NO];
+            let _: () = msg_send![window, setBackgroundColor: clear];
+            let _: () = msg_send![window, setHasShadow: NO];
+            let _: () = msg_send![window, setIgnoresMouseEvents: YES];
+            let _: () = msg_send![window, setReleasedWhenClosed: NO];
+            let _: () = msg_send![window, setLevel: NS_STATUS_WINDOW_LEVEL];
+            // Keep the border itself out of any screen capture
+            let _: () = msg_send![window, setSharingType: NS_WINDOW_SHARING_NONE];
+            let _: () = msg_send![window, setCollectionBehavior:
+                NS_WINDOW_COLLECTION_BEHAVIOR_CAN_JOIN_ALL_SPACES | NS_WINDOW_COLLECTION_BEHAVIOR_STATIONARY];
+
+            // Draw the border with the content view's backing layer
+            let view: *mut Object = msg_send![window, contentView];
+            let _: () = msg_send![view, setWantsLayer: YES];
+            let layer: *mut Object = msg_send![view, layer];
+            let color = CGColorCreateGenericRGB(0.86, 0.21, 0.27, 1.0);
+            let _: () = msg_send![layer, setBorderColor: color];
+            CGColorRelease(color);
+            let _: () = msg_send![layer, setBorderWidth: BORDER_WIDTH];
+            let _: () = msg_send![layer, setCornerRadius: 6.0f64];
+
+            let _: () = msg_send![window, orderFrontRegardless];
+            Some(Self { window, bounds })
+        }
+    }
+
+    /// Move/resize the border to follow the window's current bounds
+    pub fn update(&mut self, bounds: WindowBounds) {
+        if bounds != self.bounds {
+            unsafe {
+                let _: () = msg_send![self.window, setFrame: overlay_frame(bounds) display: YES];
+            }
+            self.bounds = bounds;
+        }
+    }
+}
+
+impl Drop for BorderOverlay {
+    fn drop(&mut self) {
+        unsafe {
+            let _: () = msg_send![self.window, orderOut: std::ptr::null_mut::<Object>()];
+            let _: () = msg_send![self.window, close];
+            let _: () = msg_send![self.window, release];
+        }
+    }
+}
+
+/// Cocoa frame (bottom-left origin) of a border around the window's CG bounds (top-left origin)
+fn overlay_frame(bounds: WindowBounds) -> CGRect {
+    let main_height = unsafe { CGDisplayBounds(CGMainDisplayID()) }.size.height;
+    let x = bounds.x as f64 - BORDER_WIDTH;
+    let y = main_height - (bounds.y as f64 + bounds.height as f64) - BORDER_WIDTH;
+    CGRect::new(
+        &CGPoint::new(x, y),
+        &CGSize::new(
+            bounds.width as f64 + 2.0 * BORDER_WIDTH,
+            bounds.height as f64 + 2.0 * BORDER_WIDTH,
+        ),
+    )
+}
+
+/// What a pick in the status item's menu asks the app to do
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StatusItemAction {
+    Stop(u64),
+    StopAll,
+}
+
+/// Menu bar item: a dot that turns red while anything records, with a menu
+/// to stop recordings or bring the app forward. Works while the app's window
+/// is hidden. AppKit requires this to live on the main thread.
+pub struct StatusItem {
+    item: *mut Object,
+    target: *mut Object, // Receives menu picks; owns the boxed callback
+    listed: Option<Vec<(u64, String)>>, // Recordings the menu shows; None before the first update
+}
+
+impl StatusItem {
+    /// Add the item to the menu bar; menu picks other than showing the app
+    /// are passed to `on_action` on the main thread
+    pub fn new(on_action: impl Fn(StatusItemAction) + 'static) -> Option<Self> {
+        unsafe {
+            let bar: *mut Object = msg_send![class!(NSStatusBar), systemStatusBar];
+            let item: *mut Object = msg_send![bar, statusItemWithLength: NS_VARIABLE_STATUS_ITEM_LENGTH];
+            if item.is_null() {
+                return None;
+            }
+            let _: () = msg_send![item, retain];
+
+            let target: *mut Object = msg_send![status_target_class(), new];
+            let callback: Box<Box<dyn Fn(StatusItemAction)>> = Box::new(Box::new(on_action));
+            (*target).set_ivar::<usize>("callback", Box::into_raw(callback) as usize);
+
+            let mut status = Self { item, target, listed: None };
+            status.update(&[]);
+            Some(status)
+        }
+    }
+
+    /// Show `recordings` (window id and name) in the menu, and the dot in red
+    /// when there are any. Only rebuilds the menu when they change.
+    pub fn update(&mut self, recordings: &[(u64, String)]) {
+        if self.listed.as_deref() == Some(recordings) {
+            return;
+        }
+        unsafe {
+            let button: *mut Object = msg_send![self.item, button];
+            let color: *mut Object = if recordings.is_empty() {
+                msg_send![class!(NSColor), secondaryLabelColor]
+            } else {
+                msg_send![class!(NSColor), systemRedColor]
+            };
+            // NSForegroundColorAttributeName
+            let attributes: *mut Object = msg_send![class!(NSDictionary), dictionaryWithObject: color forKey: nsstring("NSColor")];
+            let title: *mut Object = msg_send![class!(NSAttributedString), alloc];
+            let title: *mut Object = msg_send![title, initWithString: nsstring("●") attributes: attributes];
+            let _: () = msg_send![button, setAttributedTitle: title];
+            let _: () = msg_send![title, release];
+
+            let menu: *mut Object = msg_send![class!(NSMenu), new];
+            let _: () = msg_send![menu, setAutoenablesItems: NO];
+            let header = match recordings.len() {
+                0 => "Not recording".to_string(),
+                1 => "Recording 1 window".to_string(),
+                n => format!("Recording {} windows", n),
+            };
+            self.add_menu_item(menu, &header, STATUS_TAG_HEADER);
+            for (window_id, name) in recordings {
+                self.add_menu_item(menu, &format!("Stop {}", name), *window_id as isize);
+            }
+            if !recordings.is_empty() {
+                let _: () = msg_send![menu, addItem: separator_item()];
+                self.add_menu_item(menu, "Stop All", STATUS_TAG_STOP_ALL);
+            }
+            let _: () = msg_send![menu, addItem: separator_item()];
+            self.add_menu_item(menu, "Show Screen Recorder", STATUS_TAG_SHOW_APP);
+            let _: () = msg_send![self.item, setMenu: menu];
+            let _: () = msg_send![menu, release];
+        }
+        self.listed = Some(recordings.to_vec());
+    }
+
+    unsafe fn add_menu_item(&self, menu: *mut Object, title: &str, tag: isize) {
+        let item: *mut Object = msg_send![class!(NSMenuItem), alloc];
+        let item: *mut Object = msg_send![item,
+            initWithTitle: nsstring(title)
+            action: sel!(menuItemClicked:)
+            keyEquivalent: nsstring("")];
+        let _: () = msg_send![item, setTarget: self.target];
+        let _: () = msg_send![item, setTag: tag];
+        let _: () = msg_send![item, setEnabled: if tag == STATUS_TAG_HEADER { NO } else { YES }];
+        let _: () = msg_send![menu, addItem: item];
+        let _: () = msg_send![item, release];
+    }
+}
+
+impl Drop for StatusItem {
+    fn drop(&mut self) {
+        unsafe {
+            let bar: *mut Object = msg_send![class!(NSStatusBar), systemStatusBar];
+            let _: () = msg_send![bar, removeStatusItem: self.item];
+            let _: () = msg_send![self.item, release];
+
+            let callback = *(*self.target).get_ivar::<usize>("callback") as *mut Box<dyn Fn(StatusItemAction)>;
+            (*self.target).set_ivar::<usize>("callback", 0);
+            let _: () = msg_send![self.target, release];
+            if !callback.is_null() {
+                drop(Box::from_raw(callback));
+            }
+        }
+    }
+}
+
+unsafe fn separator_item() -> *mut Object {
+    msg_send![class!(NSMenuItem), separatorItem]
+}
+
+/// NSObject subclass that forwards status menu picks to a `StatusItem`'s callback
+fn status_target_class() -> &'static Class {
+    static REGISTER: Once = Once::new();
+    REGISTER.call_once(|| {
+        let mut decl = ClassDecl::new("ScreencastStatusTarget", class!(NSObject))
+            .expect("status target class is registered once");
+        decl.add_ivar::<usize>("callback");
+        unsafe {
+            decl.add_method(
+                sel!(menuItemClicked:),
+                status_menu_item_clicked as extern "C" fn(&Object, Sel, *mut Object),
+            );
+        }
+        decl.register();
+    });
+    class!(ScreencastStatusTarget)
+}
+
+extern "C" fn status_menu_item_clicked(this: &Object, _: Sel, sender: *mut Object) {
+    unsafe {
+        let tag: isize = msg_send![sender, tag];
+        let action = match tag {
+            STATUS_TAG_HEADER => return,
+            STATUS_TAG_SHOW_APP => {
+                let app: *mut Object = msg_send![class!(NSApplication), sharedApplication];
+                let _: () = msg_send![app, unhide: std::ptr::null_mut::<Object>()];
+                let _: () = msg_send![app, activateIgnoringOtherApps: YES];
+                return;
+            }
+            STATUS_TAG_STOP_ALL => StatusItemAction::StopAll,
+            window_id => StatusItemAction::Stop(window_id as u64),
+        };
+        let callback = *this.get_ivar::<usize>("callback") as *const Box<dyn Fn(StatusItemAction)>;
+        if let Some(callback) = callback.as_ref() {
+            callback(action);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rgba_buffer_len_fits_real_windows() {
+        assert_eq!(rgba_buffer_len(1, 1), Some(4));
+        assert_eq!(rgba_buffer_len(2 * 6016, 3384), Some(2 * 6016 * 3384 * 4));
+        assert_eq!(rgba_buffer_len(8192, 8192), Some(MAX_CAPTURE_BYTES));
+    }
+
+    #[test]
+    fn rgba_buffer_len_rejects_absurd_sizes() {
+        assert_eq!(rgba_buffer_len(8192, 8193), None);
+        assert_eq!(rgba_buffer_len(usize::MAX, 2), None);
+        // width * height fits, the 4 bytes per pixel don't
+        assert_eq!(rgba_buffer_len(usize::MAX / 2, 1), None);
+    }
+}
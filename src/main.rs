@@ -1,43 +1,134 @@
-mod window;
-mod recorder;
-mod ffmpeg;
-mod audio;
+use screencast::{audio, control, error, events, ffmpeg, hotkey, recorder, screenshot, settings, window};
 
 #[cfg(target_os = "macos")]
-mod macos;
+use screencast::macos;
 
 use std::path::PathBuf;
 use std::time::{Duration, Instant};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Receiver;
 
+use clap::{ArgGroup, Parser};
 use eframe::egui;
 use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
 use tracing::{error, info};
 
-use window::WindowManager;
-use recorder::{RecorderState, RecordingConfig};
-use ffmpeg::{find_ffmpeg, start_ffmpeg_for_window, send_quit_and_wait};
-use audio::{AudioDeviceManager, debug_list_audio_devices};
+use window::{WindowInfo, WindowManager, sort_by_saved_order};
+use recorder::{AppFolder, AudioMonitoring, LowPowerMode, QualityPreset, RecorderState, RecordingConfig};
+use ffmpeg::{AudioBackend, AudioCodec, Canvas, CaptureHealth, Container, EvenSize, CropRegion, Mp4Layout, PixelFormat, ProResProfile, RateControl, Rotation, StreamEnd, VideoEncoder, X264Preset, bits_per_pixel, bpp_quality, dry_run_command, find_ffmpeg, scaled_size, start_ffmpeg_for_window, suggest_bitrate};
+use audio::{AudioDeviceManager, SilenceTimer, debug_list_audio_devices};
+use error::RecorderError;
+use events::RecordingEvent;
+use hotkey::Hotkey;
+use settings::RecordingProfile;
 
-// Cache for window preview textures with throttling
+/// Encoders offered in the settings and per-window overrides
+const ENCODERS: [VideoEncoder; 5] = [
+    VideoEncoder::Libx264,
+    VideoEncoder::H264VideoToolbox,
+    VideoEncoder::H264VideoToolboxFallback,
+    VideoEncoder::HevcVideoToolbox,
+    VideoEncoder::ProResVideoToolbox,
+];
+
+/// How often recorded windows' bounds are re-read while recording
+const BOUNDS_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How often recordings that failed are looked for to tear down, and for how
+/// long after failing: a failure can come in before its start thread has
+/// registered the recording
+const FAILURE_REAP_INTERVAL: Duration = Duration::from_millis(250);
+const FAILURE_REAP_WINDOW: Duration = Duration::from_secs(5);
+
+/// A failure stays on its window's row this long unless it's retried first
+const FAILURE_SHOWN_FOR: Duration = Duration::from_secs(10 * 60);
+
+/// Mini mode strip: width, and height without / per active recording
+const MINI_WIDTH: f32 = 360.0;
+const MINI_BASE_HEIGHT: f32 = 44.0;
+const MINI_ROW_HEIGHT: f32 = 26.0;
+
+/// Restored when leaving mini mode if the normal size wasn't seen (started in mini mode)
+const NORMAL_WINDOW_SIZE: egui::Vec2 = egui::vec2(900.0, 700.0);
+
+/// Largest side of the expanded preview, and the smallest it shrinks to for
+/// very tall or wide windows
+const PREVIEW_MAX_SIZE: usize = 400;
+const PREVIEW_MIN_SIZE: f32 = 120.0;
+
+/// Preview textures uploaded per UI frame; further finished captures wait
+/// for the next frames, so many previews refreshing at once don't spike the GPU
+const PREVIEW_UPLOADS_PER_FRAME: usize = 2;
+
+/// How often a missing screen recording permission is re-checked
+const PERMISSION_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Output folders remembered for the "recent" menus
+const RECENT_DIRS_LIMIT: usize = 8;
+
+/// A recording whose file hasn't grown for this long is flagged in its row
+const OUTPUT_STALL_WARNING: Duration = Duration::from_secs(10);
+
+/// Matches listed in the quick-record palette
+const QUICK_RECORD_RESULTS: usize = 8;
+
+/// Length of the clip "Record 3s & play" records
+const AUDIO_CHECK_LENGTH: Duration = Duration::from_secs(3);
+
+/// Peaks of an audio check below this count as silence, and above this as clipping
+const AUDIO_CHECK_SILENT: f32 = 0.01;
+const AUDIO_CHECK_CLIPPING: f32 = 0.99;
+
+/// Finder's color labels: a tag with one of these names shows in its color
+#[cfg(target_os = "macos")]
+const FINDER_COLOR_TAGS: [(&str, egui::Color32); 7] = [
+    ("Red", egui::Color32::from_rgb(255, 69, 58)),
+    ("Orange", egui::Color32::from_rgb(255, 159, 10)),
+    ("Yellow", egui::Color32::from_rgb(255, 214, 10)),
+    ("Green", egui::Color32::from_rgb(48, 209, 88)),
+    ("Blue", egui::Color32::from_rgb(10, 132, 255)),
+    ("Purple", egui::Color32::from_rgb(191, 90, 242)),
+    ("Gray", egui::Color32::from_rgb(152, 152, 157)),
+];
+
+// Cache for window preview textures with throttling. Captures run on a
+// background thread; the previous texture stays up until the new one arrives.
 struct PreviewCache {
     textures: HashMap<u64, egui::TextureHandle>,
-    last_update: HashMap<u64, Instant>,
+    last_update: HashMap<u64, Instant>, // When each texture was captured
+    last_request: HashMap<u64, Instant>, // When a capture was last started, successful or not
+    pending: HashSet<u64>, // Captures in flight or waiting to be uploaded
+    frames_tx: std::sync::mpsc::Sender<(u64, Option<egui::ColorImage>)>,
+    frames_rx: Receiver<(u64, Option<egui::ColorImage>)>,
+    uploads: VecDeque<(u64, egui::ColorImage)>, // Finished captures over this frame's upload budget
+    uploads_left: (u64, usize), // Pass number and the uploads it has left
     update_interval: Duration,
 }
 
 impl PreviewCache {
     fn new() -> Self {
+        let (frames_tx, frames_rx) = std::sync::mpsc::channel();
         Self {
             textures: HashMap::new(),
             last_update: HashMap::new(),
+            last_request: HashMap::new(),
+            pending: HashSet::new(),
+            frames_tx,
+            frames_rx,
+            uploads: VecDeque::new(),
+            uploads_left: (0, PREVIEW_UPLOADS_PER_FRAME),
             update_interval: Duration::from_millis(1000), // Update preview every 1000ms max
         }
     }
     
     fn should_update(&self, window_id: u64) -> bool {
-        match self.last_update.get(&window_id) {
+        if self.pending.contains(&window_id) {
+            return false;
+        }
+        match self.last_request.get(&window_id) {
             Some(last) => last.elapsed() >= self.update_interval,
             None => true, // Never updated, should update
         }
@@ -47,31 +138,76 @@ impl PreviewCache {
         &mut self,
         ctx: &egui::Context,
         window_id: u64,
-        capture_fn: impl FnOnce() -> Option<(Vec<u8>, usize, usize)>,
+        capture_fn: impl FnOnce() -> Option<(Arc<Vec<u8>>, usize, usize)> + Send + 'static,
     ) -> Option<&egui::TextureHandle> {
+        self.receive_frames(ctx);
         if self.should_update(window_id) {
-            if let Some((buffer, width, height)) = capture_fn() {
-                // Downscale image for preview to reduce memory and GPU load
-                let (small_buffer, small_width, small_height) = 
-                    downscale_image(&buffer, width, height, 512); // Max 512px width
-                
-                let image = egui::ColorImage::from_rgba_unmultiplied(
-                    [small_width, small_height],
-                    &small_buffer,
-                );
-                let texture = ctx.load_texture(
-                    format!("card_preview_{}", window_id),
-                    image,
-                    egui::TextureOptions::LINEAR,
-                );
-                
-                self.textures.insert(window_id, texture);
-                self.last_update.insert(window_id, Instant::now());
-            }
+            self.pending.insert(window_id);
+            self.last_request.insert(window_id, Instant::now());
+            let frames_tx = self.frames_tx.clone();
+            let ctx = ctx.clone();
+            std::thread::spawn(move || {
+                let image = capture_fn().map(|(buffer, width, height)| {
+                    // Downscale image for preview to reduce memory and GPU load
+                    let (mut small_buffer, small_width, small_height) =
+                        downscale_image(&buffer, width, height, 512); // Max 512px width
+                    
+                    // Captures are premultiplied; averaging them while downscaling is
+                    // still right, but egui wants straight alpha here
+                    screenshot::unpremultiply(&mut small_buffer);
+                    egui::ColorImage::from_rgba_unmultiplied([small_width, small_height], &small_buffer)
+                });
+                let _ = frames_tx.send((window_id, image));
+                ctx.request_repaint();
+            });
         }
         
         self.textures.get(&window_id)
     }
+    
+    /// Upload finished captures, oldest first, up to this frame's budget
+    fn receive_frames(&mut self, ctx: &egui::Context) {
+        while let Ok((window_id, image)) = self.frames_rx.try_recv() {
+            match image {
+                Some(image) => self.uploads.push_back((window_id, image)),
+                None => {
+                    self.pending.remove(&window_id);
+                }
+            }
+        }
+        
+        let frame = ctx.cumulative_pass_nr();
+        if self.uploads_left.0 != frame {
+            self.uploads_left = (frame, PREVIEW_UPLOADS_PER_FRAME);
+        }
+        while self.uploads_left.1 > 0 {
+            let Some((window_id, image)) = self.uploads.pop_front() else {
+                break;
+            };
+            let texture = ctx.load_texture(
+                format!("card_preview_{}", window_id),
+                image,
+                egui::TextureOptions::LINEAR,
+            );
+            self.textures.insert(window_id, texture);
+            self.last_update.insert(window_id, Instant::now());
+            self.pending.remove(&window_id);
+            self.uploads_left.1 -= 1;
+        }
+        if !self.uploads.is_empty() {
+            ctx.request_repaint();
+        }
+    }
+    
+    /// A capture has been started for this window and hasn't come back yet
+    fn is_pending(&self, window_id: u64) -> bool {
+        self.pending.contains(&window_id)
+    }
+    
+    /// How old a window's cached preview is
+    fn age(&self, window_id: u64) -> Option<Duration> {
+        self.last_update.get(&window_id).map(|t| t.elapsed())
+    }
 }
 
 // Downscale RGBA image to reduce preview size
@@ -80,17 +216,16 @@ fn downscale_image(buffer: &[u8], width: usize, height: usize, max_width: usize)
         return (buffer.to_vec(), width, height);
     }
     
-    let scale = max_width as f32 / width as f32;
-    let new_width = max_width;
-    let new_height = (height as f32 * scale) as usize;
+    let (new_width, new_height) = scaled_size(width, height, Some(max_width), None, true);
+    let scale = new_width as f32 / width as f32;
     
     let mut result = vec![0u8; new_width * new_height * 4];
     
     // Simple nearest-neighbor downscaling (fast)
     for y in 0..new_height {
         for x in 0..new_width {
-            let src_x = (x as f32 / scale) as usize;
-            let src_y = (y as f32 / scale) as usize;
+            let src_x = ((x as f32 / scale) as usize).min(width - 1);
+            let src_y = ((y as f32 / scale) as usize).min(height - 1);
             
             let src_idx = (src_y * width + src_x) * 4;
             let dst_idx = (y * new_width + x) * 4;
@@ -102,11 +237,194 @@ fn downscale_image(buffer: &[u8], width: usize, height: usize, max_width: usize)
     (result, new_width, new_height)
 }
 
+// Size of the expanded preview box for a window, matching its aspect ratio once rotated
+fn preview_box(window: &WindowInfo, rotation: Rotation) -> (f32, f32) {
+    let (width, height) = rotation.apply(window.width.max(1) as usize, window.height.max(1) as usize);
+    let (width, height) = scaled_size(
+        width,
+        height,
+        Some(PREVIEW_MAX_SIZE),
+        Some(PREVIEW_MAX_SIZE),
+        true,
+    );
+    ((width as f32).max(PREVIEW_MIN_SIZE), (height as f32).max(PREVIEW_MIN_SIZE))
+}
+
+// A drag or click in progress, or a focused text field: rebuilding the window
+// list now would steal the field's focus or jump rows under the pointer
+fn is_interacting(ctx: &egui::Context) -> bool {
+    let editing = ctx.memory(|m| m.focused())
+        .is_some_and(|id| egui::TextEdit::load_state(ctx, id).is_some());
+    editing || ctx.input(|i| i.pointer.any_down())
+}
+
+// Label how old the shown frame is over the bottom-left corner of a preview;
+// yellow once it's older than a couple of refreshes
+fn paint_frame_age(ui: &egui::Ui, rect: egui::Rect, label: &str, age: Duration) {
+    let color = if age > Duration::from_secs(3) { egui::Color32::YELLOW } else { egui::Color32::WHITE };
+    let text = format!("{} {}s ago", label, age.as_secs());
+    let galley = ui.painter().layout_no_wrap(text, egui::FontId::proportional(11.0), color);
+    let pos = rect.left_bottom() + egui::vec2(6.0, -6.0 - galley.size().y);
+    ui.painter().rect_filled(
+        egui::Rect::from_min_size(pos, galley.size()).expand(3.0),
+        3.0,
+        egui::Color32::from_black_alpha(160),
+    );
+    ui.painter().galley(pos, galley, color);
+}
+
+/// Byte count for display, in KB below a megabyte and MB above
+fn format_size(bytes: u64) -> String {
+    if bytes < 1_000_000 {
+        format!("{:.0} KB", bytes as f64 / 1e3)
+    } else {
+        format!("{:.1} MB", bytes as f64 / 1e6)
+    }
+}
+
+/// 🕘 menu of recently used output folders; returns the one picked. Folders
+/// that no longer exist are shown disabled.
+fn recent_dirs_menu(ui: &mut egui::Ui, recent: &[PathBuf]) -> Option<PathBuf> {
+    let mut picked = None;
+    ui.add_enabled_ui(!recent.is_empty(), |ui| {
+        ui.menu_button("🕘", |ui| {
+            for dir in recent {
+                if ui.add_enabled(dir.is_dir(), egui::Button::new(dir.display().to_string())).clicked() {
+                    picked = Some(dir.clone());
+                    ui.close_menu();
+                }
+            }
+        })
+        .response
+        .on_hover_text("Recent output folders");
+    });
+    picked
+}
+
+// Format an elapsed duration as mm:ss.mmm
+fn format_elapsed(duration: Duration) -> String {
+    let total_seconds = duration.as_secs();
+    format!("{:02}:{:02}.{:03}", total_seconds / 60, total_seconds % 60, duration.subsec_millis())
+}
+
+/// Progress of the settings tab's "Record 3s & play" check
+#[derive(Clone)]
+enum AudioCheck {
+    Idle,
+    Recording,
+    Playing,
+    Done { peak: f32 }, // Loudest sample, 0.0 to 1.0
+    Failed(String),
+}
+
 // Per-window recording settings
-#[derive(Clone, Default)]
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 struct WindowRecordingSettings {
     output_folder: Option<PathBuf>,
     custom_filename: Option<String>,
+    encoder: Option<VideoEncoder>, // Overrides for this window; None uses the global config
+    container: Option<Container>,
+    fps: Option<i32>,
+    rotation: Rotation,
+    crop: Option<CropRegion>,
+    system_audio: bool, // Record the loopback device instead of the selected input; resolved when recording starts
+    finder_tag: Option<String>, // Finder tag for every recording of this window, set as each one stops
+}
+
+impl WindowRecordingSettings {
+    /// Apply this window's format overrides on top of the global config
+    fn apply(&self, config: &mut RecordingConfig) {
+        if let Some(encoder) = self.encoder {
+            config.encoder = encoder;
+        }
+        if let Some(container) = self.container {
+            config.container = Some(container);
+        }
+        if let Some(fps) = self.fps {
+            config.fps = fps;
+            config.match_display_refresh = false;
+        }
+        config.rotation = self.rotation;
+        config.crop = self.crop;
+    }
+}
+
+/// The config a recording of `info` runs with: `base`, low power adjustments,
+/// then the window's own overrides, with fps resolved and the bitrate floored.
+/// Shared by the app and `--print-command` so both build the same command.
+fn prepare_recording_config(
+    base: &RecordingConfig,
+    info: &WindowInfo,
+    low_power: bool,
+    overrides: Option<&WindowRecordingSettings>,
+    devices: &[audio::AudioDevice],
+) -> RecordingConfig {
+    let mut config = base.clone();
+    if low_power {
+        config.apply_low_power();
+    }
+    if let Some(settings) = overrides {
+        settings.apply(&mut config);
+        // Looked up now: the loopback device may have been installed or removed since
+        let loopback = audio::system_audio_device(devices);
+        if let Some(device) = loopback.filter(|_| settings.system_audio) {
+            if config.second_audio_input_device.as_ref() == Some(&device.id) {
+                config.second_audio_input_device = None;
+            }
+            config.audio_input_device = Some(device.id.clone());
+        }
+    }
+    config.fps = config.resolve_fps(info);
+    if let RateControl::Bitrate(kbps) = &mut config.rate_control {
+        *kbps = (*kbps).max(500);
+    }
+    config
+}
+
+/// What Stop All finalized; shown once every recording it stopped is done
+#[derive(Default)]
+struct StopAllSummary {
+    pending: HashMap<u64, (String, Duration)>, // Name and length of recordings still finalizing
+    finished: Vec<StoppedRecording>,
+}
+
+/// The menu bar item and what its stops share with the app
+#[cfg(target_os = "macos")]
+struct StatusMenu {
+    item: macos::StatusItem,
+    ffmpeg: Arc<Mutex<Option<PathBuf>>>, // `ffmpeg_path`, kept current for the item's stops to finish with
+    stops: Receiver<(macos::StatusItemAction, Vec<(u64, Duration)>)>, // Recordings each pick stopped, and how long they ran
+}
+
+struct StoppedRecording {
+    name: String,
+    path: PathBuf,
+    duration: Duration,
+    size: Option<u64>, // None if the file is missing
+    error: Option<String>,
+}
+
+impl StopAllSummary {
+    /// Note a `Stopped` event if it belongs to one of the recordings stopped together
+    fn record(&mut self, window_id: u64, path: &std::path::Path, error: Option<String>) {
+        if let Some((name, duration)) = self.pending.remove(&window_id) {
+            let size = std::fs::metadata(path).ok().map(|m| m.len());
+            let error = error.or_else(|| match size {
+                None => Some("file is missing".to_string()),
+                Some(0) => Some("file is empty".to_string()),
+                Some(_) => None,
+            });
+            self.finished.push(StoppedRecording { name, path: path.to_path_buf(), duration, size, error });
+        }
+    }
+}
+
+/// ⌘K palette for starting a recording by typing part of a window's name
+#[derive(Default)]
+struct QuickRecordPalette {
+    query: String,
+    selected: usize, // Index into the current matches
 }
 
 
@@ -122,17 +440,60 @@ struct AppState {
     window_manager: WindowManager,
     recorder: Arc<Mutex<RecorderState>>,
     config: RecordingConfig,
+    saved_config: RecordingConfig, // Last config written to disk
     ffmpeg_path: Option<PathBuf>,
     status: String,
     has_permissions: bool,
+    captures_blank: bool, // Other apps' windows capture empty, so recordings would be black
+    capture_probe: Option<Receiver<bool>>, // `captures_blank` being measured off the UI thread
     preview_cache: Mutex<PreviewCache>,
     expanded_previews: HashMap<u64, bool>, // Track which windows have preview+settings expanded
     window_settings: HashMap<u64, WindowRecordingSettings>, // Per-window overrides
-    starting_recordings: Arc<Mutex<HashMap<u64, bool>>>, // Track which windows are starting
-    recording_start_times: Arc<Mutex<HashMap<u64, std::time::Instant>>>, // Track recording start times
+    starting_recordings: HashMap<u64, Arc<AtomicBool>>, // Windows being started, with their cancel flags; cleared by their `Started`/`Failed`
+    recording_start_times: HashMap<u64, std::time::Instant>, // Track recording start times
+    stop_all_summary: Option<StopAllSummary>, // Results of the last Stop All, until dismissed
+    quick_record: Option<QuickRecordPalette>, // Open ⌘K palette
+    recording_errors: HashMap<u64, (String, Instant)>, // Failed recordings and when, until retried, expired or the window closes
+    recording_events: Receiver<RecordingEvent>, // From `recorder`
+    capture_rates: Arc<Mutex<HashMap<u64, f64>>>, // Last measured sustainable capture fps per window; shared with `measure_capture_rate`'s thread, which isn't a recording
+    audio_check: Arc<Mutex<AudioCheck>>, // Progress of "Record 3s & play", updated from its thread
+    silence_timers: HashMap<u64, SilenceTimer>, // Quiet time per recording, for `stop_after_silence_secs`
+    voice_armed: HashMap<u64, Option<Instant>>, // Windows that start recording on sound, and when they last recorded
     selected_tab: Tab, // Current tab selection
+    focused_window: Option<u64>, // Keyboard focus in the window list, by id so it survives reordering
+    window_order: Vec<String>, // Manual window list order, as `WindowInfo::stable_key`s
+    recent_dirs: Vec<PathBuf>, // Output folders picked lately, newest first
+    normal_window_size: Option<egui::Vec2>, // Size to restore when leaving mini mode
+    mini_layout: Option<usize>, // Recordings the mini strip is sized for; None outside mini mode
+    screenshot_hotkey: Arc<Mutex<Option<Hotkey>>>, // `config.screenshot_hotkey`, read by the hotkey listener
+    hotkey_presses: Option<Receiver<()>>, // None until the hotkey listener is running
+    hotkey_error: Option<String>, // Why the global hotkey isn't available
+    audio_device_changes: Option<Receiver<()>>, // Hot-plug notifications; None means the device list is polled
+    display_changes: Option<Receiver<()>>, // Display reconfigurations; None means the refresh rate is re-read on auto-refresh
+    main_display_refresh: Option<i32>, // Main display's refresh rate, cached for the settings tab; None until read
+    audio_enumeration: Option<Receiver<anyhow::Result<Vec<audio::AudioDevice>>>>, // Device list being refreshed off the UI thread
+    control: control::ControlSettings, // App-level, saved apart from `config`
+    saved_control: control::ControlSettings, // Last control settings written to disk
+    control_server: Option<control::ControlServer>, // Running while `control.enabled` is on
+    control_server_failed: Option<(u16, String)>, // Port that couldn't be listened on, and why; retried once it changes
+    last_bounds_poll: Instant, // Last time recorded windows' geometry was refreshed
+    last_failure_reap: Instant, // Last time `reap_failed_recordings` tore down failed recordings
+    last_permission_check: Instant,
+    window_focused: bool, // App focus in the previous frame, to notice it coming back
+    low_power_active: bool, // Resolved from `config.low_power_mode` and the power source
+    profiles: Vec<RecordingProfile>, // Saved named configs
+    orphaned_encoders: Vec<ffmpeg::OrphanedEncoder>, // ffmpeg left running by a run that crashed
+    active_profile: Option<String>, // Profile the current config was loaded from
+    profile_name_input: String, // Name typed for "Save profile"
+    #[cfg(target_os = "macos")]
+    border_overlays: HashMap<u64, macos::BorderOverlay>, // Borders around recorded windows
+    #[cfg(target_os = "macos")]
+    status_item: Option<StatusMenu>, // Menu bar item, while `config.show_status_item` is on
+    #[cfg(target_os = "macos")]
+    hidden_from_capture: Option<(bool, usize)>, // `hide_from_capture` as last applied, and to how many app windows
     audio_device_manager: AudioDeviceManager,
     selected_audio_device: Option<String>, // Selected audio input device ID
+    failed_audio_monitor: Option<String>, // Device whose monitoring failed to start; retried once reselected
 }
 
 impl Default for AppState {
@@ -141,6 +502,9 @@ impl Default for AppState {
         let mut window_manager = WindowManager::new();
         let _ = window_manager.refresh();
         
+        let saved_config = settings::load_config();
+        let control = settings::load_control_settings();
+        
         // Initialize audio device manager and select default device
         let mut audio_device_manager = AudioDeviceManager::new();
         
@@ -149,26 +513,24 @@ impl Default for AppState {
             eprintln!("Failed to debug list audio devices: {}", e);
         }
         
-        let selected_audio_device = match audio_device_manager.enumerate_devices() {
+        // Core Audio may not be ready yet right at launch
+        let selected_audio_device = match audio_device_manager.enumerate_devices_with_retry() {
             Ok(devices) => {
                 info!("Found {} audio devices:", devices.len());
                 for device in &devices {
-                    info!("  [{}] {} (default: {})", device.id, device.name, device.is_default);
+                    info!("  {} (default: {})", device.name, device.is_default);
                 }
                 
-                // Find the default device or use the first one
-                let device_id = devices.iter()
-                    .find(|d| d.is_default)
+                // Prefer the saved device if it's still present, else the default or the first one
+                let saved_device = saved_config.as_ref()
+                    .and_then(|c| c.audio_input_device.as_ref())
+                    .and_then(|id| devices.iter().find(|d| d.id == *id));
+                let device_id = saved_device
+                    .or_else(|| devices.iter().find(|d| d.is_default))
                     .or_else(|| devices.first())
                     .map(|d| d.id.clone());
                 
-                // Start monitoring the selected device
-                if let Some(ref device_id) = device_id {
-                    if let Err(e) = audio_device_manager.start_level_monitoring(device_id) {
-                        eprintln!("Failed to start audio level monitoring for {}: {}", device_id, e);
-                    }
-                }
-                
+                // Monitoring starts with the first frame, if `audio_monitoring` wants it
                 device_id
             }
             Err(e) => {
@@ -177,12 +539,26 @@ impl Default for AppState {
             }
         };
         
-        Self {
+        let config = match saved_config {
+            Some(mut config) => {
+                config.audio_input_device = selected_audio_device.clone();
+                config
+            }
+            None => RecordingConfig::with_audio_device(selected_audio_device.clone()),
+        };
+        
+        let recorder = RecorderState::new();
+        let recording_events = recorder.events().subscribe();
+        
+        let mut app = Self {
             window_manager,
-            recorder: Arc::new(Mutex::new(RecorderState::new())),
-            config: RecordingConfig::with_audio_device(selected_audio_device.clone()),
+            recorder: Arc::new(Mutex::new(recorder)),
+            saved_config: config.clone(),
+            config,
             ffmpeg_path: ffmpeg_path.clone(),
             status: String::new(),
+            captures_blank: false, // Probed right after startup
+            capture_probe: None,
             has_permissions: {
                 #[cfg(target_os = "macos")]
                 { macos::has_screen_capture_access() }
@@ -192,12 +568,55 @@ impl Default for AppState {
             preview_cache: Mutex::new(PreviewCache::new()),
             expanded_previews: HashMap::new(),
             window_settings: HashMap::new(),
-            starting_recordings: Arc::new(Mutex::new(HashMap::new())),
-            recording_start_times: Arc::new(Mutex::new(HashMap::new())),
+            starting_recordings: HashMap::new(),
+            recording_start_times: HashMap::new(),
+            stop_all_summary: None,
+            quick_record: None,
+            recording_errors: HashMap::new(),
+            recording_events,
+            capture_rates: Arc::new(Mutex::new(HashMap::new())),
+            audio_check: Arc::new(Mutex::new(AudioCheck::Idle)),
+            silence_timers: HashMap::new(),
+            voice_armed: HashMap::new(),
             selected_tab: Tab::Windows, // Default to Windows tab
+            focused_window: None,
+            window_order: settings::load_window_order(),
+            recent_dirs: settings::load_recent_dirs(),
+            normal_window_size: None,
+            mini_layout: None,
+            screenshot_hotkey: Arc::new(Mutex::new(None)), // Synced from the config every frame
+            hotkey_presses: None,
+            hotkey_error: None,
+            audio_device_changes: None,
+            display_changes: None,
+            main_display_refresh: None,
+            audio_enumeration: None,
+            control: control.clone(),
+            saved_control: control,
+            control_server: None,
+            control_server_failed: None,
+            last_bounds_poll: Instant::now(),
+            last_failure_reap: Instant::now(),
+            last_permission_check: Instant::now(),
+            window_focused: true,
+            low_power_active: false,
+            profiles: settings::load_profiles(),
+            orphaned_encoders: ffmpeg::find_orphaned_encoders(),
+            active_profile: None,
+            profile_name_input: String::new(),
+            #[cfg(target_os = "macos")]
+            border_overlays: HashMap::new(),
+            #[cfg(target_os = "macos")]
+            status_item: None,
+            #[cfg(target_os = "macos")]
+            hidden_from_capture: None,
             audio_device_manager,
             selected_audio_device,
-        }
+            failed_audio_monitor: None,
+        };
+        app.refresh_power_state();
+        app.probe_capture();
+        app
     }
 }
 
@@ -214,11 +633,50 @@ impl AppState {
         self.selected_audio_device = Some(device_id.clone());
         
         // Update config to use the selected device
-        self.config.audio_input_device = Some(device_id.clone());
+        self.config.audio_input_device = Some(device_id);
+        
+        // `sync_audio_monitoring` starts the new device when it's needed
+        self.failed_audio_monitor = None;
+    }
+    
+    /// Keep the selected input's level monitored only while something uses it:
+    /// a visible level meter or auto start/stop, unless it's set to always run.
+    /// Meters pause while the app is in the background; auto start/stop doesn't.
+    fn sync_audio_monitoring(&mut self) {
+        let watching_level = !self.voice_armed.is_empty()
+            || (self.config.stop_after_silence_secs.is_some()
+                && !self.recorder.lock().recording_ids().is_empty());
+        let pause = self.config.pause_monitoring_unfocused && !self.window_focused && !watching_level;
+        if pause != self.audio_device_manager.is_paused() {
+            if pause {
+                self.audio_device_manager.pause_monitoring();
+            } else if let Err(e) = self.audio_device_manager.resume_monitoring() {
+                eprintln!("Failed to resume audio level monitoring: {}", e);
+            }
+        }
+        if pause {
+            return;
+        }
         
-        // Start monitoring new device
-        if let Err(e) = self.audio_device_manager.start_level_monitoring(&device_id) {
-            eprintln!("Failed to start audio level monitoring for {}: {}", device_id, e);
+        let Some(device_id) = self.selected_audio_device.clone() else {
+            return;
+        };
+        let needed = match self.config.audio_monitoring {
+            AudioMonitoring::Always => true,
+            AudioMonitoring::WhenNeeded => {
+                self.selected_tab == Tab::Settings
+                    || !self.expanded_previews.is_empty() // The expanded panel has a meter
+                    || watching_level
+            }
+        };
+        let monitoring = self.audio_device_manager.is_monitoring(&device_id);
+        if needed && !monitoring && self.failed_audio_monitor.as_ref() != Some(&device_id) {
+            if let Err(e) = self.audio_device_manager.start_level_monitoring(&device_id) {
+                eprintln!("Failed to start audio level monitoring for {}: {}", device_id, e);
+                self.failed_audio_monitor = Some(device_id);
+            }
+        } else if !needed && monitoring {
+            self.audio_device_manager.stop_level_monitoring(&device_id);
         }
     }
     
@@ -260,6 +718,252 @@ impl AppState {
         });
     }
     
+    /// FPS, bitrate and encoder controls, set together by the quality presets
+    fn render_video_controls(&mut self, ui: &mut egui::Ui) {
+        // FPS setting
+        ui.horizontal(|ui| {
+            ui.label("FPS:");
+            ui.add_enabled(
+                !self.config.match_display_refresh,
+                egui::DragValue::new(&mut self.config.fps).range(1..=120),
+            )
+            .on_hover_text("1–120 fps");
+            ui.label("frames per second");
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.config.match_display_refresh, "Match display refresh rate");
+            #[cfg(target_os = "macos")]
+            {
+                if self.config.match_display_refresh {
+                    let main_rate = *self.main_display_refresh.get_or_insert_with(macos::main_display_refresh_rate);
+                    ui.label(
+                        egui::RichText::new(format!(
+                            "resolved per window at start (main display: {} fps)",
+                            main_rate
+                        ))
+                        .small()
+                        .color(ui.style().visuals.weak_text_color()),
+                    );
+                }
+            }
+        });
+        // Fewer captures than output frames, for mostly static windows
+        ui.horizontal(|ui| {
+            let mut lower = self.config.capture_fps.is_some();
+            if ui.checkbox(&mut lower, "Capture at")
+                .on_hover_text("Capture fewer frames than the file has and repeat each one. Saves CPU and pipe bandwidth on mostly static windows like slides or terminals; motion looks choppier.")
+                .changed()
+            {
+                self.config.capture_fps = lower.then_some(5);
+            }
+            if let Some(rate) = self.config.capture_fps.as_mut() {
+                ui.add(egui::DragValue::new(rate).range(1..=120));
+            }
+            ui.label("fps");
+            if self.config.capture_fps.is_some_and(|rate| rate >= self.config.fps) && !self.config.match_display_refresh {
+                ui.label(
+                    egui::RichText::new("at or above the output fps, so every frame is captured")
+                        .small()
+                        .color(ui.style().visuals.weak_text_color()),
+                );
+            }
+        });
+        
+        ui.add_space(10.0);
+        
+        // Bitrate setting, rated against the selected window's encoded size
+        let encoded = self.selected_window().map(|info| {
+            let config = self.recording_config_for(info);
+            let (w, h) = match config.canvas {
+                Some(canvas) => (canvas.width as usize, canvas.height as usize),
+                None => config.rotation.apply(info.width.max(2) as usize, info.height.max(2) as usize),
+            };
+            let (w, h) = config.output_size_for(w, h).unwrap_or((w, h));
+            (info.display_name(), w, h, config.fps)
+        });
+        let mut constant_quality = matches!(self.config.rate_control, RateControl::ConstantQuality(_));
+        if ui.checkbox(&mut constant_quality, "Constant quality")
+            .on_hover_text("Spend bits on what changes on screen instead of a fixed bitrate; static windows make much smaller files")
+            .changed()
+        {
+            self.config.rate_control = if constant_quality {
+                RateControl::ConstantQuality(RateControl::DEFAULT_CRF)
+            } else {
+                RateControl::Bitrate(RateControl::DEFAULT_BITRATE_KBPS)
+            };
+        }
+        if let RateControl::ConstantQuality(crf) = &mut self.config.rate_control {
+            ui.horizontal(|ui| {
+                ui.label("CRF:");
+                ui.add(egui::Slider::new(crf, 0..=51))
+                    .on_hover_text("Lower is better: 18 looks lossless, 23 is a good default, 28 and up gets blurry. VideoToolbox gets the equivalent -q:v.");
+            });
+        }
+        ui.horizontal(|ui| {
+            let bitrate_mode = !constant_quality;
+            let mut unused = self.config.bitrate_kbps();
+            let kbps = match &mut self.config.rate_control {
+                RateControl::Bitrate(kbps) => kbps,
+                RateControl::ConstantQuality(_) => &mut unused,
+            };
+            ui.label("Bitrate:");
+            let range = RateControl::BITRATE_RANGE_KBPS;
+            ui.add_enabled(bitrate_mode, egui::DragValue::new(kbps).range(range.clone()))
+                .on_hover_text(format!("{}–{} kbps", range.start(), range.end()))
+                .on_disabled_hover_text("Not used in constant quality mode");
+            ui.label("kbps");
+            let suggestion = encoded.as_ref()
+                .filter(|_| bitrate_mode)
+                .map(|(_, w, h, fps)| suggest_bitrate(*w, *h, *fps));
+            let button = ui.add_enabled(suggestion.is_some(), egui::Button::new("Suggest"));
+            let button = match suggestion {
+                Some(kbps) => button.on_hover_text(format!("{} kbps for the selected window", kbps)),
+                None if !bitrate_mode => button.on_disabled_hover_text("Not used in constant quality mode"),
+                None => button.on_disabled_hover_text("Select a window in the list first"),
+            };
+            if let Some(kbps) = suggestion.filter(|_| button.clicked()) {
+                self.config.rate_control = RateControl::Bitrate(kbps);
+            }
+        });
+        let uses_bitrate = self.config.encoder.uses_bitrate() && !constant_quality;
+        if let Some((name, w, h, fps)) = encoded.as_ref().filter(|_| uses_bitrate) {
+            let bpp = bits_per_pixel(self.config.bitrate_kbps(), *w, *h, *fps);
+            ui.label(
+                egui::RichText::new(format!("{:.2} bpp — {} for {} at {}x{}, {} fps", bpp, bpp_quality(bpp), name, w, h, fps))
+                    .small()
+                    .color(ui.style().visuals.weak_text_color()),
+            );
+        }
+        
+        // Explain adjustments the encoder makes at record time
+        if let Some(adjustment) = self.config.encoder.bitrate_adjustment(self.config.bitrate_kbps()).filter(|_| uses_bitrate) {
+            ui.colored_label(egui::Color32::YELLOW, format!("⚠ {}", adjustment));
+        }
+        
+        // Encoder selection
+        ui.horizontal(|ui| {
+            ui.label("Encoder:");
+            egui::ComboBox::from_id_salt("encoder_select")
+                .selected_text(self.config.encoder.label())
+                .show_ui(ui, |ui| {
+                    for encoder in ENCODERS {
+                        ui.selectable_value(&mut self.config.encoder, encoder, encoder.label());
+                    }
+                });
+            ui.checkbox(&mut self.config.encoder_fallback, "Fall back if it fails")
+                .on_hover_text("When off, a recording whose encoder won't start fails with ffmpeg's error instead of switching to another encoder");
+        });
+        if self.config.encoder == VideoEncoder::Libx264 {
+            ui.horizontal(|ui| {
+                ui.label("x264 preset:");
+                egui::ComboBox::from_id_salt("x264_preset_select")
+                    .selected_text(self.config.x264_preset.ffmpeg_name())
+                    .show_ui(ui, |ui| {
+                        for preset in X264Preset::ALL {
+                            ui.selectable_value(&mut self.config.x264_preset, preset, preset.ffmpeg_name());
+                        }
+                    })
+                    .response
+                    .on_hover_text("Slower presets make smaller files at the same quality but use more CPU; if the machine can't keep up, frames are dropped");
+            });
+        }
+        if self.config.encoder == VideoEncoder::ProResVideoToolbox {
+            ui.horizontal(|ui| {
+                ui.label("ProRes profile:");
+                egui::ComboBox::from_id_salt("prores_profile_select")
+                    .selected_text(self.config.prores_profile.label())
+                    .show_ui(ui, |ui| {
+                        for profile in ProResProfile::ALL {
+                            ui.selectable_value(&mut self.config.prores_profile, profile, profile.label());
+                        }
+                    });
+            });
+            ui.label(
+                egui::RichText::new("ProRes ignores the bitrate and is saved as .mov rather than .mp4; expect several GB per minute")
+                    .small()
+                    .color(ui.style().visuals.weak_text_color()),
+            );
+        }
+        
+        // Pixel format, limited to what the encoder takes
+        ui.horizontal(|ui| {
+            ui.label("Pixel format:");
+            egui::ComboBox::from_id_salt("pixel_format_select")
+                .selected_text(self.config.pixel_format.label())
+                .show_ui(ui, |ui| {
+                    for format in [PixelFormat::Yuv420p, PixelFormat::Nv12, PixelFormat::Yuv444p] {
+                        ui.add_enabled_ui(format.supported_by(self.config.encoder), |ui| {
+                            ui.selectable_value(&mut self.config.pixel_format, format, format.label());
+                        })
+                        .response
+                        .on_disabled_hover_text("Only libx264 encodes this");
+                    }
+                });
+        });
+        if !self.config.pixel_format.supported_by(self.config.encoder) {
+            ui.colored_label(
+                egui::Color32::YELLOW,
+                format!("⚠ {} can't encode this pixel format; recordings will use yuv420p", self.config.encoder.label()),
+            );
+        } else if self.config.pixel_format == PixelFormat::Yuv444p {
+            ui.label(
+                egui::RichText::new("QuickTime, browsers and most phones can't play 4:4:4 files")
+                    .small()
+                    .color(ui.style().visuals.weak_text_color()),
+            );
+        }
+    }
+    
+    /// Rules sending each app's recordings to its own folder
+    fn render_app_folders(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new(format!("Folders per app ({})", self.config.app_folders.len()))
+            .id_salt("app_folders")
+            .show(ui, |ui| {
+                let mut remove = None;
+                let mut picked = None;
+                for (index, rule) in self.config.app_folders.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.add(egui::TextEdit::singleline(&mut rule.app).desired_width(140.0).hint_text("App or bundle id"));
+                        ui.label("→");
+                        ui.label(egui::RichText::new(rule.folder.display().to_string()).small());
+                        if ui.small_button("📁").clicked() {
+                            if let Some(path) = rfd::FileDialog::new().set_directory(&rule.folder).pick_folder() {
+                                picked = Some(path.clone());
+                                rule.folder = path;
+                            }
+                        }
+                        if let Some(path) = recent_dirs_menu(ui, &self.recent_dirs) {
+                            picked = Some(path.clone());
+                            rule.folder = path;
+                        }
+                        if ui.small_button("❌").clicked() {
+                            remove = Some(index);
+                        }
+                    });
+                }
+                if let Some(index) = remove {
+                    self.config.app_folders.remove(index);
+                }
+                if let Some(path) = picked {
+                    self.remember_dir(&path);
+                }
+                
+                // Start from the selected window's app, if any
+                if ui.small_button("➕ Add app").clicked() {
+                    let app = self.selected_window()
+                        .map(|info| info.owner_name.clone())
+                        .unwrap_or_default();
+                    let folder = self.config.output_dir.clone().unwrap_or_else(|| PathBuf::from("."));
+                    self.config.app_folders.push(AppFolder { app, folder });
+                }
+                ui.label(
+                    egui::RichText::new("Recordings of a matching app go here unless the window has its own folder. Matches the app's name or bundle identifier.")
+                        .small()
+                        .color(ui.style().visuals.weak_text_color()),
+                );
+            });
+    }
+    
     fn render_settings_tab(&mut self, ui: &mut egui::Ui) {
         ui.vertical(|ui| {
             ui.heading("Recording Settings");
@@ -278,94 +982,385 @@ impl AppState {
                     if let Some(path) = rfd::FileDialog::new()
                         .set_directory(initial.unwrap_or_else(|| PathBuf::from(".")))
                         .pick_folder() {
+                        self.remember_dir(&path);
                         self.config.output_dir = Some(path);
                     }
                 }
+                if let Some(path) = recent_dirs_menu(ui, &self.recent_dirs) {
+                    self.remember_dir(&path);
+                    self.config.output_dir = Some(path);
+                }
+            });
+            ui.horizontal(|ui| {
+                let mut dated = self.config.folder_template.is_some();
+                if ui.checkbox(&mut dated, "Organize into subfolders:").changed() {
+                    self.config.folder_template = dated.then(|| "{year}/{month}-{day}".to_string());
+                }
+                if let Some(template) = self.config.folder_template.as_mut() {
+                    ui.add(egui::TextEdit::singleline(template).desired_width(180.0))
+                        .on_hover_text(format!("Use / between folders. Tokens: {}", ffmpeg::PATH_TOKENS));
+                }
             });
+            ui.checkbox(&mut self.config.tag_source_app, "Tag recordings with the recorded app")
+                .on_hover_text("Writes the app's name and bundle identifier (e.g. com.apple.Safari) into each file's metadata, for tools that sort recordings");
+            self.render_app_folders(ui);
             
             ui.add_space(10.0);
             
-            // FPS setting
+            // Quality preset; the individual controls live under "Advanced"
+            if let Some(preset) = self.config.quality_preset {
+                if !preset.matches(&self.config) {
+                    self.config.quality_preset = None;
+                }
+            }
             ui.horizontal(|ui| {
-                ui.label("FPS:");
-                ui.add(egui::DragValue::new(&mut self.config.fps).range(1..=120));
-                ui.label("frames per second");
+                ui.label("Quality:");
+                egui::ComboBox::from_id_salt("quality_select")
+                    .selected_text(self.config.quality_preset.map_or("Custom", |p| p.label()))
+                    .show_ui(ui, |ui| {
+                        for preset in [QualityPreset::Low, QualityPreset::Medium, QualityPreset::High, QualityPreset::Max] {
+                            let selected = self.config.quality_preset == Some(preset);
+                            if ui.selectable_label(selected, preset.label())
+                                .on_hover_text(preset.description())
+                                .clicked()
+                            {
+                                preset.apply(&mut self.config);
+                            }
+                        }
+                    });
             });
+            egui::CollapsingHeader::new("Advanced")
+                .default_open(self.config.quality_preset.is_none())
+                .show(ui, |ui| {
+                    self.render_video_controls(ui);
+                });
             
             ui.add_space(10.0);
             
-            // Bitrate setting
+            // Output scaling; an unchecked side follows the window. A canvas
+            // sets the final size by itself.
+            let has_canvas = self.config.canvas.is_some();
+            ui.add_enabled_ui(!has_canvas, |ui| ui.horizontal(|ui| {
+                ui.label("Output size:");
+                for (label, side, default) in [
+                    ("Width", &mut self.config.output_width, 1920),
+                    ("Height", &mut self.config.output_height, 1080),
+                ] {
+                    let mut enabled = side.is_some();
+                    if ui.checkbox(&mut enabled, label).changed() {
+                        *side = enabled.then_some(default);
+                    }
+                    if let Some(value) = side.as_mut() {
+                        ui.add(egui::DragValue::new(value).range(16..=7680));
+                    }
+                }
+                ui.checkbox(&mut self.config.lock_aspect_ratio, "🔒 Keep aspect ratio")
+                    .on_hover_text("Derive the other side from each window's size, or fit inside both. Otherwise the video is stretched.");
+            }))
+            .response
+            .on_disabled_hover_text("The fixed canvas sets the output size");
+            ui.add_enabled_ui(!has_canvas, |ui| ui.horizontal(|ui| {
+                let mut limited = self.config.max_capture_dimension.is_some();
+                if ui.checkbox(&mut limited, "Scale down windows larger than").changed() {
+                    self.config.max_capture_dimension = limited.then_some(4096);
+                }
+                if let Some(max) = self.config.max_capture_dimension.as_mut() {
+                    ui.add(egui::DragValue::new(max).range(320..=8192));
+                }
+                ui.label("px")
+                    .on_hover_text("Keeps very large windows within encoder limits (4096 for hardware H.264)");
+            }))
+            .response
+            .on_disabled_hover_text("The fixed canvas sets the output size");
+            // Same frame size for every window, whatever its shape
             ui.horizontal(|ui| {
-                ui.label("Bitrate:");
-                ui.add(egui::DragValue::new(&mut self.config.bitrate_kbps).range(500..=50000));
-                ui.label("kbps");
+                let mut fixed = self.config.canvas.is_some();
+                if ui.checkbox(&mut fixed, "Fixed canvas")
+                    .on_hover_text("Fit every window into one frame size, with bars where the shapes differ. Recordings of different windows then line up for editing. The size is that of the finished video, after rotation.")
+                    .changed()
+                {
+                    self.config.canvas = fixed.then_some(Canvas { width: 1920, height: 1080, background: [0, 0, 0] });
+                }
+                if let Some(canvas) = self.config.canvas.as_mut() {
+                    ui.add(egui::DragValue::new(&mut canvas.width).range(16..=7680));
+                    ui.label("×");
+                    ui.add(egui::DragValue::new(&mut canvas.height).range(16..=7680));
+                    ui.label("bars:");
+                    ui.color_edit_button_srgb(&mut canvas.background);
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Odd sizes:");
+                egui::ComboBox::from_id_salt("even_size")
+                    .selected_text(self.config.even_size.label())
+                    .show_ui(ui, |ui| {
+                        for mode in [EvenSize::Crop, EvenSize::Pad] {
+                            ui.selectable_value(&mut self.config.even_size, mode, mode.label());
+                        }
+                    })
+                    .response
+                    .on_hover_text("Encoders need even widths and heights. Crop drops a window's last odd column or row; pad repeats it.");
             });
+            if !self.config.scales_output() && self.config.canvas.is_none() {
+                ui.label(
+                    egui::RichText::new("Recordings use each window's own size")
+                        .small()
+                        .color(ui.style().visuals.weak_text_color()),
+                );
+            }
             
             ui.add_space(10.0);
             
-            // Encoder selection
+            // Time segments, cut by ffmpeg itself
             ui.horizontal(|ui| {
-                ui.label("Encoder:");
-                egui::ComboBox::from_id_salt("encoder_select")
-                    .selected_text(match self.config.encoder {
-                        ffmpeg::VideoEncoder::H264VideoToolbox => "H.264 VideoToolbox (Hardware)",
-                        ffmpeg::VideoEncoder::H264VideoToolboxFallback => "H.264 VideoToolbox (Fallback)",
-                        ffmpeg::VideoEncoder::Libx264 => "H.264 libx264 (Software)",
-                    })
+                let mut segments_enabled = self.config.segment_seconds.is_some();
+                if ui.checkbox(&mut segments_enabled, "Split files every").changed() {
+                    self.config.segment_seconds = if segments_enabled { Some(600) } else { None };
+                }
+                if let Some(seconds) = self.config.segment_seconds.as_mut() {
+                    ui.add(egui::DragValue::new(seconds).range(10..=86_400));
+                }
+                ui.label("s")
+                    .on_hover_text("Files are numbered name_000, name_001, … and each one plays on its own");
+            });
+
+            // Size-based splitting
+            ui.add_enabled_ui(self.config.segment_seconds.is_none(), |ui| {
+                ui.horizontal(|ui| {
+                    let mut split_enabled = self.config.split_size_mb.is_some();
+                    if ui.checkbox(&mut split_enabled, "Split files at").changed() {
+                        self.config.split_size_mb = if split_enabled { Some(2000) } else { None };
+                    }
+                    if let Some(split_mb) = self.config.split_size_mb.as_mut() {
+                        ui.add(egui::DragValue::new(split_mb).range(10..=100_000));
+                    }
+                    ui.label("MB")
+                        .on_hover_text("Estimated from bitrate. Stay below 4096 MB for FAT32 drives.");
+                });
+            })
+            .response
+            .on_disabled_hover_text("Time segments replace size-based splitting");
+            
+            ui.add_space(10.0);
+            
+            // MP4 layout
+            ui.horizontal(|ui| {
+                ui.label("MP4 layout:");
+                egui::ComboBox::from_id_salt("mp4_layout_select")
+                    .selected_text(self.config.mp4_layout.label())
                     .show_ui(ui, |ui| {
-                        ui.selectable_value(&mut self.config.encoder, ffmpeg::VideoEncoder::Libx264, "H.264 libx264 (Software)");
-                        ui.selectable_value(&mut self.config.encoder, ffmpeg::VideoEncoder::H264VideoToolbox, "H.264 VideoToolbox (Hardware)");
-                        ui.selectable_value(&mut self.config.encoder, ffmpeg::VideoEncoder::H264VideoToolboxFallback, "H.264 VideoToolbox (Fallback)");
-                    });
+                        for layout in [Mp4Layout::Standard, Mp4Layout::Faststart, Mp4Layout::Fragmented] {
+                            ui.selectable_value(&mut self.config.mp4_layout, layout, layout.label())
+                                .on_hover_text(layout.tooltip());
+                        }
+                    })
+                    .response
+                    .on_hover_text(self.config.mp4_layout.tooltip());
             });
+            ui.checkbox(&mut self.config.verify_recordings, "Check recordings when they stop")
+                .on_hover_text("Opens each finished file with ffprobe and warns if it's empty or won't play. Turn off to save a moment per stop.");
             
-            ui.add_space(20.0);
+            ui.add_space(10.0);
             
-            // Audio input device selection
+            // Low power mode
             ui.horizontal(|ui| {
-                ui.label("🎤 Audio Input:");
-                egui::ComboBox::from_id_salt("audio_input_select")
-                    .selected_text(
-                        self.selected_audio_device.as_ref()
-                            .and_then(|id| {
-                                self.audio_device_manager.get_devices()
-                                    .iter()
-                                    .find(|d| d.id == *id)
-                                    .map(|d| d.name.as_str())
-                            })
-                            .unwrap_or("No device selected")
-                    )
+                ui.label("Low power mode:");
+                egui::ComboBox::from_id_salt("low_power_select")
+                    .selected_text(self.config.low_power_mode.label())
                     .show_ui(ui, |ui| {
-                        // Refresh devices button
-                        if ui.button("🔄 Refresh").clicked() {
-                            if let Ok(devices) = self.audio_device_manager.enumerate_devices() {
-                                if self.selected_audio_device.is_none() && !devices.is_empty() {
-                                    // Auto-select default device if none selected
-                                    self.selected_audio_device = devices.iter()
-                                        .find(|d| d.is_default)
-                                        .or_else(|| devices.first())
-                                        .map(|d| d.id.clone());
-                                }
+                        for mode in [LowPowerMode::Auto, LowPowerMode::On, LowPowerMode::Off] {
+                            if ui.selectable_value(&mut self.config.low_power_mode, mode, mode.label()).changed() {
+                                self.refresh_power_state();
                             }
                         }
-                        
-                        ui.separator();
-                        
-                        let devices = self.audio_device_manager.get_devices().to_vec();
-                        for device in devices {
-                            let display_name = if device.is_default {
+                    });
+            });
+            
+            ui.add_space(10.0);
+            
+            #[cfg(target_os = "macos")]
+            {
+                ui.checkbox(&mut self.config.show_recording_border, "Show a border around recorded windows");
+                ui.checkbox(&mut self.config.show_status_item, "Show recording status in the menu bar")
+                    .on_hover_text("A dot that turns red while recording, with a menu to stop recordings even when this window is hidden");
+                ui.checkbox(&mut self.config.hide_from_capture, "Hide this app from screen captures")
+                    .on_hover_text("Best effort: asks macOS to leave this app's windows out of screen captures and screen sharing. Older capture methods respect it, but apps using ScreenCaptureKit (recent macOS screenshots, most screen sharing) may still show them.");
+                ui.add_space(10.0);
+            }
+            
+            // Screenshot hotkey
+            ui.horizontal(|ui| {
+                let mut enabled = self.config.screenshot_hotkey.is_some();
+                if ui.checkbox(&mut enabled, "Screenshot hotkey")
+                    .on_hover_text("Saves a PNG of the frontmost window to the output folder, even while this app is in the background")
+                    .changed()
+                {
+                    self.config.screenshot_hotkey = enabled.then(Hotkey::default);
+                    // Turning it back on retries a listener that failed
+                    self.hotkey_error = None;
+                }
+                if let Some(hotkey) = self.config.screenshot_hotkey.as_mut() {
+                    // The last of ⌃⌥⌘ stays on: without one, every press of the key would count
+                    let held = [hotkey.control, hotkey.option, hotkey.command].iter().filter(|&&held| held).count();
+                    let modifiers = [
+                        (&mut hotkey.control, "⌃", true),
+                        (&mut hotkey.option, "⌥", true),
+                        (&mut hotkey.shift, "⇧", false),
+                        (&mut hotkey.command, "⌘", true),
+                    ];
+                    for (modifier, symbol, counts) in modifiers {
+                        let last = counts && *modifier && held == 1;
+                        ui.add_enabled_ui(!last, |ui| ui.toggle_value(modifier, symbol))
+                            .inner
+                            .on_disabled_hover_text("The hotkey needs at least one of ⌃, ⌥ and ⌘");
+                    }
+                    egui::ComboBox::from_id_salt("hotkey_key_select")
+                        .width(40.0)
+                        .selected_text(hotkey.key.to_string())
+                        .show_ui(ui, |ui| {
+                            for key in Hotkey::KEYS.chars() {
+                                ui.selectable_value(&mut hotkey.key, key, key.to_string());
+                            }
+                        });
+                }
+            });
+            if let Some(err) = &self.hotkey_error {
+                ui.colored_label(egui::Color32::YELLOW, format!("⚠ {}", err));
+            }
+            ui.add_space(10.0);
+            
+            // Local control server for stream decks and scripts
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.control.enabled, "Control server on port")
+                    .on_hover_text("Lets tools on this Mac list windows and start or stop recordings over a JSON protocol. Only local connections that send the token are accepted.");
+                ui.add(egui::DragValue::new(&mut self.control.port).range(1024..=65535));
+            });
+            if self.control.enabled {
+                ui.horizontal(|ui| {
+                    ui.label("Token:");
+                    ui.label(egui::RichText::new(&self.control.token).monospace().small());
+                    if ui.small_button("📋").on_hover_text("Copy token").clicked() {
+                        ui.ctx().copy_text(self.control.token.clone());
+                    }
+                    if ui.small_button("New token").on_hover_text("Tools using the old token stop working").clicked() {
+                        self.control.token.clear(); // `sync_control_server` generates a new one
+                    }
+                });
+                if let Some((port, err)) = &self.control_server_failed {
+                    ui.colored_label(egui::Color32::YELLOW, format!("⚠ Port {}: {}", port, err));
+                }
+            }
+            ui.add_space(10.0);
+            
+            ui.checkbox(&mut self.config.debug_tools, "Show debug tools")
+                .on_hover_text("Adds \"Export frame\" to each window, which saves its raw capture and the sizes derived from it for a bug report");
+            
+            ui.add_space(20.0);
+            
+            // Audio input device selection
+            ui.horizontal(|ui| {
+                ui.label("🎤 Audio Input:");
+                egui::ComboBox::from_id_salt("audio_input_select")
+                    .selected_text(
+                        self.selected_audio_device.as_ref()
+                            .and_then(|id| {
+                                self.audio_device_manager.get_devices()
+                                    .iter()
+                                    .find(|d| d.id == *id)
+                                    .map(|d| d.name.as_str())
+                            })
+                            .unwrap_or("No device selected")
+                    )
+                    .show_ui(ui, |ui| {
+                        // Refresh devices button
+                        if ui.button("🔄 Refresh").clicked() {
+                            self.refresh_audio_devices(ui.ctx());
+                        }
+                        
+                        ui.separator();
+                        
+                        let devices = self.audio_device_manager.get_devices().to_vec();
+                        for device in devices {
+                            let display_name = if device.is_default {
                                 format!("{} (Default)", device.name)
                             } else {
                                 device.name.clone()
                             };
                             
-                            if ui.selectable_value(&mut self.selected_audio_device, Some(device.id.clone()), display_name).clicked() {
+                            // Let `select_audio_device` see the previous device so it can stop it
+                            let selected = self.selected_audio_device.as_ref() == Some(&device.id);
+                            if ui.selectable_label(selected, display_name).clicked() {
                                 self.select_audio_device(device.id.clone());
                             }
                         }
                     });
+                
+                // Ear check: a short clip from the device, played straight back
+                let check = self.audio_check.lock().clone();
+                let busy = matches!(check, AudioCheck::Recording | AudioCheck::Playing);
+                if ui.add_enabled(!busy && self.selected_audio_device.is_some(), egui::Button::new("🎧 Record 3s & play"))
+                    .on_hover_text("Records three seconds from this input and plays them back, to check it's the right device and channel")
+                    .clicked()
+                {
+                    self.start_audio_check(ui.ctx());
+                }
+                match check {
+                    AudioCheck::Idle => {}
+                    AudioCheck::Recording => {
+                        ui.label("Recording…");
+                    }
+                    AudioCheck::Playing => {
+                        ui.label("Playing back…");
+                    }
+                    AudioCheck::Done { peak } if peak < AUDIO_CHECK_SILENT => {
+                        ui.colored_label(egui::Color32::YELLOW, "⚠ Silent");
+                    }
+                    AudioCheck::Done { peak } if peak >= AUDIO_CHECK_CLIPPING => {
+                        ui.colored_label(egui::Color32::YELLOW, "⚠ Clipping: lower the input volume");
+                    }
+                    AudioCheck::Done { peak } => {
+                        ui.label(format!("Peak {:.0}%", peak * 100.0));
+                    }
+                    AudioCheck::Failed(error) => {
+                        ui.colored_label(egui::Color32::RED, "⚠ Check failed").on_hover_text(error);
+                    }
+                }
+            });
+            
+            // Optional second device, kept as its own track
+            ui.horizontal(|ui| {
+                ui.label("Second track:");
+                let devices = self.audio_device_manager.get_devices().to_vec();
+                let selected_name = self.config.second_audio_input_device.as_ref()
+                    .and_then(|id| devices.iter().find(|d| d.id == *id))
+                    .map(|d| d.name.clone())
+                    .unwrap_or_else(|| "None".to_string());
+                egui::ComboBox::from_id_salt("second_audio_select")
+                    .selected_text(selected_name)
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.config.second_audio_input_device, None, "None");
+                        for device in devices.iter().filter(|d| Some(&d.id) != self.selected_audio_device.as_ref()) {
+                            ui.selectable_value(&mut self.config.second_audio_input_device, Some(device.id.clone()), &device.name);
+                        }
+                    })
+                    .response
+                    .on_hover_text("Recorded as a separate audio track (e.g. system audio via a loopback device), not mixed. QuickTime only plays the first track.");
             });
             
+            ui.horizontal(|ui| {
+                ui.label("Level meter:");
+                egui::ComboBox::from_id_salt("audio_monitoring_select")
+                    .selected_text(self.config.audio_monitoring.label())
+                    .show_ui(ui, |ui| {
+                        for mode in [AudioMonitoring::WhenNeeded, AudioMonitoring::Always] {
+                            ui.selectable_value(&mut self.config.audio_monitoring, mode, mode.label());
+                        }
+                    })
+                    .response
+                    .on_hover_text("Metering keeps the microphone open and shows the privacy indicator. \"Only while needed\" meters on this tab, in an expanded window, and while voice trigger or stop-after-silence is watching.");
+                ui.checkbox(&mut self.config.pause_monitoring_unfocused, "Pause in background")
+                    .on_hover_text("Release the microphone while another app is in front, unless voice trigger or stop-after-silence is watching. Recordings keep their audio.");
+            });
             
             // Audio level indicator
             if let Some(device_id) = &self.selected_audio_device {
@@ -375,6 +1370,123 @@ impl AppState {
                 }
             }
             
+            // Auto-stop once the input goes quiet
+            ui.horizontal(|ui| {
+                let mut enabled = self.config.stop_after_silence_secs.is_some();
+                if ui.checkbox(&mut enabled, "Stop after").changed() {
+                    self.config.stop_after_silence_secs = enabled.then_some(30);
+                }
+                if let Some(secs) = self.config.stop_after_silence_secs.as_mut() {
+                    ui.add(egui::DragValue::new(secs).range(1..=3600).suffix(" s"));
+                }
+                ui.label("of silence, below level");
+                ui.add_enabled(
+                    self.config.stop_after_silence_secs.is_some(),
+                    egui::Slider::new(&mut self.config.silence_threshold, 0.0..=0.5),
+                )
+                .on_hover_text("Compare with the level meter above while the room is quiet");
+            });
+            
+            // Voice activation of windows armed with 🎙
+            ui.horizontal(|ui| {
+                ui.label("🎙 Voice trigger level:");
+                ui.add(egui::Slider::new(&mut self.config.voice_trigger_threshold, 0.01..=1.0));
+                ui.label("Cooldown:");
+                ui.add(egui::DragValue::new(&mut self.config.voice_cooldown_secs).range(0..=3600).suffix(" s"))
+                    .on_hover_text("Wait this long after a recording before the window can trigger again");
+                ui.label("Min length:");
+                ui.add(egui::DragValue::new(&mut self.config.voice_min_duration_secs).range(0..=3600).suffix(" s"))
+                    .on_hover_text("Voice-triggered recordings aren't stopped for silence before this");
+            });
+            
+            ui.add_space(10.0);
+            
+            // How the primary device gets into the recording
+            ui.horizontal(|ui| {
+                ui.label("Record audio via:");
+                egui::ComboBox::from_id_salt("audio_backend_select")
+                    .selected_text(self.config.audio_backend.label())
+                    .show_ui(ui, |ui| {
+                        for backend in [AudioBackend::Avfoundation, AudioBackend::Cpal] {
+                            ui.selectable_value(&mut self.config.audio_backend, backend, backend.label());
+                        }
+                    })
+                    .response
+                    .on_hover_text("cpal opens the same device as the level meter and pipes it into ffmpeg, instead of ffmpeg guessing its AVFoundation index. The second track always uses AVFoundation.");
+            });
+            
+            // Audio codec and bitrate
+            ui.horizontal(|ui| {
+                ui.label("Audio codec:");
+                egui::ComboBox::from_id_salt("audio_codec_select")
+                    .selected_text(self.config.audio_codec.label())
+                    .show_ui(ui, |ui| {
+                        for codec in [AudioCodec::Aac, AudioCodec::Opus, AudioCodec::Alac, AudioCodec::Pcm] {
+                            ui.selectable_value(&mut self.config.audio_codec, codec, codec.label());
+                        }
+                    });
+                ui.add_enabled(
+                    self.config.audio_codec.uses_bitrate(),
+                    egui::DragValue::new(&mut self.config.audio_bitrate_kbps).range(32..=512),
+                );
+                ui.label("kbps");
+            });
+            if let Some(warning) = self.config.audio_codec.container_warning() {
+                ui.colored_label(egui::Color32::YELLOW, format!("⚠ {}", warning));
+            }
+            
+            // What ends a recording with audio
+            ui.horizontal(|ui| {
+                ui.label("End recording:");
+                egui::ComboBox::from_id_salt("stream_end_select")
+                    .selected_text(self.config.stream_end.label())
+                    .show_ui(ui, |ui| {
+                        for stream_end in [StreamEnd::Video, StreamEnd::Shortest] {
+                            ui.selectable_value(&mut self.config.stream_end, stream_end, stream_end.label())
+                                .on_hover_text(stream_end.tooltip());
+                        }
+                    })
+                    .response
+                    .on_hover_text(self.config.stream_end.tooltip());
+            });
+            
+            // Audio filters, each can be turned off on its own
+            ui.horizontal(|ui| {
+                let mut highpass = self.config.audio_highpass_hz.is_some();
+                if ui.checkbox(&mut highpass, "Highpass").changed() {
+                    self.config.audio_highpass_hz = highpass.then_some(60);
+                }
+                if let Some(hz) = &mut self.config.audio_highpass_hz {
+                    ui.add(egui::DragValue::new(hz).range(20..=1000).suffix(" Hz"));
+                }
+                
+                ui.add_space(12.0);
+                
+                let mut lowpass = self.config.audio_lowpass_hz.is_some();
+                if ui.checkbox(&mut lowpass, "Lowpass").changed() {
+                    self.config.audio_lowpass_hz = lowpass.then_some(18000);
+                }
+                if let Some(hz) = &mut self.config.audio_lowpass_hz {
+                    ui.add(egui::DragValue::new(hz).range(1000..=20000).suffix(" Hz"));
+                }
+            });
+            
+            ui.add_space(10.0);
+            
+            // Settings file to hand to someone else
+            ui.horizontal(|ui| {
+                ui.label("Share settings:");
+                if ui.button("⬆ Export…")
+                    .on_hover_text("Save these settings and the overrides of open windows to a JSON file")
+                    .clicked()
+                {
+                    self.export_settings();
+                }
+                if ui.button("⬇ Import…").clicked() {
+                    self.import_settings();
+                }
+            });
+            
             ui.add_space(20.0);
             
             // ffmpeg status
@@ -399,14 +1511,7 @@ impl AppState {
                     if !self.has_permissions {
                         ui.colored_label(egui::Color32::RED, "⚠ Screen recording permission required");
                         if ui.button("🔐 Grant Access").clicked() {
-                            let granted = macos::request_screen_capture_access();
-                            self.has_permissions = granted;
-                            if !granted {
-                                self.status = "Permission denied. Enable in System Settings > Privacy & Security > Screen Recording.".to_string();
-                            } else {
-                                self.status = "Permission granted.".to_string();
-                                self.refresh_windows();
-                            }
+                            self.request_permission();
                         }
                     } else {
                         ui.colored_label(egui::Color32::GREEN, "✓ Screen recording permission granted");
@@ -420,14 +1525,45 @@ impl AppState {
         let mut to_start: Vec<u64> = Vec::new();
         let mut to_stop: Vec<u64> = Vec::new();
         
+        self.render_permission_banner(ui);
+        
+        let mut windows: Vec<_> = self.window_manager.windows().iter().cloned().collect();
+        // Manual order first; new windows follow by window_id for consistent ordering
+        sort_by_saved_order(&mut windows, &self.window_order);
+        let mut reorder: Option<(u64, usize)> = None;
+        
+        // Keyboard navigation: ↑/↓ move focus, Enter/Space toggle recording, R expands
+        let mut focus_moved = false;
+        if !windows.is_empty() && !ctx.wants_keyboard_input() {
+            let (up, down, toggle, expand) = ctx.input(|i| (
+                i.key_pressed(egui::Key::ArrowUp),
+                i.key_pressed(egui::Key::ArrowDown),
+                i.key_pressed(egui::Key::Enter) || i.key_pressed(egui::Key::Space),
+                i.key_pressed(egui::Key::R),
+            ));
+            self.focused_window = window::move_focus(&windows, self.focused_window, up, down);
+            focus_moved = up || down;
+            
+            if let Some(window_id) = self.focused_window {
+                if toggle {
+                    if self.recorder.lock().is_recording(window_id) {
+                        to_stop.push(window_id);
+                    } else {
+                        to_start.push(window_id);
+                    }
+                }
+                if expand && self.expanded_previews.remove(&window_id).is_none() {
+                    // Keep the single-expanded behavior of the expand button
+                    self.expanded_previews.clear();
+                    self.expanded_previews.insert(window_id, true);
+                }
+            }
+        }
+        
         // Grid view with expandable inline previews - use full width and height
         egui::ScrollArea::vertical()
             .auto_shrink([false, false]) // Don't auto-shrink horizontally or vertically
             .show(ui, |ui| {
-            let mut windows: Vec<_> = self.window_manager.windows().iter().cloned().collect();
-            // Sort windows by window_id for consistent ordering
-            windows.sort_by_key(|w| w.window_id);
-            
             if windows.is_empty() {
                 ui.centered_and_justified(|ui| {
                     ui.label("No windows found. Click 'Refresh windows' to scan again.");
@@ -440,14 +1576,59 @@ impl AppState {
                     egui::vec2(available_width, available_height),
                     egui::Layout::top_down(egui::Align::Min),
                     |ui| {
-                        for window in &windows {
+                        for (index, window) in windows.iter().enumerate() {
                             let is_rec = self.recorder.lock().is_recording(window.window_id);
-                            self.render_window_with_expanded_content(ui, ctx, window, is_rec, &mut to_start, &mut to_stop);
+                            let row_top = ui.cursor().min.y;
+                            // Widget state (text edits, collapsing headers) follows the
+                            // window rather than its position when the list reorders
+                            ui.push_id(window.window_id, |ui| {
+                                self.render_window_with_expanded_content(ui, ctx, window, is_rec, &mut to_start, &mut to_stop);
+                            });
+                            
+                            // Focus ring around the keyboard-focused row
+                            if self.focused_window == Some(window.window_id) {
+                                let rect = egui::Rect::from_min_max(
+                                    egui::pos2(ui.max_rect().left(), row_top),
+                                    egui::pos2(ui.max_rect().right(), ui.cursor().min.y),
+                                );
+                                ui.painter().rect_stroke(
+                                    rect.shrink(1.0),
+                                    4.0,
+                                    egui::Stroke::new(2.0, ui.visuals().selection.stroke.color),
+                                );
+                                if focus_moved {
+                                    ui.scroll_to_rect(rect, None);
+                                }
+                            }
+                            
+                            // Drop target for drag-to-reorder: insert above or below this row
+                            let row_rect = egui::Rect::from_min_max(
+                                egui::pos2(ui.max_rect().left(), row_top),
+                                egui::pos2(ui.max_rect().right(), ui.cursor().min.y),
+                            );
+                            if egui::DragAndDrop::has_payload_of_type::<u64>(ctx) && ui.rect_contains_pointer(row_rect) {
+                                let pointer_y = ctx.pointer_interact_pos().map_or(row_rect.center().y, |p| p.y);
+                                let below = pointer_y > row_rect.center().y;
+                                ui.painter().hline(
+                                    row_rect.x_range(),
+                                    if below { row_rect.bottom() } else { row_rect.top() },
+                                    egui::Stroke::new(2.0, ui.visuals().selection.stroke.color),
+                                );
+                                if ctx.input(|i| i.pointer.any_released()) {
+                                    if let Some(dragged) = egui::DragAndDrop::take_payload::<u64>(ctx) {
+                                        reorder = Some((*dragged, index + below as usize));
+                                    }
+                                }
+                            }
                         }
                     }
                 );
             }
         });
+        
+        if let Some((window_id, target)) = reorder {
+            self.reorder_window(&windows, window_id, target);
+        }
 
         for id in to_start {
             self.start_for_window(id);
@@ -458,199 +1639,6 @@ impl AppState {
         }
     }
     
-    fn render_window_row(
-        &mut self,
-        ui: &mut egui::Ui,
-        ctx: &egui::Context,
-        window: &window::WindowInfo,
-        is_rec: bool,
-        to_start: &mut Vec<u64>,
-        to_stop: &mut Vec<u64>,
-    ) {
-        let window_id = window.window_id;
-        let is_expanded = self.expanded_previews.get(&window_id).copied().unwrap_or(false);
-        
-        // Window row: |fixed expand icon|stretching content|fixed action buttons|
-        ui.horizontal(|ui| {
-            // 1. Fixed expand icon (30px width)
-            ui.allocate_ui_with_layout(
-                egui::vec2(30.0, ui.available_height()),
-                egui::Layout::centered_and_justified(egui::Direction::TopDown),
-                |ui| {
-                    let preview_icon = if is_expanded { "▼" } else { "▶" };
-                    if ui.button(preview_icon).clicked() {
-                        if is_expanded {
-                            // If currently expanded, close it
-                            self.expanded_previews.remove(&window_id);
-                        } else {
-                            // If currently closed, close all others and open this one
-                            self.expanded_previews.clear();
-                            self.expanded_previews.insert(window_id, true);
-                        }
-                    }
-                }
-            );
-            
-            // 2. Stretching content with window name (fills remaining space)
-            ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
-                ui.vertical(|ui| {
-                    // Window name and dimensions - use full width
-                    ui.horizontal(|ui| {
-                        // Window name with ellipsis truncation - takes remaining space
-                        ui.label(window.display_name());
-                        
-                        // Dimensions text - fixed size
-                        ui.label(egui::RichText::new(format!("({})", window.dimensions_str()))
-                            .small()
-                            .color(ui.style().visuals.weak_text_color()));
-                    });
-                    
-                    // Status information
-                    let is_starting = self.starting_recordings.lock().get(&window_id).copied().unwrap_or(false);
-                    
-                    if is_starting {
-                        ui.horizontal(|ui| {
-                            ui.spinner();
-                            ui.colored_label(egui::Color32::YELLOW, "Starting...");
-                        });
-                    } else if is_rec {
-                        // Show recording time
-                        if let Some(start_time) = self.recording_start_times.lock().get(&window_id) {
-                            let duration = start_time.elapsed();
-                            let total_seconds = duration.as_secs();
-                            let minutes = total_seconds / 60;
-                            let seconds = total_seconds % 60;
-                            let milliseconds = duration.subsec_millis();
-                            ui.horizontal(|ui| {
-                                ui.colored_label(egui::Color32::GREEN, "● REC");
-                                ui.label(egui::RichText::new(format!("{:02}:{:02}.{:03}", minutes, seconds, milliseconds))
-                                    .color(egui::Color32::GREEN)
-                                    .monospace());
-                            });
-                        }
-                    }
-                });
-            });
-            
-            // 3. Fixed area for action buttons (100px width) - pulled to the right
-            ui.allocate_ui_with_layout(
-                egui::vec2(100.0, ui.available_height()),
-                egui::Layout::right_to_left(egui::Align::Center),
-                |ui| {
-                    ui.add_space(10.0); // 10px margin from right edge
-                    
-                    if is_rec {
-                        if ui.button("⏹ Stop").clicked() {
-                            to_stop.push(window_id);
-                        }
-                    } else {
-                        if ui.button("⏺ Start").clicked() {
-                            to_start.push(window_id);
-                        }
-                    }
-                }
-            );
-        });
-    
-        // Expanded content below the fixed-height row
-        if is_expanded {
-            ui.add_space(6.0);
-            ui.indent("expanded", |ui| {
-                ui.horizontal(|ui| {
-                    let preview_width = 400.0;
-                    let preview_height = 225.0;
-    
-                    ui.allocate_ui_with_layout(
-                        egui::vec2(preview_width, preview_height),
-                        egui::Layout::centered_and_justified(egui::Direction::TopDown),
-                        |ui| {
-                            #[cfg(target_os = "macos")]
-                            {
-                                let mut cache = self.preview_cache.lock();
-    
-                                if let Some(texture) = cache.get_or_update(
-                                    ctx,
-                                    window_id,
-                                    || macos::capture_window_image(window_id),
-                                ) {
-                                    let size = texture.size_vec2();
-                                    let scale = (preview_width / size.x).min(preview_height / size.y).min(1.0);
-                                    let display_size = size * scale;
-                                    ui.image((texture.id(), display_size));
-                                } else {
-                                    ui.label("Failed to capture preview");
-                                }
-                            }
-    
-                            #[cfg(not(target_os = "macos"))]
-                            {
-                                ui.label("Preview not available on this platform");
-                            }
-                        },
-                    );
-    
-                    ui.add_space(12.0);
-    
-                    // Settings panel (unchanged)
-                    ui.vertical(|ui| {
-                        let settings = self
-                            .window_settings
-                            .entry(window_id)
-                            .or_insert_with(WindowRecordingSettings::default);
-    
-                        ui.horizontal(|ui| {
-                            ui.label("Output folder:");
-                        });
-                        ui.horizontal(|ui| {
-                            if let Some(ref folder) = settings.output_folder {
-                                ui.label(egui::RichText::new(folder.display().to_string()).small());
-                                if ui.small_button("❌").clicked() {
-                                    settings.output_folder = None;
-                                }
-                            } else {
-                                ui.label(egui::RichText::new("(use default)").small().italics());
-                            }
-                            if ui.small_button("📁").clicked() {
-                                let initial = settings
-                                    .output_folder
-                                    .clone()
-                                    .or_else(|| self.config.output_dir.clone());
-                                if let Some(path) = rfd::FileDialog::new()
-                                    .set_directory(initial.unwrap_or_else(|| PathBuf::from(".")))
-                                    .pick_folder()
-                                {
-                                    settings.output_folder = Some(path);
-                                }
-                            }
-                        });
-    
-                        ui.add_space(8.0);
-    
-                        ui.horizontal(|ui| {
-                            ui.label("Filename:");
-                        });
-                        ui.horizontal(|ui| {
-                            let mut filename = settings.custom_filename.clone().unwrap_or_default();
-                            let response = ui.add_sized(
-                                egui::vec2(200.0, 20.0),
-                                egui::TextEdit::singleline(&mut filename).hint_text("auto-generated"),
-                            );
-                            if response.changed() {
-                                if filename.is_empty() {
-                                    settings.custom_filename = None;
-                                } else {
-                                    settings.custom_filename = Some(filename);
-                                }
-                            }
-                        });
-                    });
-                });
-            });
-        }
-    
-        ui.separator();
-    }
-    
     fn render_window_with_expanded_content(
         &mut self,
         ui: &mut egui::Ui,
@@ -668,7 +1656,7 @@ impl AppState {
         // Fixed metrics
         const EXPAND_W: f32 = 30.0;    // expand/collapse icon area width
         const SPACING_W: f32 = 10.0;   // spacing between expand button and window name
-        const BUTTONS_W: f32 = 120.0;  // start/stop buttons area width
+        const BUTTONS_W: f32 = 244.0;  // start/stop/mark/copy/arm buttons area width
         const ROW_H: f32 = 32.0;       // row height
     
         // Allocate entire row once; split into explicit sub-rects to avoid layout drift
@@ -727,18 +1715,71 @@ impl AppState {
             {
                 ui.allocate_ui_at_rect(name_dims_rect, |ui| {
                     ui.with_layout(egui::Layout::top_down(egui::Align::LEFT), |ui| {
-                        // Window name: left-aligned, non-wrapping, truncates with ellipsis
-                        let name_label = egui::Label::new(egui::RichText::new(window.display_name()))
-                            .truncate();
-                        ui.add(name_label);
+                        // Drag handle and window name: left-aligned, non-wrapping, truncates with ellipsis
+                        ui.horizontal(|ui| {
+                            ui.dnd_drag_source(egui::Id::new(("reorder_window", window_id)), window_id, |ui| {
+                                ui.label(egui::RichText::new("☰").color(ui.style().visuals.weak_text_color()));
+                            })
+                            .response
+                            .on_hover_text("Drag to reorder");
+                            let name_label = egui::Label::new(egui::RichText::new(window.display_name()))
+                                .truncate();
+                            ui.add(name_label);
+                        });
                         
-                        // Dimensions: left-aligned, smaller text
-                        let dims_text = format!("({})", window.dimensions_str());
-                        ui.label(
-                            egui::RichText::new(dims_text)
-                                .small()
-                                .color(ui.style().visuals.weak_text_color()),
-                        );
+                        // Dimensions (or the failure reason): left-aligned, smaller text
+                        if let Some((err, _)) = self.recording_errors.get(&window_id) {
+                            ui.add(egui::Label::new(
+                                egui::RichText::new(format!("⚠ Recording failed: {}", err))
+                                    .small()
+                                    .color(egui::Color32::RED),
+                            ).truncate());
+                        } else {
+                            let dims_text = format!("({})", window.dimensions_str());
+                            let (health, encoder, output_bytes, output_stalled) = {
+                                let rec = self.recorder.lock();
+                                (
+                                    rec.capture_health(window_id),
+                                    rec.encoder(window_id),
+                                    rec.output_bytes(window_id),
+                                    rec.output_stalled(window_id, OUTPUT_STALL_WARNING),
+                                )
+                            };
+                            ui.horizontal(|ui| {
+                                // Capture health dot while recording
+                                if let Some(health) = health {
+                                    let color = match health {
+                                        CaptureHealth::Fresh => egui::Color32::GREEN,
+                                        CaptureHealth::Stale => egui::Color32::YELLOW,
+                                        CaptureHealth::Failing => egui::Color32::RED,
+                                    };
+                                    ui.label(egui::RichText::new("●").small().color(color))
+                                        .on_hover_text(health.description());
+                                }
+                                // Which encoder won the fallback cascade
+                                if let Some(encoder) = encoder {
+                                    let color = match encoder {
+                                        VideoEncoder::Libx264 => egui::Color32::from_rgb(230, 160, 60),
+                                        _ => egui::Color32::from_rgb(90, 170, 240),
+                                    };
+                                    ui.label(egui::RichText::new(encoder.short_label()).small().monospace().color(color))
+                                        .on_hover_text(encoder.label());
+                                }
+                                // Size on disk, so a recording that writes nothing stands out
+                                if output_stalled {
+                                    ui.label(egui::RichText::new(format!("⚠ {}", format_size(output_bytes.unwrap_or(0)))).small().color(egui::Color32::YELLOW))
+                                        .on_hover_text(format!("The file hasn't grown in {} s; ffmpeg may not be writing frames", OUTPUT_STALL_WARNING.as_secs()));
+                                } else if let Some(bytes) = output_bytes {
+                                    ui.label(egui::RichText::new(format_size(bytes)).small().monospace())
+                                        .on_hover_text("Size of the file being written");
+                                }
+                                ui.label(
+                                    egui::RichText::new(dims_text)
+                                        .small()
+                                        .color(ui.style().visuals.weak_text_color()),
+                                );
+                            });
+                        }
                     });
                 });
             }
@@ -750,13 +1791,8 @@ impl AppState {
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                     if is_rec {
                         // Create stop button with runtime and red styling
-                        let runtime_text = if let Some(start_time) = self.recording_start_times.lock().get(&window_id) {
-                            let duration = start_time.elapsed();
-                            let total_seconds = duration.as_secs();
-                            let minutes = total_seconds / 60;
-                            let seconds = total_seconds % 60;
-                            let milliseconds = duration.subsec_millis();
-                            format!("{:02}:{:02}.{:03}", minutes, seconds, milliseconds)
+                        let runtime_text = if let Some(start_time) = self.recording_start_times.get(&window_id) {
+                            format_elapsed(start_time.elapsed())
                         } else {
                             "00:00.000".to_string()
                         };
@@ -765,120 +1801,486 @@ impl AppState {
                         if ui.add_sized(egui::vec2(90.0, ROW_H), egui::Button::new(stop_button_text).fill(egui::Color32::from_rgb(220, 53, 69))).clicked() {
                             to_stop.push(window_id);
                         }
+                        
+                        // Chapter marker button
+                        let marker_count = self.recorder.lock().marker_count(window_id);
+                        if ui.add_sized(egui::vec2(36.0, ROW_H), egui::Button::new("🔖"))
+                            .on_hover_text(format!("Add chapter marker (M) — {} so far", marker_count))
+                            .clicked()
+                        {
+                            self.add_marker(window_id);
+                        }
+                    } else if self.starting_recordings.contains_key(&window_id) {
+                        if ui.add_sized(egui::vec2(54.0, ROW_H), egui::Button::new("✕"))
+                            .on_hover_text("Cancel starting")
+                            .clicked()
+                        {
+                            self.cancel_start(window_id);
+                        }
+                        ui.add_sized(egui::vec2(32.0, ROW_H), egui::Spinner::new())
+                            .on_hover_text("Starting...");
+                    } else if self.recording_errors.contains_key(&window_id) {
+                        let retry = egui::Button::new("↻ Retry").fill(egui::Color32::from_rgb(200, 120, 30));
+                        if ui.add_enabled_ui(!self.capture_blocked(), |ui| ui.add_sized(egui::vec2(90.0, ROW_H), retry))
+                            .inner
+                            .on_disabled_hover_text("Grant the screen recording permission first")
+                            .clicked()
+                        {
+                            to_start.push(window_id);
+                        }
                     } else {
-                        if ui.add_sized(egui::vec2(90.0, ROW_H), egui::Button::new("⏺ Start")).clicked() {
+                        if ui.add_enabled_ui(!self.capture_blocked(), |ui| ui.add_sized(egui::vec2(90.0, ROW_H), egui::Button::new("⏺ Start")))
+                            .inner
+                            .on_disabled_hover_text("Grant the screen recording permission first")
+                            .clicked()
+                        {
                             to_start.push(window_id);
                         }
                     }
-                });
-            });
-        }
+                    
+                    if ui.add_sized(egui::vec2(36.0, ROW_H), egui::Button::new("📋"))
+                        .on_hover_text("Copy a screenshot of the window to the clipboard")
+                        .clicked()
+                    {
+                        self.copy_window_image(window_id);
+                    }
+                    
+                    // Voice activation: start recording when the audio input gets loud
+                    let armed = self.voice_armed.contains_key(&window_id);
+                    if ui.add_sized(egui::vec2(36.0, ROW_H), egui::Button::new("🎙").selected(armed))
+                        .on_hover_text("Start recording when the audio input gets louder than the trigger level. Use \"Stop after silence\" to stop again.")
+                        .clicked()
+                    {
+                        if armed {
+                            self.voice_armed.remove(&window_id);
+                        } else {
+                            self.voice_armed.insert(window_id, None);
+                        }
+                    }
+                });
+            });
+        }
     
         // Expanded content below fixed-height row
         if is_expanded {
-            ui.add_space(6.0);
-            ui.indent("expanded", |ui| {
-                ui.horizontal(|ui| {
-                    let preview_width = 400.0;
-                    let preview_height = 225.0;
-    
-                    ui.allocate_ui_with_layout(
-                        egui::vec2(preview_width, preview_height),
-                        egui::Layout::centered_and_justified(egui::Direction::TopDown),
-                        |ui| {
-                            #[cfg(target_os = "macos")]
-                            {
-                                let mut cache = self.preview_cache.lock();
-                                if let Some(texture) = cache.get_or_update(
-                                    ctx,
-                                    window_id,
-                                    || macos::capture_window_image(window_id),
-                                ) {
-                                    let size = texture.size_vec2();
-                                    let scale = (preview_width / size.x).min(preview_height / size.y).min(1.0);
-                                    let display_size = size * scale;
-                                    ui.image((texture.id(), display_size));
+            self.render_expanded_panel(ui, ctx, window, is_rec);
+        }
+    
+        ui.separator();
+    }
+
+    /// Preview and per-window settings shown below an expanded window row
+    fn render_expanded_panel(
+        &mut self,
+        ui: &mut egui::Ui,
+        ctx: &egui::Context,
+        window: &window::WindowInfo,
+        is_rec: bool,
+    ) {
+        let window_id = window.window_id;
+        let mut picked_dir: Option<PathBuf> = None; // Output folder chosen for this window this frame
+
+        ui.add_space(6.0);
+        ui.indent("expanded", |ui| {
+            ui.horizontal(|ui| {
+                // Preview box follows the window's aspect ratio, within PREVIEW_MAX_SIZE
+                let rotation = self.window_settings.get(&window_id).map_or(Rotation::None, |s| s.rotation);
+                let (preview_width, preview_height) = preview_box(window, rotation);
+
+                ui.allocate_ui_with_layout(
+                    egui::vec2(preview_width, preview_height),
+                    egui::Layout::centered_and_justified(egui::Direction::TopDown),
+                    |ui| {
+                        #[cfg(target_os = "macos")]
+                        {
+                            // A recording shares the frame it last sent to ffmpeg, so
+                            // the preview shows the output and costs no extra capture
+                            let latest_frame = self.recorder.lock().latest_frame(window_id);
+                            let mut cache = self.preview_cache.lock();
+                            let texture = cache.get_or_update(
+                                ctx,
+                                window_id,
+                                move || {
+                                    let (rgba, width, height) = match latest_frame.and_then(|slot| slot.lock().clone()) {
+                                        Some(frame) => (frame.rgba, frame.width, frame.height),
+                                        None => macos::capture_window_image(window_id)
+                                            .map(|(buffer, width, height)| (Arc::new(buffer), width, height))?,
+                                    };
+                                    if rotation == Rotation::None {
+                                        return Some((rgba, width, height));
+                                    }
+                                    let (rotated, width, height) = rotation.rotate_rgba(&rgba, width, height);
+                                    Some((Arc::new(rotated), width, height))
+                                },
+                            )
+                            .map(|texture| (texture.id(), texture.size_vec2()));
+                            if let Some((texture_id, size)) = texture {
+                                let scale = (preview_width / size.x).min(preview_height / size.y).min(1.0);
+                                let display_size = size * scale;
+                                let image_rect = ui.image((texture_id, display_size)).rect;
+                                
+                                // A recording's own last capture says more than this preview's
+                                let age = if is_rec {
+                                    self.recorder.lock().last_capture(window_id)
+                                        .map(|t| ("last frame", t.elapsed()))
                                 } else {
-                                    ui.label("Failed to capture preview");
+                                    cache.age(window_id).map(|age| ("updated", age))
+                                };
+                                if let Some((label, age)) = age {
+                                    paint_frame_age(ui, image_rect, label, age);
                                 }
+                                // The crop outline, while it lines up with the unrotated
+                                // preview; a recording's frames are already cropped
+                                let crop = self.window_settings.get(&window_id).and_then(|s| s.crop);
+                                if let Some(crop) = crop.filter(|_| rotation == Rotation::None && !is_rec) {
+                                    let points = image_rect.width() / window.width.max(1) as f32;
+                                    let outline = egui::Rect::from_min_size(
+                                        image_rect.min + egui::vec2(crop.x as f32, crop.y as f32) * points,
+                                        egui::vec2(crop.width as f32, crop.height as f32) * points,
+                                    )
+                                    .intersect(image_rect);
+                                    ui.painter().rect_stroke(outline, 0.0, egui::Stroke::new(1.5, egui::Color32::YELLOW));
+                                }
+                                ctx.request_repaint_after(Duration::from_secs(1));
+                            } else if cache.is_pending(window_id) {
+                                ui.spinner();
+                            } else {
+                                ui.label("Failed to capture preview");
+                                ctx.request_repaint_after(Duration::from_secs(1));
+                            }
+                        }
+                        #[cfg(not(target_os = "macos"))]
+                        {
+                            ui.label("Preview not available on this platform");
+                        }
+                    },
+                );
+
+                ui.add_space(12.0);
+
+                // Settings (unchanged)
+                ui.vertical(|ui| {
+                    let extension = self.recording_config_for(window).container_extension();
+                    let settings = self
+                        .window_settings
+                        .entry(window_id)
+                        .or_insert_with(WindowRecordingSettings::default);
+
+                    ui.horizontal(|ui| {
+                        ui.label("Output folder:");
+                    });
+                    ui.horizontal(|ui| {
+                        if let Some(folder) = &settings.output_folder {
+                            ui.label(egui::RichText::new(folder.display().to_string()).small());
+                            if ui.small_button("❌").clicked() {
+                                settings.output_folder = None;
                             }
-                            #[cfg(not(target_os = "macos"))]
+                        } else if let Some(rule) = self.config.app_folders.iter().find(|rule| rule.matches(window)) {
+                            ui.label(egui::RichText::new(format!("{} (for {})", rule.folder.display(), rule.app)).small().italics());
+                        } else {
+                            ui.label(egui::RichText::new("(use default)").small().italics());
+                        }
+                        if ui.small_button("📁").clicked() {
+                            let initial = settings
+                                .output_folder
+                                .clone()
+                                .or_else(|| self.config.output_dir.clone());
+                            if let Some(path) = rfd::FileDialog::new()
+                                .set_directory(initial.unwrap_or_else(|| PathBuf::from(".")))
+                                .pick_folder()
                             {
-                                ui.label("Preview not available on this platform");
+                                picked_dir = Some(path);
                             }
-                        },
-                    );
-    
-                    ui.add_space(12.0);
-    
-                    // Settings (unchanged)
-                    ui.vertical(|ui| {
-                        let settings = self
-                            .window_settings
-                            .entry(window_id)
-                            .or_insert_with(WindowRecordingSettings::default);
-    
-                        ui.horizontal(|ui| {
-                            ui.label("Output folder:");
-                        });
-                        ui.horizontal(|ui| {
-                            if let Some(folder) = &settings.output_folder {
-                                ui.label(egui::RichText::new(folder.display().to_string()).small());
-                                if ui.small_button("❌").clicked() {
-                                    settings.output_folder = None;
-                                }
-                            } else {
-                                ui.label(egui::RichText::new("(use default)").small().italics());
+                        }
+                        if let Some(path) = recent_dirs_menu(ui, &self.recent_dirs) {
+                            picked_dir = Some(path);
+                        }
+                        if let Some(path) = &picked_dir {
+                            settings.output_folder = Some(path.clone());
+                        }
+                    });
+
+                    ui.add_space(8.0);
+
+                    ui.horizontal(|ui| {
+                        ui.label("Filename:");
+                    });
+                    ui.horizontal(|ui| {
+                        let mut filename = settings.custom_filename.clone().unwrap_or_default();
+                        let response = ui.add_sized(
+                            egui::vec2(200.0, 20.0),
+                            egui::TextEdit::singleline(&mut filename).hint_text("auto-generated"),
+                        )
+                        .on_hover_text(format!("Tokens: {} {{counter}}", ffmpeg::PATH_TOKENS));
+                         if response.changed() {
+                             settings.custom_filename = if filename.is_empty() {
+                                 None
+                             } else {
+                                 Some(filename)
+                             };
+                         }
+                    });
+                    // What the name turns into once tokens are filled in and
+                    // characters file names can't hold are dropped
+                    if let Some(name) = settings.custom_filename.as_deref() {
+                        if ffmpeg::sanitize_custom_filename(name, window).is_empty() {
+                            ui.colored_label(egui::Color32::YELLOW, "⚠ Nothing is left of this name in a file name; only a timestamp will be used");
+                        } else {
+                            let output_dir = settings.output_folder.clone().or_else(|| self.config.output_dir_for(window).cloned());
+                            let path = ffmpeg::planned_output_path(
+                                window,
+                                output_dir.as_ref(),
+                                self.config.folder_template.as_deref(),
+                                Some(name),
+                                extension,
+                            );
+                            ui.label(
+                                egui::RichText::new(format!("→ {}", ffmpeg::output_path_with_counter(&path, 1).display()))
+                                    .small()
+                                    .color(ui.style().visuals.weak_text_color()),
+                            );
+                        }
+                    }
+                    
+                    // Finder tag for this window's recordings: a color, or any word. It's
+                    // a per-window setting; there's no list of past recordings to tag
+                    // one at a time, and nothing is kept beside the file
+                    #[cfg(target_os = "macos")]
+                    ui.horizontal(|ui| {
+                        ui.label("Finder tag for its recordings:");
+                        for (name, color) in FINDER_COLOR_TAGS {
+                            let selected = settings.finder_tag.as_deref() == Some(name);
+                            if ui.selectable_label(selected, egui::RichText::new("●").color(color))
+                                .on_hover_text(name)
+                                .clicked()
+                            {
+                                settings.finder_tag = (!selected).then(|| name.to_string());
                             }
-                            if ui.small_button("📁").clicked() {
-                                let initial = settings
-                                    .output_folder
-                                    .clone()
-                                    .or_else(|| self.config.output_dir.clone());
-                                if let Some(path) = rfd::FileDialog::new()
-                                    .set_directory(initial.unwrap_or_else(|| PathBuf::from(".")))
-                                    .pick_folder()
-                                {
-                                    settings.output_folder = Some(path);
+                        }
+                        let mut word = settings.finder_tag.clone()
+                            .filter(|tag| !FINDER_COLOR_TAGS.iter().any(|(name, _)| name == tag))
+                            .unwrap_or_default();
+                        if ui.add(egui::TextEdit::singleline(&mut word).desired_width(90.0).hint_text("or a word"))
+                            .on_hover_text("Set on every file a recording of this window wrote, split files and time segments included, when it stops. Finder and Spotlight can sort and search by it.")
+                            .changed()
+                        {
+                            settings.finder_tag = (!word.is_empty()).then_some(word);
+                        }
+                    });
+                    
+                    ui.add_space(8.0);
+                    
+                    ui.horizontal(|ui| {
+                        ui.label("Format:");
+                    });
+                    ui.horizontal(|ui| {
+                        egui::ComboBox::from_id_salt(("window_encoder", window_id))
+                            .width(120.0)
+                            .selected_text(settings.encoder.map_or("Default encoder", |e| e.label()))
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut settings.encoder, None, "Default encoder");
+                                for encoder in ENCODERS {
+                                    ui.selectable_value(&mut settings.encoder, Some(encoder), encoder.label());
+                                }
+                            });
+                        egui::ComboBox::from_id_salt(("window_container", window_id))
+                            .width(70.0)
+                            .selected_text(settings.container.map_or("Default", |c| c.extension()))
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut settings.container, None, "Default");
+                                for container in [Container::Mp4, Container::Mov, Container::Mkv] {
+                                    ui.selectable_value(&mut settings.container, Some(container), container.extension());
                                 }
+                            });
+                        let mut override_fps = settings.fps.is_some();
+                        if ui.checkbox(&mut override_fps, "FPS").changed() {
+                            settings.fps = override_fps.then_some(self.config.fps);
+                        }
+                        if let Some(fps) = &mut settings.fps {
+                            ui.add(egui::DragValue::new(fps).range(1..=120));
+                        }
+                    });
+                    // The override records at the shared bitrate, which it may clamp
+                    let adjustment = settings.encoder
+                        .zip(self.config.rate_control.bitrate_kbps())
+                        .and_then(|(encoder, kbps)| encoder.bitrate_adjustment(kbps));
+                    if let Some(adjustment) = adjustment {
+                        ui.colored_label(egui::Color32::YELLOW, format!("⚠ {}", adjustment));
+                    }
+                    ui.horizontal(|ui| {
+                        ui.label("Rotate:");
+                        for rotation in [Rotation::None, Rotation::Cw90, Rotation::Cw180, Rotation::Cw270] {
+                            ui.selectable_value(&mut settings.rotation, rotation, rotation.label());
+                        }
+                    })
+                    .response
+                    .on_hover_text("Clockwise, e.g. for a mirrored phone in portrait. The preview shows the result.");
+                    
+                    // This window's video with everything the Mac plays
+                    ui.horizontal(|ui| {
+                        match audio::system_audio_device(self.audio_device_manager.get_devices()) {
+                            Some(device) => {
+                                ui.toggle_value(&mut settings.system_audio, "🔊 Window + system audio")
+                                    .on_hover_text(format!("Record this window with system audio from {} instead of the selected input", device.name));
                             }
-                        });
-    
-                        ui.add_space(8.0);
-    
-                        ui.horizontal(|ui| {
-                            ui.label("Filename:");
-                        });
-                        ui.horizontal(|ui| {
-                            let mut filename = settings.custom_filename.clone().unwrap_or_default();
-                            let response = ui.add_sized(
-                                egui::vec2(200.0, 20.0),
-                                egui::TextEdit::singleline(&mut filename).hint_text("auto-generated"),
-                            );
-                             if response.changed() {
-                                 settings.custom_filename = if filename.is_empty() {
-                                     None
-                                 } else {
-                                     Some(filename)
-                                 };
-                             }
-                        });
-                        
-                        ui.add_space(8.0);
-                        
-                        // Audio level indicator for this window
-                        if let Some(device_id) = &self.selected_audio_device {
-                            if let Some(monitor) = self.audio_device_manager.get_level_monitor(device_id) {
-                                let level = monitor.get_level();
-                                self.render_audio_level_indicator(ui, level);
+                            None => {
+                                ui.add_enabled(false, egui::SelectableLabel::new(false, "🔊 Window + system audio"))
+                                    .on_disabled_hover_text("Needs a loopback audio device. Install BlackHole (brew install blackhole-2ch), then in Audio MIDI Setup create a Multi-Output Device with your speakers and BlackHole and make it the system output.");
+                                if settings.system_audio {
+                                    ui.colored_label(egui::Color32::YELLOW, "⚠ No loopback device; records the selected input");
+                                }
                             }
                         }
                     });
+                    
+                    // Record only part of the window, in window points
+                    egui::CollapsingHeader::new("Advanced")
+                        .id_salt(("window_advanced", window_id))
+                        .default_open(settings.crop.is_some())
+                        .show(ui, |ui| {
+                            let mut cropped = settings.crop.is_some();
+                            if ui.checkbox(&mut cropped, "Crop to a region").changed() {
+                                settings.crop = cropped.then(|| CropRegion {
+                                    x: 0,
+                                    y: 0,
+                                    width: window.width.max(1) as u32,
+                                    height: window.height.max(1) as u32,
+                                    follow_resize: false,
+                                });
+                            }
+                            if let Some(crop) = &mut settings.crop {
+                                let (max_w, max_h) = (window.width.max(1) as u32, window.height.max(1) as u32);
+                                ui.horizontal(|ui| {
+                                    ui.label("x");
+                                    ui.add(egui::DragValue::new(&mut crop.x).range(0..=max_w - 1));
+                                    ui.label("y");
+                                    ui.add(egui::DragValue::new(&mut crop.y).range(0..=max_h - 1));
+                                    ui.label("size");
+                                    ui.add(egui::DragValue::new(&mut crop.width).range(1..=max_w));
+                                    ui.label("×");
+                                    ui.add(egui::DragValue::new(&mut crop.height).range(1..=max_h));
+                                });
+                                ui.checkbox(&mut crop.follow_resize, "Follow window resizes")
+                                    .on_hover_text("Scale the region with the window, so it keeps covering the same content. Otherwise it keeps its size and position.");
+                            }
+                        });
+                    
+                    ui.add_space(8.0);
+                    
+                    // Sustainable capture rate vs. the rate this window captures at
+                    let target_fps = settings.fps.unwrap_or(self.config.fps);
+                    let target_fps = self.config.capture_fps.map_or(target_fps, |rate| rate.clamp(1, target_fps.max(1)));
+                    let capture_rate = self.capture_rates.lock().get(&window_id).copied();
+                    let mut measure = false;
+                    ui.horizontal(|ui| {
+                        match capture_rate {
+                            Some(rate) => ui.label(format!("Capture rate: ~{:.0} fps", rate)),
+                            None => ui.label(egui::RichText::new("Capture rate: not measured").small().italics()),
+                        };
+                        measure = ui.small_button("⏱ Measure").clicked();
+                    });
+                    if let Some(rate) = capture_rate.filter(|r| *r < target_fps as f64) {
+                        ui.colored_label(
+                            egui::Color32::YELLOW,
+                            format!(
+                                "⚠ Capture can only sustain ~{:.0} fps; recording will duplicate frames to reach {}",
+                                rate, target_fps
+                            ),
+                        );
+                    }
+                    if measure {
+                        self.measure_capture_rate(window_id);
+                    }
+                    #[cfg(target_os = "macos")]
+                    if macos::display_is_hdr(window) {
+                        ui.colored_label(egui::Color32::YELLOW, "⚠ HDR display: recordings are converted to SDR and bright highlights will clip")
+                            .on_hover_text("Turn off HDR for this display, or move the window to an SDR display, to record it as it looks");
+                    }
+                    #[cfg(target_os = "macos")]
+                    if let Some((low, high)) = macos::mixed_display_scales(window) {
+                        ui.colored_label(egui::Color32::YELLOW, format!("⚠ Spans displays at {}x and {}x: part of the recording will look soft", low, high))
+                            .on_hover_text("Move the window onto one display to record it at a single sharpness");
+                    }
+                    
+                    ui.add_space(8.0);
+                    
+                    if ui.small_button("📋 Copy ffmpeg command")
+                        .on_hover_text("The command this window would be recorded with. It reads raw RGBA frames from stdin.")
+                        .clicked()
+                    {
+                        self.copy_ffmpeg_command(ui.ctx(), window_id);
+                    }
+                    if self.config.debug_tools
+                        && ui.small_button("🐞 Export frame")
+                            .on_hover_text("Save the raw capture, a PNG of it and a text file with its sizes, to attach to a bug report")
+                            .clicked()
+                    {
+                        self.export_debug_frame(window_id);
+                    }
+                    
+                    ui.add_space(8.0);
+                    
+                    // Audio level indicator for this window
+                    if let Some(device_id) = &self.selected_audio_device {
+                        if let Some(monitor) = self.audio_device_manager.get_level_monitor(device_id) {
+                            let level = monitor.get_level();
+                            self.render_audio_level_indicator(ui, level);
+                        }
+                    }
+                    
+                    // Recent ffmpeg output while recording
+                    if is_rec {
+                        let output = self.recorder.lock().ffmpeg_output(window_id);
+                        egui::CollapsingHeader::new(format!("ffmpeg output ({} lines)", output.len()))
+                            .id_salt(("ffmpeg_output", window_id))
+                            .show(ui, |ui| {
+                                egui::ScrollArea::vertical()
+                                    .max_height(120.0)
+                                    .stick_to_bottom(true)
+                                    .show(ui, |ui| {
+                                        for line in &output {
+                                            ui.label(egui::RichText::new(line).small().monospace());
+                                        }
+                                    });
+                            });
+                    }
                 });
             });
+        });
+        if let Some(path) = picked_dir {
+            self.remember_dir(&path);
         }
+    }
     
-        ui.separator();
+    /// Move a window to position `target` of the displayed list and save the new order
+    fn reorder_window(&mut self, windows: &[WindowInfo], window_id: u64, target: usize) {
+        let Some(from) = windows.iter().position(|w| w.window_id == window_id) else {
+            return;
+        };
+        let mut order: Vec<String> = windows.iter().map(|w| w.stable_key()).collect();
+        let key = order.remove(from);
+        let target = if target > from { target - 1 } else { target };
+        order.insert(target.min(order.len()), key);
+        
+        // Keep saved positions of windows that aren't open right now
+        order.extend(self.window_order.iter().cloned());
+        let mut seen = HashSet::new();
+        order.retain(|key| seen.insert(key.clone()));
+        
+        self.window_order = order;
+        if let Err(e) = settings::save_window_order(&self.window_order) {
+            self.status = format!("Failed to save window order: {:#}", e);
+        }
+    }
+    
+    /// Put an output folder at the top of the recent list and save it
+    fn remember_dir(&mut self, dir: &std::path::Path) {
+        self.recent_dirs.retain(|d| d != dir);
+        self.recent_dirs.insert(0, dir.to_path_buf());
+        self.recent_dirs.truncate(RECENT_DIRS_LIMIT);
+        if let Err(e) = settings::save_recent_dirs(&self.recent_dirs) {
+            self.status = format!("Failed to save recent folders: {:#}", e);
+        }
     }
     
     fn refresh_windows(&mut self) {
@@ -892,9 +2294,49 @@ impl AppState {
         }
     }
 
+    /// Window titles are listed even without the screen recording permission,
+    /// but their content isn't: notice that before recordings come out black.
+    /// The probe captures a few windows, so it runs in the background and only
+    /// at startup, when the permission may have changed and on "Check again".
+    fn probe_capture(&mut self) {
+        #[cfg(target_os = "macos")]
+        if self.capture_probe.is_none() {
+            let (tx, rx) = std::sync::mpsc::channel();
+            std::thread::spawn(move || {
+                let _ = tx.send(macos::captures_are_blank().unwrap_or(false));
+            });
+            self.capture_probe = Some(rx);
+        }
+    }
+    
+    /// Take the result of `probe_capture` once it's in
+    fn collect_capture_probe(&mut self, ctx: &egui::Context) {
+        let Some(probe) = &self.capture_probe else {
+            return;
+        };
+        match probe.try_recv() {
+            Ok(blank) => {
+                self.captures_blank = blank;
+                self.capture_probe = None;
+            }
+            Err(std::sync::mpsc::TryRecvError::Empty) => ctx.request_repaint_after(Duration::from_millis(100)),
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => self.capture_probe = None,
+        }
+    }
+    
+    /// Recordings would be black until the permission is granted (and, after a
+    /// revoke, the app relaunched)
+    fn capture_blocked(&self) -> bool {
+        !self.has_permissions || self.captures_blank
+    }
+    
     fn start_for_window(&mut self, window_id: u64) {
         if self.ffmpeg_path.is_none() {
-            self.status = "ffmpeg not found. Install via Homebrew: brew install ffmpeg".to_string();
+            self.status = RecorderError::FfmpegMissing.to_string();
+            return;
+        }
+        if self.capture_blocked() {
+            self.status = RecorderError::PermissionDenied.to_string();
             return;
         }
         
@@ -907,42 +2349,39 @@ impl AppState {
             }
             
             let ffmpeg = self.ffmpeg_path.clone().unwrap();
-            let fps = self.config.fps.max(1);
-            let bitrate = self.config.bitrate_kbps.max(500);
             
-            // Get per-window settings or use defaults
-            let window_settings = self.window_settings.get(&window_id).cloned();
-            let output_dir = window_settings
-                .as_ref()
-                .and_then(|s| s.output_folder.clone())
-                .or_else(|| self.config.output_dir.clone());
-            let custom_filename = window_settings
-                .and_then(|s| s.custom_filename.clone());
+            // Starting again (or retrying) clears any previous failure
+            self.recording_errors.remove(&window_id);
+            
+            let config = self.recording_config_for(&info);
+            let fps = config.fps;
+            let (output_dir, custom_filename) = self.output_target(window_id);
             
             // Mark as starting and record start time immediately
-            self.starting_recordings.lock().insert(window_id, true);
-            self.recording_start_times.lock().insert(window_id, std::time::Instant::now());
+            let cancel = Arc::new(AtomicBool::new(false));
+            self.starting_recordings.insert(window_id, cancel.clone());
+            self.recording_start_times.insert(window_id, std::time::Instant::now());
             
-            let starting = self.starting_recordings.clone();
+            let events = rec.lock().events().clone();
             
-            // Start in background thread to avoid blocking UI
-            let config = self.config.clone();
+            self.status = match self.capture_rates.lock().get(&window_id) {
+                Some(rate) if *rate < fps as f64 => format!(
+                    "Starting recording for window {}: capture can only sustain ~{:.0} fps; recording will duplicate frames to reach {}",
+                    window_id, rate, fps
+                ),
+                _ => format!("Starting recording for window {} at {} fps...", window_id, fps),
+            };
             
+            // Start in background thread to avoid blocking UI
             std::thread::spawn(move || {
-                match start_ffmpeg_for_window(&ffmpeg, &info, fps, bitrate, output_dir.as_ref(), custom_filename.as_deref(), &config) {
-                    Ok((child, stop_signal, _output_path)) => {
-                        rec.lock().start_recording(window_id, child, stop_signal);
-                        
-                        // Wait a moment to ensure ffmpeg has actually started recording
-                        std::thread::sleep(std::time::Duration::from_millis(500));
-                        
-                        // Remove from starting state
-                        starting.lock().remove(&window_id);
-                        
+                match start_ffmpeg_for_window(&ffmpeg, &info, output_dir.as_ref(), custom_filename.as_deref(), &config, &events, &cancel) {
+                    Ok(handles) => {
+                        rec.lock().start_recording(window_id, info.bounds(), handles);
                         info!("Started recording: {}", info.window_title);
                     }
+                    Err(RecorderError::Canceled) => {}
                     Err(e) => {
-                        starting.lock().remove(&window_id);
+                        // The failure reaches the UI as a `RecordingEvent::Failed`
                         error!("Failed to start ffmpeg for {:?}: {}", info.window_title, e);
                     }
                 }
@@ -950,40 +2389,126 @@ impl AppState {
         }
     }
 
+    /// Abort a recording that is still starting; the start thread cleans up
+    /// at its next check, at the latest once the current encoder probe ends.
+    /// A canceled start sends no event, so it stops counting as starting now.
+    fn cancel_start(&mut self, window_id: u64) {
+        if let Some(cancel) = self.starting_recordings.remove(&window_id) {
+            cancel.store(true, Ordering::Relaxed);
+            self.recording_start_times.remove(&window_id);
+            self.status = format!("Canceled starting recording for window {}", window_id);
+        }
+    }
+
+    /// Resize the native window and its level to match `config.mini_mode`
+    fn sync_mini_mode(&mut self, ctx: &egui::Context) {
+        if self.config.mini_mode {
+            let rows = self.recording_start_times.len();
+            if self.mini_layout == Some(rows) {
+                return;
+            }
+            if self.mini_layout.is_none() {
+                self.normal_window_size = ctx.input(|i| i.viewport().inner_rect).map(|r| r.size());
+                ctx.send_viewport_cmd(egui::ViewportCommand::WindowLevel(egui::WindowLevel::AlwaysOnTop));
+            }
+            let height = MINI_BASE_HEIGHT + MINI_ROW_HEIGHT * rows as f32;
+            ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(egui::vec2(MINI_WIDTH, height)));
+            self.mini_layout = Some(rows);
+        } else if self.mini_layout.take().is_some() {
+            ctx.send_viewport_cmd(egui::ViewportCommand::WindowLevel(egui::WindowLevel::Normal));
+            let size = self.normal_window_size.take().unwrap_or(NORMAL_WINDOW_SIZE);
+            ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(size));
+        }
+    }
+    
+    /// Mini mode: Stop All, a way back, and one line per active recording
+    fn render_mini_mode(&mut self, ctx: &egui::Context) {
+        let mut to_stop: Vec<u64> = Vec::new();
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("⏹ Stop All").clicked() {
+                    self.stop_all();
+                }
+                if ui.button("⤢ Full window").clicked() {
+                    self.config.mini_mode = false;
+                }
+                if self.recording_start_times.is_empty() {
+                    ui.label(egui::RichText::new("Not recording").weak());
+                }
+            });
+            
+            let mut recordings: Vec<(u64, Instant)> = self.recording_start_times
+                .iter()
+                .map(|(id, started)| (*id, *started))
+                .collect();
+            recordings.sort_by_key(|(_, started)| *started);
+            for (window_id, started) in recordings {
+                ui.horizontal(|ui| {
+                    if ui.small_button("⏹").on_hover_text("Stop").clicked() {
+                        to_stop.push(window_id);
+                    }
+                    ui.label(egui::RichText::new(format_elapsed(started.elapsed()))
+                        .color(egui::Color32::GREEN)
+                        .monospace());
+                    let name = self.window_manager.get_window(window_id)
+                        .map_or_else(|| format!("Window {}", window_id), |w| w.display_name());
+                    ui.add(egui::Label::new(name).truncate());
+                });
+            }
+        });
+        
+        for id in to_stop {
+            self.stop_for_window(id);
+        }
+    }
+    
     fn stop_all(&mut self) {
-        let mut rec = self.recorder.lock();
-        let recordings_to_stop = rec.stop_all();
+        let recordings_to_stop = self.recorder.lock().stop_all();
         
         // Clean up all recording start times immediately
-        self.recording_start_times.lock().clear();
-        
-        self.status = "Stopping all recordings...".to_string();
+        self.recording_start_times.clear();
         
         // Stop recordings in background thread to avoid blocking UI
         if !recordings_to_stop.is_empty() {
+            self.expect_stop_all(recordings_to_stop.iter()
+                .map(|recording| (recording.window_id, recording.started_at.elapsed()))
+                .collect());
+            let ffmpeg = self.ffmpeg_path.clone();
             std::thread::spawn(move || {
-                for (mut child, stop_signal) in recordings_to_stop {
-                    stop_signal.store(true, std::sync::atomic::Ordering::Relaxed);
-                    let _ = send_quit_and_wait(&mut child);
+                for recording in recordings_to_stop {
+                    recording.finish(ffmpeg.as_ref());
                 }
                 info!("All recordings stopped");
             });
         }
     }
+    
+    /// Gather the `Stopped` events of recordings stopped together, by window
+    /// id with how long each ran, into the Stop All summary
+    fn expect_stop_all(&mut self, stopped: Vec<(u64, Duration)>) {
+        self.status = "Stopping all recordings...".to_string();
+        let pending = stopped.into_iter()
+            .map(|(window_id, duration)| {
+                let name = self.window_manager.get_window(window_id)
+                    .map_or_else(|| format!("Window {}", window_id), |w| w.display_name());
+                (window_id, (name, duration))
+            })
+            .collect();
+        self.stop_all_summary = Some(StopAllSummary { pending, finished: Vec::new() });
+    }
 
     fn stop_for_window(&mut self, id: u64) {
         let mut rec = self.recorder.lock();
-        if let Some((child, stop_signal)) = rec.stop_recording(id) {
+        if let Some(recording) = rec.stop_recording(id) {
             // Clean up recording start time immediately
-            self.recording_start_times.lock().remove(&id);
+            self.recording_start_times.remove(&id);
             
             self.status = format!("Stopping recording for window {}...", id);
             
             // Stop recording in background thread to avoid blocking UI
+            let ffmpeg = self.ffmpeg_path.clone();
             std::thread::spawn(move || {
-                stop_signal.store(true, std::sync::atomic::Ordering::Relaxed);
-                let mut child = child;
-                let _ = send_quit_and_wait(&mut child);
+                recording.finish(ffmpeg.as_ref());
                 
                 // Wait a bit for ffmpeg to fully finalize the file
                 std::thread::sleep(std::time::Duration::from_millis(500));
@@ -992,83 +2517,1520 @@ impl AppState {
             });
         }
     }
-}
-
-impl eframe::App for AppState {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        // Auto-refresh windows list every 3 seconds
-        if self.window_manager.should_auto_refresh() {
-            self.refresh_windows();
-        }
-        
-        // Request UI refresh frequently when recordings are active for real-time timer updates
-        if !self.recording_start_times.lock().is_empty() {
-            ctx.request_repaint_after(std::time::Duration::from_millis(50));
-        }
-        
-        // Request UI refresh when audio monitoring is active for real-time level updates
-        if self.selected_audio_device.is_some() {
-            ctx.request_repaint_after(std::time::Duration::from_millis(100));
-        }
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            // Top toolbar with global actions
-            ui.horizontal(|ui| {
-                if ui.button("⏹ Stop All").clicked() {
-                    self.stop_all();
+    /// Apply events from recordings: status messages, and failures kept until retried
+    fn handle_recording_events(&mut self) {
+        while let Ok(event) = self.recording_events.try_recv() {
+            match event {
+                RecordingEvent::Started { window_id, path } => {
+                    self.starting_recordings.remove(&window_id);
+                    // Canceled too late to stop it: it runs like any other
+                    self.recording_start_times.entry(window_id).or_insert_with(Instant::now);
+                    self.status = format!("Recording window {} to {}", window_id, path.display());
                 }
-                
-                ui.separator();
-                
-                // Show ffmpeg status as icon
-                if self.ffmpeg_path.is_none() {
-                    ui.colored_label(egui::Color32::RED, "⚠ ffmpeg not found");
+                RecordingEvent::Stopped { window_id, path, files, error, .. } => {
+                    #[cfg(target_os = "macos")]
+                    if let Some(tag) = self.window_settings.get(&window_id)
+                        .and_then(|s| s.finder_tag.as_deref())
+                        .map(str::trim)
+                        .filter(|tag| !tag.is_empty())
+                    {
+                        // Every split file and time segment, not just the last
+                        for file in files.iter().filter(|file| file.exists()) {
+                            if let Err(e) = macos::set_finder_tags(file, &[tag.to_string()]) {
+                                error!("Failed to tag {}: {}", file.display(), e);
+                            }
+                        }
+                    }
+                    #[cfg(not(target_os = "macos"))]
+                    let _ = files;
+                    if let Some(error) = &error {
+                        self.status = format!("⚠ Saved with problems: {}", error);
+                    } else if !self.recording_errors.contains_key(&window_id) {
+                        self.status = format!("Saved {}", path.display());
+                    }
+                    if let Some(summary) = self.stop_all_summary.as_mut() {
+                        summary.record(window_id, &path, error);
+                    }
                 }
-            });
-
-            ui.separator();
-
-            // Tab bar
-            ui.horizontal(|ui| {
-                ui.selectable_value(&mut self.selected_tab, Tab::Windows, "Windows");
-                ui.selectable_value(&mut self.selected_tab, Tab::Settings, "Settings");
-            });
-
-            ui.separator();
-
-            // Tab content
-            match self.selected_tab {
-                Tab::Windows => {
-                    self.render_windows_tab(ui, ctx);
+                RecordingEvent::Failed { window_id, error } => {
+                    self.starting_recordings.remove(&window_id);
+                    self.status = format!("Recording failed for window {}: {}", window_id, error);
+                    self.recording_errors.insert(window_id, (error, Instant::now()));
                 }
-                Tab::Settings => {
-                    self.render_settings_tab(ui);
+                RecordingEvent::EncoderFellBack { window_id, from, to } => {
+                    self.status = format!("{} failed for window {}; using {}", from.label(), window_id, to.label());
                 }
             }
-        });
-        
-        // Footer with status
-        egui::TopBottomPanel::bottom("footer").show(ctx, |ui| {
-            ui.horizontal(|ui| {
-                ui.label(egui::RichText::new(&self.status).small());
-            });
-        });
+        }
     }
-}
 
-fn main() -> anyhow::Result<()> {
-    tracing_subscriber::fmt()
+    /// Tear down recordings that failed (e.g. ffmpeg died), and forget failures
+    /// that expired or whose window closed
+    fn reap_failed_recordings(&mut self) {
+        let window_manager = &self.window_manager;
+        self.recording_errors.retain(|id, (_, at)| {
+            at.elapsed() < FAILURE_SHOWN_FOR && window_manager.get_window(*id).is_some()
+        });
+        if self.last_failure_reap.elapsed() < FAILURE_REAP_INTERVAL {
+            return;
+        }
+        self.last_failure_reap = Instant::now();
+        
+        let failed: Vec<u64> = self.recording_errors.iter()
+            .filter(|(_, (_, at))| at.elapsed() < FAILURE_REAP_WINDOW)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in failed {
+            self.recording_start_times.remove(&id);
+            let recording = self.recorder.lock().stop_recording(id);
+            if let Some(recording) = recording {
+                // Reap the dead ffmpeg and keep whatever was written
+                let ffmpeg = self.ffmpeg_path.clone();
+                std::thread::spawn(move || {
+                    recording.finish(ffmpeg.as_ref());
+                });
+            }
+        }
+    }
+
+    /// Stop recordings once the audio input has been quiet for
+    /// `stop_after_silence_secs`. Each recording's clock starts when it does.
+    fn stop_silent_recordings(&mut self) {
+        let ids = self.recorder.lock().recording_ids();
+        self.silence_timers.retain(|id, _| ids.contains(id));
+        let Some(secs) = self.config.stop_after_silence_secs else {
+            self.silence_timers.clear();
+            return;
+        };
+        let Some(level) = self.input_level() else {
+            return;
+        };
+        
+        let limit = Duration::from_secs(secs as u64);
+        let threshold = self.config.silence_threshold;
+        // Voice-triggered recordings get a minimum length so a short pause can't end them
+        let min_duration = Duration::from_secs(self.config.voice_min_duration_secs as u64);
+        let start_times = self.recording_start_times.clone();
+        let silent: Vec<u64> = ids.into_iter()
+            .filter(|id| self.silence_timers.entry(*id).or_default().update(level, threshold) >= limit)
+            .filter(|id| {
+                !self.voice_armed.contains_key(id)
+                    || start_times.get(id).is_some_and(|started| started.elapsed() >= min_duration)
+            })
+            .collect();
+        for id in silent {
+            info!("Window {} has been silent for {}s, stopping", id, secs);
+            self.stop_for_window(id);
+            self.status = format!("Stopped window {} after {}s of silence", id, secs);
+        }
+    }
+
+    /// Metered level of the recorded audio input. None unless it is being
+    /// monitored: an unmonitored input would read as silent.
+    fn input_level(&self) -> Option<f32> {
+        self.config.audio_input_device.as_ref()
+            .and_then(|id| self.audio_device_manager.get_level_monitor(id))
+            .filter(|monitor| monitor.is_monitoring.load(Ordering::Relaxed))
+            .map(|monitor| monitor.get_level())
+    }
+
+    /// Start recording armed windows when the audio input gets louder than
+    /// `voice_trigger_threshold`, unless they recorded within the cooldown
+    fn start_voice_triggered_recordings(&mut self) {
+        if self.voice_armed.is_empty() {
+            return;
+        }
+        // Forget windows that have closed
+        let window_manager = &self.window_manager;
+        self.voice_armed.retain(|id, _| window_manager.get_window(*id).is_some());
+        
+        let recording = self.recorder.lock().recording_ids();
+        let now = Instant::now();
+        for (id, last_recorded) in self.voice_armed.iter_mut() {
+            if recording.contains(id) || self.starting_recordings.contains_key(id) {
+                *last_recorded = Some(now);
+            }
+        }
+        
+        let Some(level) = self.input_level() else {
+            return;
+        };
+        if level < self.config.voice_trigger_threshold {
+            return;
+        }
+        let cooldown = Duration::from_secs(self.config.voice_cooldown_secs as u64);
+        let triggered: Vec<u64> = self.voice_armed.iter()
+            .filter(|(_, last_recorded)| last_recorded.is_none_or(|t| t.elapsed() >= cooldown))
+            .map(|(id, _)| *id)
+            .filter(|id| !self.recording_errors.contains_key(id))
+            .collect();
+        for id in triggered {
+            info!("Audio level {:.2} triggered recording of window {}", level, id);
+            self.voice_armed.insert(id, Some(now));
+            self.start_for_window(id);
+        }
+    }
+
+    /// A folder dropped onto the app becomes the output directory
+    fn handle_dropped_folders(&mut self, ctx: &egui::Context) {
+        let dropped: Vec<PathBuf> = ctx.input(|i| {
+            i.raw.dropped_files.iter().filter_map(|f| f.path.clone()).collect()
+        });
+        if let Some(path) = dropped.into_iter().next() {
+            if path.is_dir() {
+                self.status = format!("Output directory set to {}", path.display());
+                self.remember_dir(&path);
+                self.config.output_dir = Some(path);
+            } else {
+                self.status = format!("Not a folder: {}", path.display());
+            }
+        }
+        
+        // Hint while a drag is hovering over the window
+        if ctx.input(|i| !i.raw.hovered_files.is_empty()) {
+            let painter = ctx.layer_painter(egui::LayerId::new(
+                egui::Order::Foreground,
+                egui::Id::new("folder_drop_hint"),
+            ));
+            let screen_rect = ctx.screen_rect();
+            painter.rect_filled(screen_rect, 0.0, egui::Color32::from_black_alpha(192));
+            painter.text(
+                screen_rect.center(),
+                egui::Align2::CENTER_CENTER,
+                "📂 Drop a folder to set output directory",
+                egui::TextStyle::Heading.resolve(&ctx.style()),
+                egui::Color32::WHITE,
+            );
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    fn request_permission(&mut self) {
+        let granted = macos::request_screen_capture_access();
+        self.has_permissions = granted;
+        if !granted {
+            self.status = "Permission denied. Enable in System Settings > Privacy & Security > Screen Recording.".to_string();
+        } else {
+            self.status = "Permission granted.".to_string();
+            self.refresh_windows();
+            self.probe_capture();
+        }
+    }
+    
+    /// Pick up a permission granted (or revoked) in System Settings without a relaunch:
+    /// checked every few seconds while missing and whenever the app regains focus
+    fn poll_permission(&mut self, ctx: &egui::Context) {
+        let focused = ctx.input(|i| i.viewport().focused.unwrap_or(true));
+        let regained_focus = focused && !self.window_focused;
+        self.window_focused = focused;
+        
+        if !self.has_permissions {
+            ctx.request_repaint_after(PERMISSION_POLL_INTERVAL);
+        }
+        let poll_due = !self.has_permissions && self.last_permission_check.elapsed() >= PERMISSION_POLL_INTERVAL;
+        if !regained_focus && !poll_due {
+            return;
+        }
+        self.last_permission_check = Instant::now();
+        
+        #[cfg(target_os = "macos")]
+        {
+            let granted = macos::has_screen_capture_access();
+            if granted != self.has_permissions {
+                self.has_permissions = granted;
+                if granted {
+                    self.status = "Screen recording permission granted.".to_string();
+                    self.refresh_windows();
+                } else {
+                    self.status = "Screen recording permission was revoked.".to_string();
+                }
+                self.probe_capture();
+            }
+        }
+        // A revoke isn't reported to a running process, but blanks its
+        // captures; coming back from System Settings is when to look
+        if regained_focus {
+            self.probe_capture();
+        }
+    }
+    
+    /// Look for ffmpeg and the screen recording permission again
+    fn recheck_requirements(&mut self) {
+        self.ffmpeg_path = find_ffmpeg();
+        #[cfg(target_os = "macos")]
+        {
+            self.has_permissions = macos::has_screen_capture_access();
+        }
+        if self.has_permissions {
+            self.refresh_windows();
+        }
+        self.probe_capture();
+    }
+    
+    /// Explain why Start is disabled while window contents can't be captured
+    fn render_permission_banner(&mut self, ui: &mut egui::Ui) {
+        if !self.capture_blocked() {
+            return;
+        }
+        egui::Frame::none()
+            .fill(egui::Color32::from_rgb(120, 30, 30))
+            .inner_margin(8.0)
+            .rounding(4.0)
+            .show(ui, |ui| {
+                ui.set_width(ui.available_width());
+                ui.label(egui::RichText::new("⚠ Windows are listed, but their contents can't be captured").strong().color(egui::Color32::WHITE));
+                let detail = if self.has_permissions {
+                    "macOS is still handing this app empty images. If you changed the screen recording permission, quit and reopen the app."
+                } else {
+                    "Recordings would be black. Allow the app in System Settings > Privacy & Security > Screen Recording."
+                };
+                ui.label(egui::RichText::new(detail).color(egui::Color32::WHITE));
+                ui.horizontal(|ui| {
+                    #[cfg(target_os = "macos")]
+                    if !self.has_permissions && ui.button("🔐 Grant Access").clicked() {
+                        self.request_permission();
+                    }
+                    if ui.button("🔄 Check again").clicked() {
+                        self.recheck_requirements();
+                    }
+                });
+            });
+        ui.add_space(6.0);
+    }
+    
+    /// Offer to stop encoders a crashed earlier run left behind
+    fn render_orphaned_encoders(&mut self, ui: &mut egui::Ui) {
+        if self.orphaned_encoders.is_empty() {
+            return;
+        }
+        let files = self.orphaned_encoders.iter()
+            .map(|e| match &e.output {
+                Some(path) => format!("pid {}: {}", e.pid, path.display()),
+                None => format!("pid {}", e.pid),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        ui.horizontal(|ui| {
+            ui.colored_label(
+                egui::Color32::YELLOW,
+                format!("⚠ {} ffmpeg process(es) still running from a previous session", self.orphaned_encoders.len()),
+            )
+            .on_hover_text(files);
+            if ui.button("Stop them").on_hover_text("Lets ffmpeg finish its files, then exit").clicked() {
+                let stopped = ffmpeg::stop_orphaned_encoders(&self.orphaned_encoders);
+                self.status = format!("Stopped {} leftover ffmpeg process(es)", stopped);
+                self.orphaned_encoders.clear();
+            }
+            if ui.button("Ignore").clicked() {
+                self.orphaned_encoders.clear();
+            }
+        });
+        ui.separator();
+    }
+    
+    /// List what Stop All saved once the last of its recordings is finalized
+    fn render_stop_all_summary(&mut self, ctx: &egui::Context) {
+        let Some(summary) = self.stop_all_summary.as_ref().filter(|s| s.pending.is_empty()) else {
+            return;
+        };
+        let failed = summary.finished.iter().filter(|r| r.error.is_some()).count();
+        let title = if failed == 0 {
+            format!("Saved {} recording(s)", summary.finished.len())
+        } else {
+            format!("Saved {} of {} recordings", summary.finished.len() - failed, summary.finished.len())
+        };
+        let mut open = true;
+        egui::Window::new(title)
+            .collapsible(false)
+            .open(&mut open)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .show(ctx, |ui| {
+                egui::Grid::new("stop_all_summary").striped(true).show(ui, |ui| {
+                    for recording in &summary.finished {
+                        match &recording.error {
+                            None => ui.colored_label(egui::Color32::GREEN, "✓"),
+                            Some(error) => ui.colored_label(egui::Color32::RED, "✗").on_hover_text(error),
+                        };
+                        ui.label(&recording.name);
+                        ui.label(format_elapsed(recording.duration));
+                        ui.label(recording.size.map_or("—".to_string(), |size| format!("{:.1} MB", size as f64 / 1e6)));
+                        ui.vertical(|ui| {
+                            ui.label(egui::RichText::new(recording.path.display().to_string()).small());
+                            if let Some(error) = &recording.error {
+                                ui.colored_label(egui::Color32::RED, egui::RichText::new(error).small());
+                            }
+                        });
+                        ui.end_row();
+                    }
+                });
+            });
+        if !open {
+            self.stop_all_summary = None;
+        }
+    }
+    
+    /// Quick-record palette: type to filter windows, ↑/↓ to choose, Enter to
+    /// start recording the chosen one
+    fn render_quick_record(&mut self, ctx: &egui::Context) {
+        let Some(palette) = self.quick_record.as_mut() else {
+            return;
+        };
+        // Taken before the text field sees them, so they don't move its cursor
+        let (up, down, enter, escape) = ctx.input_mut(|i| (
+            i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp),
+            i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown),
+            i.consume_key(egui::Modifiers::NONE, egui::Key::Enter),
+            i.consume_key(egui::Modifiers::NONE, egui::Key::Escape),
+        ));
+        let recording: HashSet<u64> = self.recorder.lock().recording_ids().into_iter().collect();
+        let windows = self.window_manager.windows();
+        let mut chosen = None;
+        
+        egui::Window::new("Quick record")
+            .title_bar(false)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 60.0))
+            .show(ctx, |ui| {
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut palette.query)
+                        .hint_text("Record window…")
+                        .desired_width(360.0),
+                );
+                response.request_focus();
+                if response.changed() {
+                    palette.selected = 0;
+                }
+                
+                let matches: Vec<&WindowInfo> = window::fuzzy_filter(windows, &palette.query)
+                    .into_iter()
+                    .take(QUICK_RECORD_RESULTS)
+                    .collect();
+                if matches.is_empty() {
+                    ui.label(egui::RichText::new("No matching windows").weak());
+                    return;
+                }
+                if up {
+                    palette.selected = palette.selected.saturating_sub(1);
+                }
+                if down {
+                    palette.selected += 1;
+                }
+                palette.selected = palette.selected.min(matches.len() - 1);
+                
+                for (i, window) in matches.iter().enumerate() {
+                    let label = if recording.contains(&window.window_id) {
+                        format!("⏺ {}", window.display_name())
+                    } else {
+                        window.display_name()
+                    };
+                    if ui.selectable_label(i == palette.selected, label).clicked() {
+                        chosen = Some(window.window_id);
+                    }
+                }
+                if enter {
+                    chosen = Some(matches[palette.selected].window_id);
+                }
+                ui.label(egui::RichText::new("↑↓ choose · Enter record · Esc close").small().weak());
+            });
+        
+        if escape {
+            self.quick_record = None;
+        }
+        if let Some(window_id) = chosen {
+            self.quick_record = None;
+            self.focused_window = Some(window_id);
+            self.start_for_window(window_id);
+        }
+    }
+    
+    /// First-run walkthrough for the two things recording can't work without
+    fn render_onboarding(&mut self, ctx: &egui::Context) {
+        if self.config.first_run_complete || (self.has_permissions && self.ffmpeg_path.is_some()) {
+            return;
+        }
+        
+        egui::Window::new("Welcome to Screen Recorder")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .show(ctx, |ui| {
+                ui.label("Two things are needed before windows can be recorded:");
+                ui.add_space(10.0);
+                
+                // Step 1: screen recording permission
+                ui.horizontal(|ui| {
+                    if self.has_permissions {
+                        ui.colored_label(egui::Color32::GREEN, "✓ 1. Screen recording permission granted");
+                    } else {
+                        ui.colored_label(egui::Color32::RED, "✗ 1. Screen recording permission");
+                        #[cfg(target_os = "macos")]
+                        if ui.button("🔐 Grant Access").clicked() {
+                            self.request_permission();
+                        }
+                    }
+                });
+                if !self.has_permissions {
+                    ui.label(
+                        egui::RichText::new("Allow the app in System Settings > Privacy & Security > Screen Recording. macOS may ask you to reopen the app afterwards.")
+                            .small(),
+                    );
+                }
+                
+                ui.add_space(8.0);
+                
+                // Step 2: ffmpeg
+                if self.ffmpeg_path.is_some() {
+                    ui.colored_label(egui::Color32::GREEN, "✓ 2. ffmpeg found");
+                } else {
+                    ui.colored_label(egui::Color32::RED, "✗ 2. ffmpeg not found");
+                    ui.horizontal(|ui| {
+                        ui.label(egui::RichText::new("Install it with Homebrew:").small());
+                        ui.code("brew install ffmpeg");
+                        if ui.small_button("📋").on_hover_text("Copy command").clicked() {
+                            ui.ctx().copy_text("brew install ffmpeg".to_string());
+                        }
+                    });
+                }
+                
+                ui.add_space(12.0);
+                ui.horizontal(|ui| {
+                    if ui.button("🔄 I've installed it, recheck").clicked() {
+                        self.recheck_requirements();
+                    }
+                    if ui.button("Skip").on_hover_text("Don't show this again").clicked() {
+                        self.config.first_run_complete = true;
+                    }
+                });
+            });
+    }
+    
+    /// Profile switcher: load a saved config, save the current one under a name, or delete one
+    fn render_profile_bar(&mut self, ui: &mut egui::Ui) {
+        ui.label("Profile:");
+        
+        let active = self.active_profile.as_ref()
+            .and_then(|name| self.profiles.iter().find(|p| p.name == *name));
+        let selected_text = match active {
+            Some(profile) if profile.config != self.config => format!("{} (modified)", profile.name),
+            Some(profile) => profile.name.clone(),
+            None => "(none)".to_string(),
+        };
+        
+        let mut selected = None;
+        egui::ComboBox::from_id_salt("profile_select")
+            .selected_text(selected_text)
+            .show_ui(ui, |ui| {
+                if self.profiles.is_empty() {
+                    ui.label(egui::RichText::new("No saved profiles").italics());
+                }
+                for (index, profile) in self.profiles.iter().enumerate() {
+                    let is_active = self.active_profile.as_deref() == Some(profile.name.as_str());
+                    if ui.selectable_label(is_active, &profile.name).clicked() {
+                        selected = Some(index);
+                    }
+                }
+            });
+        if let Some(index) = selected {
+            self.apply_profile(index);
+        }
+        
+        ui.add(
+            egui::TextEdit::singleline(&mut self.profile_name_input)
+                .hint_text("profile name")
+                .desired_width(110.0),
+        );
+        let name = self.profile_name_input.trim().to_string();
+        if ui.add_enabled(!name.is_empty(), egui::Button::new("💾 Save"))
+            .on_hover_text("Save the current settings as a profile")
+            .clicked()
+        {
+            self.save_profile(name);
+        }
+        if ui.add_enabled(self.active_profile.is_some(), egui::Button::new("🗑"))
+            .on_hover_text("Delete the selected profile")
+            .clicked()
+        {
+            self.delete_active_profile();
+        }
+    }
+    
+    /// Make a saved profile the live config
+    fn apply_profile(&mut self, index: usize) {
+        let profile = self.profiles[index].clone();
+        let device = profile.config.audio_input_device.clone();
+        self.config = profile.config;
+        
+        // Switch the audio input only if the profile's device is still connected
+        self.config.audio_input_device = self.selected_audio_device.clone();
+        let connected = device.filter(|id| {
+            self.audio_device_manager.get_devices().iter().any(|d| d.id == *id)
+        });
+        if let Some(device_id) = connected {
+            self.select_audio_device(device_id);
+        }
+        
+        self.refresh_power_state();
+        self.status = format!("Switched to profile \"{}\"", profile.name);
+        self.active_profile = Some(profile.name);
+    }
+    
+    /// Write the config and the overrides of open windows to a file of the user's choice
+    fn export_settings(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("JSON", &["json"])
+            .set_file_name("screencast-settings.json")
+            .save_file()
+        else {
+            return;
+        };
+        let windows = self.window_manager.windows().iter()
+            .filter_map(|w| Some((w.stable_key(), self.window_settings.get(&w.window_id)?.clone())))
+            .collect();
+        let shared = settings::SharedSettings { config: self.config.clone(), windows };
+        self.status = match settings::export_settings(&path, &shared) {
+            Ok(()) => format!("Exported settings to {}", path.display()),
+            Err(e) => format!("{:#}", e),
+        };
+    }
+    
+    /// Load settings exported here or on another machine. Folders and audio
+    /// devices that don't exist on this one keep their current values.
+    fn import_settings(&mut self) {
+        let Some(path) = rfd::FileDialog::new().add_filter("JSON", &["json"]).pick_file() else {
+            return;
+        };
+        let (shared, dropped) = match settings::import_settings::<WindowRecordingSettings>(&path) {
+            Ok(imported) => imported,
+            Err(e) => {
+                self.status = format!("Import failed: {:#}", e);
+                return;
+            }
+        };
+        
+        let mut config = shared.config;
+        if config.output_dir.as_ref().is_some_and(|dir| !dir.is_dir()) {
+            config.output_dir = self.config.output_dir.clone();
+        }
+        let connected = |id: &Option<String>| id.as_ref().filter(|id| {
+            self.audio_device_manager.get_devices().iter().any(|d| d.id == **id)
+        }).cloned();
+        let device = connected(&config.audio_input_device);
+        if config.second_audio_input_device.is_some() && connected(&config.second_audio_input_device).is_none() {
+            config.second_audio_input_device = None;
+        }
+        config.audio_input_device = self.selected_audio_device.clone();
+        self.config = config;
+        self.active_profile = None;
+        if let Some(device_id) = device {
+            self.select_audio_device(device_id);
+        }
+        self.refresh_power_state();
+        
+        // Per-window overrides reach the windows that are open now
+        let mut applied = 0;
+        for window in self.window_manager.windows() {
+            if let Some(settings) = shared.windows.get(&window.stable_key()) {
+                let mut settings = settings.clone();
+                if settings.output_folder.as_ref().is_some_and(|dir| !dir.is_dir()) {
+                    settings.output_folder = None;
+                }
+                self.window_settings.insert(window.window_id, settings);
+                applied += 1;
+            }
+        }
+        
+        self.status = format!(
+            "Imported settings from {} ({} of {} window overrides applied to open windows)",
+            path.display(), applied, shared.windows.len()
+        );
+        if !dropped.is_empty() {
+            self.status += &format!("; reset unrecognized values: {}", dropped.join(", "));
+        }
+    }
+    
+    /// Save the current config under `name`, replacing a profile with the same name
+    fn save_profile(&mut self, name: String) {
+        let profile = RecordingProfile { name: name.clone(), config: self.config.clone() };
+        match self.profiles.iter_mut().find(|p| p.name == name) {
+            Some(existing) => *existing = profile,
+            None => self.profiles.push(profile),
+        }
+        self.profile_name_input.clear();
+        self.persist_profiles(format!("Saved profile \"{}\"", name));
+        self.active_profile = Some(name);
+    }
+    
+    fn delete_active_profile(&mut self) {
+        if let Some(name) = self.active_profile.take() {
+            self.profiles.retain(|p| p.name != name);
+            self.persist_profiles(format!("Deleted profile \"{}\"", name));
+        }
+    }
+    
+    fn persist_profiles(&mut self, done: String) {
+        self.status = match settings::save_profiles(&self.profiles) {
+            Ok(()) => done,
+            Err(e) => format!("Failed to save profiles: {:#}", e),
+        };
+    }
+    
+    /// Re-evaluate low power mode; on battery the preview refreshes less often
+    fn refresh_power_state(&mut self) {
+        self.low_power_active = self.config.low_power_mode.is_active();
+        self.preview_cache.lock().update_interval = if self.low_power_active {
+            Duration::from_millis(3000)
+        } else {
+            Duration::from_millis(1000)
+        };
+    }
+    
+    /// Re-enumerate audio inputs on a background thread, since Core Audio can
+    /// take a while; `collect_audio_devices` applies the result
+    fn refresh_audio_devices(&mut self, ctx: &egui::Context) {
+        if self.audio_enumeration.is_some() {
+            return;
+        }
+        let (tx, rx) = std::sync::mpsc::channel();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let _ = tx.send(audio::list_input_devices());
+            ctx.request_repaint();
+        });
+        self.audio_enumeration = Some(rx);
+    }
+    
+    /// Pick up devices that appeared or went away once a refresh is in. A
+    /// selected device that disconnected falls back to the default.
+    fn collect_audio_devices(&mut self) {
+        let result = match self.audio_enumeration.as_ref().map(|rx| rx.try_recv()) {
+            Some(Ok(result)) => result,
+            Some(Err(std::sync::mpsc::TryRecvError::Disconnected)) => {
+                self.audio_enumeration = None;
+                return;
+            }
+            _ => return,
+        };
+        self.audio_enumeration = None;
+        let previous = self.audio_device_manager.get_devices().to_vec();
+        let devices = match result {
+            Ok(devices) => self.audio_device_manager.set_devices(devices),
+            Err(e) => {
+                error!("Failed to enumerate audio devices: {}", e);
+                return;
+            }
+        };
+        if devices != previous {
+            info!("Audio devices changed: {} found", devices.len());
+        }
+        let fallback = devices.iter()
+            .find(|d| d.is_default)
+            .or_else(|| devices.first());
+        let selected = self.selected_audio_device.as_ref()
+            .and_then(|id| previous.iter().find(|d| d.id == *id));
+        match selected {
+            Some(selected) if !devices.iter().any(|d| d.id == selected.id) => {
+                if let Some(device) = fallback {
+                    self.status = format!("🎤 {} was disconnected; using {}", selected.name, device.name);
+                    self.select_audio_device(device.id.clone());
+                }
+            }
+            Some(_) => {}
+            // Auto-select default device if none selected
+            None if self.selected_audio_device.is_none() => {
+                self.selected_audio_device = fallback.map(|d| d.id.clone());
+            }
+            None => {}
+        }
+    }
+
+    /// The keyboard-focused window, else the one expanded in the list
+    fn selected_window(&self) -> Option<&WindowInfo> {
+        self.focused_window
+            .or_else(|| self.expanded_previews.keys().next().copied())
+            .and_then(|id| self.window_manager.get_window(id))
+    }
+    
+    /// Config a recording of this window runs with
+    fn recording_config_for(&self, info: &window::WindowInfo) -> RecordingConfig {
+        prepare_recording_config(
+            &self.config,
+            info,
+            self.low_power_active,
+            self.window_settings.get(&info.window_id),
+            self.audio_device_manager.get_devices(),
+        )
+    }
+    
+    /// Output folder and custom file name for a window, falling back to its
+    /// app's folder and then the defaults
+    fn output_target(&self, window_id: u64) -> (Option<PathBuf>, Option<String>) {
+        let settings = self.window_settings.get(&window_id);
+        let output_dir = settings
+            .and_then(|s| s.output_folder.clone())
+            .or_else(|| match self.window_manager.get_window(window_id) {
+                Some(info) => self.config.output_dir_for(info).cloned(),
+                None => self.config.output_dir.clone(),
+            });
+        let custom_filename = settings.and_then(|s| s.custom_filename.clone());
+        (output_dir, custom_filename)
+    }
+    
+    /// Record a short clip from the selected input on a background thread and
+    /// play it back, reporting its peak in `audio_check`
+    fn start_audio_check(&mut self, ctx: &egui::Context) {
+        let Some(device_id) = self.selected_audio_device.clone() else {
+            return;
+        };
+        let check = self.audio_check.clone();
+        let ctx = ctx.clone();
+        *check.lock() = AudioCheck::Recording;
+        std::thread::spawn(move || {
+            let result = audio::record_sample(&device_id, AUDIO_CHECK_LENGTH).and_then(|sample| {
+                *check.lock() = AudioCheck::Playing;
+                ctx.request_repaint();
+                let played = audio::play_file(&sample.path);
+                let _ = std::fs::remove_file(&sample.path);
+                played.map(|()| sample.peak)
+            });
+            *check.lock() = match result {
+                Ok(peak) => AudioCheck::Done { peak },
+                Err(e) => AudioCheck::Failed(e.to_string()),
+            };
+            ctx.request_repaint();
+        });
+    }
+    
+    /// Copy the ffmpeg command a recording of this window would run
+    fn copy_ffmpeg_command(&mut self, ctx: &egui::Context, window_id: u64) {
+        let Some(ffmpeg) = self.ffmpeg_path.clone() else {
+            self.status = RecorderError::FfmpegMissing.to_string();
+            return;
+        };
+        let Some(info) = self.window_manager.get_window(window_id).cloned() else {
+            return;
+        };
+        let config = self.recording_config_for(&info);
+        let (output_dir, custom_filename) = self.output_target(window_id);
+        match dry_run_command(&ffmpeg, &info, output_dir.as_ref(), custom_filename.as_deref(), &config) {
+            Ok(command) => {
+                ctx.copy_text(command);
+                self.status = "Copied ffmpeg command to the clipboard".to_string();
+            }
+            Err(e) => self.status = format!("Failed to build ffmpeg command: {}", e),
+        }
+    }
+    
+    /// Save the files of `screenshot::export_debug_frame` next to the window's recordings
+    fn export_debug_frame(&mut self, window_id: u64) {
+        let Some(info) = self.window_manager.get_window(window_id).cloned() else {
+            return;
+        };
+        let config = self.recording_config_for(&info);
+        let (output_dir, _) = self.output_target(window_id);
+        self.status = match screenshot::export_debug_frame(&info, &config, output_dir.as_ref()) {
+            Ok(path) => format!("Saved debug frame to {} (with .rgba and .txt)", path.display()),
+            Err(e) => format!("Frame export failed: {:#}", e),
+        };
+    }
+    
+    /// Capture a window and put the image on the clipboard
+    fn copy_window_image(&mut self, window_id: u64) {
+        #[cfg(target_os = "macos")]
+        {
+            self.status = match macos::capture_window_image(window_id) {
+                Some((buffer, width, height)) => match macos::copy_image_to_clipboard(&buffer, width, height) {
+                    Ok(()) => format!("Copied a {}x{} screenshot of window {}", width, height, window_id),
+                    Err(e) => format!("Failed to copy screenshot: {}", e),
+                },
+                None => format!("Could not capture window {}", window_id),
+            };
+        }
+        #[cfg(not(target_os = "macos"))]
+        {
+            let _ = window_id;
+            self.status = "Screenshots are only supported on macOS".to_string();
+        }
+    }
+    
+    /// Start, restart or stop the control server to match the settings. Not
+    /// while a port is being dragged, to avoid binding every value on the way.
+    fn sync_control_server(&mut self, ctx: &egui::Context) {
+        if self.control.enabled && self.control.token.is_empty() {
+            match control::generate_token() {
+                Ok(token) => self.control.token = token,
+                Err(e) => {
+                    self.control_server = None;
+                    self.control_server_failed = Some((self.control.port, format!("cannot generate a token: {}", e)));
+                    return;
+                }
+            }
+        }
+        let wanted = self.control.enabled
+            .then_some((self.control.port, self.control.token.as_str()));
+        let running = self.control_server.as_ref().map(|s| (s.port(), s.token()));
+        if running == wanted || ctx.input(|i| i.pointer.any_down()) {
+            return;
+        }
+        
+        self.control_server = None; // Frees the port before listening again
+        let Some((port, token)) = wanted else {
+            self.control_server_failed = None;
+            return;
+        };
+        if self.control_server_failed.as_ref().is_some_and(|(failed, _)| *failed == port) {
+            return;
+        }
+        let ctx = ctx.clone();
+        match control::ControlServer::start(port, token, move || ctx.request_repaint()) {
+            Ok(server) => {
+                info!("Control server listening on 127.0.0.1:{}", port);
+                self.control_server = Some(server);
+                self.control_server_failed = None;
+            }
+            Err(e) => {
+                error!("Control server unavailable: {:#}", e);
+                self.control_server_failed = Some((port, format!("{:#}", e)));
+            }
+        }
+    }
+    
+    /// Carry out commands from control server clients
+    fn handle_control_requests(&mut self) {
+        let Some(server) = &self.control_server else {
+            return;
+        };
+        for request in server.requests() {
+            let result = match request.command {
+                control::Command::ListWindows => {
+                    let recording = self.recorder.lock().recording_ids();
+                    let windows: Vec<_> = self.window_manager.windows().iter()
+                        .map(|w| serde_json::json!({
+                            "window_id": w.window_id,
+                            "name": w.display_name(),
+                            "width": w.width,
+                            "height": w.height,
+                            "recording": recording.contains(&w.window_id),
+                        }))
+                        .collect();
+                    Ok(windows.into())
+                }
+                control::Command::Start { window_id } => {
+                    if self.window_manager.get_window(window_id).is_none() {
+                        Err(format!("no window with id {}", window_id))
+                    } else if self.recorder.lock().is_recording(window_id) {
+                        Err(format!("window {} is already being recorded", window_id))
+                    } else if self.ffmpeg_path.is_none() {
+                        Err(RecorderError::FfmpegMissing.to_string())
+                    } else if self.capture_blocked() {
+                        Err(RecorderError::PermissionDenied.to_string())
+                    } else {
+                        self.start_for_window(window_id);
+                        Ok(serde_json::Value::Null)
+                    }
+                }
+                control::Command::Stop { window_id } => {
+                    if self.recorder.lock().is_recording(window_id) {
+                        self.stop_for_window(window_id);
+                        Ok(serde_json::Value::Null)
+                    } else {
+                        Err(format!("window {} isn't being recorded", window_id))
+                    }
+                }
+                control::Command::Status => {
+                    let name = |id: u64| self.window_manager.get_window(id).map(|w| w.display_name());
+                    let recording: Vec<_> = self.recording_start_times.iter()
+                        .map(|(id, start)| serde_json::json!({
+                            "window_id": id,
+                            "name": name(*id),
+                            "elapsed_secs": start.elapsed().as_secs_f64(),
+                        }))
+                        .collect();
+                    let starting: Vec<u64> = self.starting_recordings.keys().copied().collect();
+                    Ok(serde_json::json!({
+                        "recording": recording,
+                        "starting": starting,
+                        "failed": self.recording_errors.iter()
+                            .map(|(id, (error, _))| (id.to_string(), error.clone()))
+                            .collect::<HashMap<_, _>>(),
+                    }))
+                }
+            };
+            request.respond(result);
+        }
+    }
+    
+    /// Follow audio devices being plugged in and out; changes wake the UI
+    fn start_audio_device_listener(&mut self, ctx: &egui::Context) {
+        #[cfg(target_os = "macos")]
+        {
+            let (tx, rx) = std::sync::mpsc::channel();
+            let ctx = ctx.clone();
+            let result = macos::listen_for_audio_device_changes(move || {
+                let _ = tx.send(());
+                ctx.request_repaint();
+            });
+            match result {
+                Ok(()) => self.audio_device_changes = Some(rx),
+                Err(e) => error!("Audio hot-plug notifications unavailable, polling instead: {}", e),
+            }
+        }
+        #[cfg(not(target_os = "macos"))]
+        let _ = ctx;
+    }
+    
+    /// Follow display reconfigurations, which can change the refresh rate the
+    /// settings tab shows; changes wake the UI
+    fn start_display_listener(&mut self, ctx: &egui::Context) {
+        #[cfg(target_os = "macos")]
+        {
+            let (tx, rx) = std::sync::mpsc::channel();
+            let ctx = ctx.clone();
+            let result = macos::listen_for_display_changes(move || {
+                let _ = tx.send(());
+                ctx.request_repaint();
+            });
+            match result {
+                Ok(()) => self.display_changes = Some(rx),
+                Err(e) => error!("Display change notifications unavailable, polling instead: {}", e),
+            }
+        }
+        #[cfg(not(target_os = "macos"))]
+        let _ = ctx;
+    }
+    
+    /// Listen for the screenshot hotkey system-wide; presses wake the UI
+    fn start_hotkey_listener(&mut self, ctx: &egui::Context) {
+        #[cfg(target_os = "macos")]
+        {
+            let (tx, rx) = std::sync::mpsc::channel();
+            let ctx = ctx.clone();
+            let result = macos::spawn_hotkey_listener(self.screenshot_hotkey.clone(), move || {
+                let _ = tx.send(());
+                ctx.request_repaint();
+            });
+            match result {
+                Ok(()) => self.hotkey_presses = Some(rx),
+                Err(e) => {
+                    error!("Screenshot hotkey unavailable: {}", e);
+                    self.hotkey_error = Some(e.to_string());
+                }
+            }
+        }
+        #[cfg(not(target_os = "macos"))]
+        {
+            let _ = ctx;
+            self.hotkey_error = Some("Global hotkeys are only supported on macOS".to_string());
+        }
+    }
+    
+    /// Hotkey action: save a PNG of the frontmost window of another app
+    fn screenshot_frontmost_window(&mut self) {
+        #[cfg(target_os = "macos")]
+        {
+            let Some(info) = macos::frontmost_window() else {
+                self.status = "No window to screenshot".to_string();
+                return;
+            };
+            self.status = match screenshot::save_window_screenshot(
+                &info,
+                self.config.output_dir_for(&info),
+                self.config.folder_template.as_deref(),
+            ) {
+                Ok(path) => {
+                    macos::play_sound("Tink");
+                    format!("📸 Saved {} to {}", info.display_name(), path.display())
+                }
+                Err(e) => format!("Screenshot failed: {:#}", e),
+            };
+        }
+    }
+    
+    /// Keep the measured capture rates of running recordings
+    fn collect_capture_rates(&mut self) {
+        let rec = self.recorder.lock();
+        let mut rates = self.capture_rates.lock();
+        for id in rec.recording_ids() {
+            if let Some(rate) = rec.capture_fps(id) {
+                rates.insert(id, rate);
+            }
+        }
+    }
+    
+    /// Time a few captures of a window in the background
+    fn measure_capture_rate(&mut self, window_id: u64) {
+        #[cfg(target_os = "macos")]
+        {
+            let rates = self.capture_rates.clone();
+            std::thread::spawn(move || {
+                if let Some(rate) = macos::measure_capture_rate(window_id, 10) {
+                    rates.lock().insert(window_id, rate);
+                }
+            });
+        }
+        #[cfg(not(target_os = "macos"))]
+        {
+            let _ = window_id;
+        }
+    }
+    
+    /// Re-read the geometry of recorded windows so anything following them stays in place
+    fn poll_recording_bounds(&mut self) {
+        self.last_bounds_poll = Instant::now();
+        
+        #[cfg(target_os = "macos")]
+        match macos::list_windows() {
+            Ok(windows) => self.recorder.lock().update_bounds(&windows),
+            Err(e) => error!("Failed to poll window bounds: {}", e),
+        }
+    }
+
+    /// Keep a border overlay around each visible recorded window, following its bounds
+    #[cfg(target_os = "macos")]
+    fn sync_border_overlays(&mut self) {
+        let bounds: HashMap<u64, _> = if self.config.show_recording_border {
+            let rec = self.recorder.lock();
+            rec.recording_ids()
+                .into_iter()
+                .filter_map(|id| rec.live_bounds(id).map(|b| (id, b)))
+                .collect()
+        } else {
+            HashMap::new()
+        };
+        self.border_overlays.retain(|id, _| bounds.contains_key(id));
+        
+        for (id, bounds) in bounds {
+            if let Some(overlay) = self.border_overlays.get_mut(&id) {
+                overlay.update(bounds);
+            } else if let Some(overlay) = macos::BorderOverlay::new(bounds) {
+                self.border_overlays.insert(id, overlay);
+            }
+        }
+    }
+
+    /// Keep the menu bar item in step with its setting and the active recordings.
+    /// Its stops go straight to the shared recorder, since the UI may not be
+    /// updating while the window is hidden; a Stop All then reports back for
+    /// its summary like the one in the app.
+    #[cfg(target_os = "macos")]
+    fn sync_status_item(&mut self, ctx: &egui::Context) {
+        if !self.config.show_status_item {
+            self.status_item = None;
+            return;
+        }
+        if self.status_item.is_none() {
+            let recorder = self.recorder.clone();
+            let ffmpeg = Arc::new(Mutex::new(self.ffmpeg_path.clone()));
+            let item_ffmpeg = ffmpeg.clone();
+            let (stops_tx, stops) = std::sync::mpsc::channel();
+            let ctx = ctx.clone();
+            let item = macos::StatusItem::new(move |action| {
+                let recordings = match action {
+                    macos::StatusItemAction::StopAll => recorder.lock().stop_all(),
+                    macos::StatusItemAction::Stop(id) => recorder.lock().stop_recording(id).into_iter().collect(),
+                };
+                // Sent before any recording finishes, so no `Stopped` event beats it
+                if !recordings.is_empty() {
+                    let _ = stops_tx.send((action, recordings.iter()
+                        .map(|recording| (recording.window_id, recording.started_at.elapsed()))
+                        .collect()));
+                }
+                let ffmpeg = item_ffmpeg.lock().clone();
+                std::thread::spawn(move || {
+                    for recording in recordings {
+                        recording.finish(ffmpeg.as_ref());
+                    }
+                });
+                ctx.request_repaint();
+            });
+            self.status_item = item.map(|item| StatusMenu { item, ffmpeg, stops });
+        }
+        
+        let mut recordings: Vec<(u64, Instant)> = self.recording_start_times
+            .iter()
+            .map(|(id, started)| (*id, *started))
+            .collect();
+        recordings.sort_by_key(|(_, started)| *started);
+        let listed: Vec<(u64, String)> = recordings.into_iter()
+            .map(|(id, _)| {
+                let name = self.window_manager.get_window(id)
+                    .map_or_else(|| format!("Window {}", id), |w| w.display_name());
+                (id, name)
+            })
+            .collect();
+        if let Some(menu) = self.status_item.as_mut() {
+            *menu.ffmpeg.lock() = self.ffmpeg_path.clone();
+            menu.item.update(&listed);
+        }
+    }
+    
+    /// Take recordings stopped from the menu bar item off the list, with a
+    /// summary for a Stop All. Runs before recording events are handled,
+    /// which complete the summary.
+    #[cfg(target_os = "macos")]
+    fn collect_status_item_stops(&mut self) {
+        let stops: Vec<_> = self.status_item.as_ref()
+            .map(|menu| menu.stops.try_iter().collect())
+            .unwrap_or_default();
+        for (action, recordings) in stops {
+            for (window_id, _) in &recordings {
+                self.recording_start_times.remove(window_id);
+            }
+            if action == macos::StatusItemAction::StopAll {
+                self.expect_stop_all(recordings);
+            }
+        }
+    }
+
+    fn add_marker(&mut self, window_id: u64) {
+        let marker = self.recorder.lock().add_marker(window_id);
+        if let Some((index, at)) = marker {
+            self.status = format!("Marker {} at {} for window {}", index, format_elapsed(at), window_id);
+        }
+    }
+}
+
+impl eframe::App for AppState {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        #[cfg(target_os = "macos")]
+        self.collect_status_item_stops();
+        self.handle_recording_events();
+        self.reap_failed_recordings();
+        self.stop_silent_recordings();
+        self.start_voice_triggered_recordings();
+        self.sync_audio_monitoring();
+        
+        // Screenshot hotkey: the event tap needs Input Monitoring, so it's
+        // only installed once a hotkey is set. Follow setting changes, then
+        // act on presses.
+        if self.config.screenshot_hotkey.is_some() && self.hotkey_presses.is_none() && self.hotkey_error.is_none() {
+            self.start_hotkey_listener(ctx);
+        }
+        *self.screenshot_hotkey.lock() = self.config.screenshot_hotkey;
+        let hotkey_pressed = self.hotkey_presses.as_ref().is_some_and(|rx| rx.try_iter().count() > 0);
+        if hotkey_pressed {
+            self.screenshot_frontmost_window();
+        }
+        self.collect_capture_rates();
+        self.sync_control_server(ctx);
+        self.handle_control_requests();
+        self.poll_permission(ctx);
+        self.collect_capture_probe(ctx);
+        self.handle_dropped_folders(ctx);
+        
+        // Persist settings once they change (not mid-drag)
+        if self.config != self.saved_config && !ctx.input(|i| i.pointer.any_down()) {
+            if let Err(e) = settings::save_config(&self.config) {
+                error!("Failed to save settings: {}", e);
+            }
+            self.saved_config = self.config.clone();
+        }
+        if self.control != self.saved_control && !ctx.input(|i| i.pointer.any_down()) {
+            if let Err(e) = settings::save_control_settings(&self.control) {
+                error!("Failed to save control settings: {}", e);
+            }
+            self.saved_control = self.control.clone();
+        }
+        
+        // Auto-refresh windows list and power state every 3 seconds, and audio
+        // devices and the display refresh rate too unless Core Audio and Core
+        // Graphics tell us when they change. A refresh that comes due
+        // mid-interaction waits, and runs once when it ends.
+        if self.window_manager.should_auto_refresh() && !is_interacting(ctx) {
+            self.refresh_windows();
+            if self.audio_device_changes.is_none() {
+                self.refresh_audio_devices(ctx);
+            }
+            if self.display_changes.is_none() {
+                self.main_display_refresh = None;
+            }
+            self.refresh_power_state();
+        }
+        if self.audio_device_changes.as_ref().is_some_and(|rx| rx.try_iter().count() > 0) {
+            self.refresh_audio_devices(ctx);
+        }
+        if self.display_changes.as_ref().is_some_and(|rx| rx.try_iter().count() > 0) {
+            self.main_display_refresh = None;
+        }
+        self.collect_audio_devices();
+        
+        // Request UI refresh frequently when recordings are active for real-time timer updates
+        if !self.recording_start_times.is_empty() {
+            ctx.request_repaint_after(std::time::Duration::from_millis(50));
+        }
+        
+        // Follow recorded windows as they move, resize or get minimized
+        let recording = !self.recorder.lock().recording_ids().is_empty();
+        if recording && self.last_bounds_poll.elapsed() >= BOUNDS_POLL_INTERVAL {
+            self.poll_recording_bounds();
+        }
+        
+        #[cfg(target_os = "macos")]
+        self.sync_border_overlays();
+        #[cfg(target_os = "macos")]
+        self.sync_status_item(ctx);
+        #[cfg(target_os = "macos")]
+        {
+            // Windows opened since (dialogs, extra viewports) need it too
+            let applied = Some((self.config.hide_from_capture, macos::app_window_count()));
+            if self.hidden_from_capture != applied {
+                macos::set_app_windows_capturable(!self.config.hide_from_capture);
+                self.hidden_from_capture = applied;
+            }
+        }
+        
+        // "M" drops a chapter marker on every active recording
+        if !ctx.wants_keyboard_input() && ctx.input(|i| i.key_pressed(egui::Key::M)) {
+            let ids = self.recorder.lock().recording_ids();
+            for id in ids {
+                self.add_marker(id);
+            }
+        }
+        
+        // Keep polling for the events of recordings Stop All is finalizing
+        if self.stop_all_summary.as_ref().is_some_and(|s| !s.pending.is_empty()) {
+            ctx.request_repaint_after(std::time::Duration::from_millis(250));
+        }
+        
+        // Request UI refresh when audio monitoring is active for real-time level updates
+        if self.selected_audio_device.is_some() {
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+        }
+
+        // ⌘K toggles the quick-record palette
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::K)) {
+            self.quick_record = match self.quick_record {
+                Some(_) => None,
+                None => Some(QuickRecordPalette::default()),
+            };
+        }
+        
+        self.render_onboarding(ctx);
+        self.render_stop_all_summary(ctx);
+        self.render_quick_record(ctx);
+        
+        // Everything needed is in place: don't walk through it again
+        if !self.config.first_run_complete && self.has_permissions && self.ffmpeg_path.is_some() {
+            self.config.first_run_complete = true;
+        }
+        
+        self.sync_mini_mode(ctx);
+        if self.config.mini_mode {
+            self.render_mini_mode(ctx);
+            return;
+        }
+        
+        egui::CentralPanel::default().show(ctx, |ui| {
+            // Top toolbar with global actions
+            ui.horizontal(|ui| {
+                if ui.button("⏹ Stop All").clicked() {
+                    self.stop_all();
+                }
+                
+                ui.separator();
+                
+                // Show ffmpeg status as icon
+                if self.ffmpeg_path.is_none() {
+                    ui.colored_label(egui::Color32::RED, "⚠ ffmpeg not found");
+                }
+                
+                if self.low_power_active {
+                    ui.colored_label(egui::Color32::YELLOW, "🔋 Low power")
+                        .on_hover_text("Slower previews, fewer captures of static windows, hardware encoding");
+                } else {
+                    ui.label(egui::RichText::new("🔌 Full power").weak());
+                }
+                
+                ui.separator();
+                
+                self.render_profile_bar(ui);
+                
+                ui.separator();
+                
+                if ui.button("🗕 Mini").on_hover_text("Shrink to a small always-on-top strip").clicked() {
+                    self.config.mini_mode = true;
+                }
+            });
+
+            ui.separator();
+
+            self.render_orphaned_encoders(ui);
+
+            // Tab bar
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut self.selected_tab, Tab::Windows, "Windows");
+                ui.selectable_value(&mut self.selected_tab, Tab::Settings, "Settings");
+            });
+
+            ui.separator();
+
+            // Tab content
+            match self.selected_tab {
+                Tab::Windows => {
+                    self.render_windows_tab(ui, ctx);
+                }
+                Tab::Settings => {
+                    self.render_settings_tab(ui);
+                }
+            }
+        });
+        
+        // Footer with status
+        egui::TopBottomPanel::bottom("footer").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(egui::RichText::new(&self.status).small());
+            });
+        });
+    }
+}
+
+/// Record individual windows to video files
+#[derive(Parser)]
+#[command(version, about)]
+#[command(group(ArgGroup::new("target").args(["window", "window_title"])))]
+struct Cli {
+    /// Print the ffmpeg command that would record the window, then exit
+    #[arg(long, requires = "target")]
+    print_command: bool,
+
+    /// Window id, as listed in the app or by --list-windows. Ids change
+    /// between launches, so scripts should prefer --window-title
+    #[arg(long)]
+    window: Option<u64>,
+
+    /// Pick the window whose "App — Title" name contains this text, ignoring case
+    #[arg(long)]
+    window_title: Option<String>,
+
+    /// With --window-title, use every matching window instead of failing
+    /// when there is more than one
+    #[arg(long, requires = "window_title")]
+    all_matches: bool,
+
+    /// Print the recordable windows with their ids, then exit
+    #[arg(long)]
+    list_windows: bool,
+
+    /// Time each encoder on synthetic 1080p frames, then exit
+    #[arg(long, hide = true)]
+    benchmark: bool,
+}
+
+/// Fail with an explanation when there is no graphical session to open the
+/// app's window in or capture windows from, e.g. over SSH
+fn require_display() -> anyhow::Result<()> {
+    #[cfg(target_os = "macos")]
+    if !macos::has_display_session() {
+        anyhow::bail!(
+            "no display available. Screen Recorder needs a logged-in desktop session with an active display, \
+             so it can't show its window or capture windows over SSH or headless. --benchmark works without one."
+        );
+    }
+    Ok(())
+}
+
+/// `--list-windows`: one window per line, id first
+fn print_windows() -> anyhow::Result<()> {
+    for info in screencast::list_windows()? {
+        println!("{:>8}  {:>9}  {}", info.window_id, info.dimensions_str(), info.display_name());
+    }
+    Ok(())
+}
+
+/// The windows `--window` or `--window-title` names, resolved against the
+/// current window list
+fn target_windows(cli: &Cli) -> anyhow::Result<Vec<WindowInfo>> {
+    let windows = screencast::list_windows()?;
+    if let Some(window_id) = cli.window {
+        let info = windows.into_iter()
+            .find(|w| w.window_id == window_id)
+            .ok_or(RecorderError::WindowGone(window_id))?;
+        return Ok(vec![info]);
+    }
+    let pattern = cli.window_title.as_deref().expect("clap enforces --window or --window-title");
+    let matches = window::title_matches(&windows, pattern);
+    match matches.len() {
+        0 => anyhow::bail!("no window matches \"{}\". --list-windows shows what's open", pattern),
+        1 => {}
+        _ if cli.all_matches => {}
+        _ => {
+            let names: Vec<String> = matches.iter()
+                .map(|w| format!("  {:>8}  {}", w.window_id, w.display_name()))
+                .collect();
+            anyhow::bail!(
+                "{} windows match \"{}\":\n{}\nNarrow the pattern, pass --window with an id, or add --all-matches",
+                matches.len(), pattern, names.join("\n")
+            );
+        }
+    }
+    Ok(matches.into_iter().cloned().collect())
+}
+
+/// `--print-command`: resolve the windows and saved settings like the app
+/// does, one command per window
+fn print_command(cli: &Cli) -> anyhow::Result<()> {
+    let ffmpeg = find_ffmpeg().ok_or(RecorderError::FfmpegMissing)?;
+    let saved = settings::load_config().unwrap_or_default();
+    let low_power = saved.low_power_mode.is_active();
+    for info in target_windows(cli)? {
+        // Per-window overrides only live in the running app
+        let config = prepare_recording_config(&saved, &info, low_power, None, &[]);
+        println!("{}", dry_run_command(&ffmpeg, &info, config.output_dir.as_ref(), None, &config)?);
+    }
+    Ok(())
+}
+
+/// `--benchmark`: encoder throughput and output size at 1080p, as a table
+fn benchmark_encoders() -> anyhow::Result<()> {
+    const WIDTH: usize = 1920;
+    const HEIGHT: usize = 1080;
+    const FRAMES: usize = 300; // 10 s at 30 fps
+    let ffmpeg = find_ffmpeg().ok_or(RecorderError::FfmpegMissing)?;
+    println!("{} frames of {}x{} at 6000 kbps\n", FRAMES, WIDTH, HEIGHT);
+    println!("{:<32} {:>8} {:>10}", "Encoder", "fps", "size");
+    for encoder in ENCODERS {
+        match ffmpeg::benchmark_encoder(&ffmpeg, encoder, WIDTH, HEIGHT, FRAMES) {
+            Ok(result) => println!(
+                "{:<32} {:>8.1} {:>7.1} MB",
+                encoder.label(), result.fps, result.size_bytes as f64 / 1e6
+            ),
+            Err(e) => println!("{:<32} failed: {:#}", encoder.label(), e),
+        }
+    }
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    tracing_subscriber::fmt()
         .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
         .with_target(false)
         .without_time()
         .init();
 
+    let cli = Cli::parse();
+    if cli.benchmark {
+        return benchmark_encoders();
+    }
+    // Everything else lists, captures or shows windows
+    require_display()?;
+    if cli.print_command {
+        return print_command(&cli);
+    }
+    if cli.list_windows {
+        return print_windows();
+    }
+
     let native_options = eframe::NativeOptions::default();
-    let app = AppState::default();
+    let mut app = AppState::default();
     let res = eframe::run_native(
         "Screen Recorder",
         native_options,
-        Box::new(|_cc| Ok(Box::new(app))),
+        Box::new(move |cc| {
+            app.start_audio_device_listener(&cc.egui_ctx);
+            app.start_display_listener(&cc.egui_ctx);
+            Ok(Box::new(app))
+        }),
     );
     match res {
         Ok(_) => Ok(()),
@@ -1,19 +1,202 @@
 use std::collections::HashMap;
 use std::path::PathBuf;
-use std::process::Child;
 use std::sync::Arc;
 use std::sync::atomic::AtomicBool;
+use std::time::{Duration, Instant};
 
-use crate::ffmpeg::VideoEncoder;
+use anyhow::Context;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
 
-/// Configuration for recording
-#[derive(Clone)]
+use crate::events::{EventBus, RecordingEvent};
+use crate::hotkey::Hotkey;
+use crate::ffmpeg::{file_markers, send_quit_and_wait, verify_output, write_chapters, AudioBackend, AudioCodec, CaptureHealth, Canvas, CaptureStats, Container, EvenSize, CropRegion, Mp4Layout, LatestFrame, OutputClaim, OutputLog, Rotation, OutputSegment, PixelFormat, ProResProfile, RateControl, RecordingHandles, StreamEnd, VideoEncoder, X264Preset, scaled_size};
+use crate::window::{WindowBounds, WindowInfo};
+
+#[cfg(target_os = "macos")]
+use crate::macos;
+
+/// When to trade capture quality for battery life
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum LowPowerMode {
+    Auto, // Only while on battery
+    On,
+    Off,
+}
+
+impl LowPowerMode {
+    pub fn label(&self) -> &'static str {
+        match self {
+            LowPowerMode::Auto => "Automatic (on battery)",
+            LowPowerMode::On => "Always",
+            LowPowerMode::Off => "Never",
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        match self {
+            LowPowerMode::On => true,
+            LowPowerMode::Off => false,
+            #[cfg(target_os = "macos")]
+            LowPowerMode::Auto => macos::on_battery_power(),
+            #[cfg(not(target_os = "macos"))]
+            LowPowerMode::Auto => false,
+        }
+    }
+}
+
+/// When to keep the audio input open to meter its level. Metering opens the
+/// microphone, which lights the system's privacy indicator.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AudioMonitoring {
+    WhenNeeded, // A level meter is on screen, or auto start/stop is watching the level
+    Always,
+}
+
+impl AudioMonitoring {
+    pub fn label(&self) -> &'static str {
+        match self {
+            AudioMonitoring::WhenNeeded => "Only while needed",
+            AudioMonitoring::Always => "Always",
+        }
+    }
+}
+
+/// Bundles of fps, bitrate and encoder for people who don't want to pick numbers
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum QualityPreset {
+    Low,
+    Medium,
+    High,
+    Max,
+}
+
+impl QualityPreset {
+    pub fn label(&self) -> &'static str {
+        match self {
+            QualityPreset::Low => "Low",
+            QualityPreset::Medium => "Medium",
+            QualityPreset::High => "High",
+            QualityPreset::Max => "Max",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            QualityPreset::Low => "15 fps, 2 Mbps, hardware encoder. Small files for slides and docs.",
+            QualityPreset::Medium => "30 fps, 6 Mbps, software encoder. Good for most screen recordings.",
+            QualityPreset::High => "60 fps, 12 Mbps, hardware encoder. Smooth scrolling and motion.",
+            QualityPreset::Max => "60 fps, 30 Mbps, hardware encoder. Large files for editing.",
+        }
+    }
+
+    /// (fps, bitrate in kbps, encoder)
+    fn settings(&self) -> (i32, i32, VideoEncoder) {
+        match self {
+            QualityPreset::Low => (15, 2000, VideoEncoder::H264VideoToolbox),
+            QualityPreset::Medium => (30, 6000, VideoEncoder::Libx264),
+            QualityPreset::High => (60, 12000, VideoEncoder::H264VideoToolbox),
+            QualityPreset::Max => (60, 30000, VideoEncoder::H264VideoToolbox),
+        }
+    }
+
+    pub fn apply(&self, config: &mut RecordingConfig) {
+        let (fps, bitrate_kbps, encoder) = self.settings();
+        config.fps = fps;
+        config.match_display_refresh = false;
+        config.rate_control = RateControl::Bitrate(bitrate_kbps);
+        config.encoder = encoder;
+        config.quality_preset = Some(*self);
+    }
+
+    /// Whether the config still has exactly this preset's values
+    pub fn matches(&self, config: &RecordingConfig) -> bool {
+        let (fps, bitrate_kbps, encoder) = self.settings();
+        config.fps == fps
+            && !config.match_display_refresh
+            && config.rate_control == RateControl::Bitrate(bitrate_kbps)
+            && config.encoder == encoder
+    }
+}
+
+/// Sends recordings of one app to a folder of its own
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AppFolder {
+    pub app: String, // Owner name or bundle identifier, matched ignoring case
+    pub folder: PathBuf,
+}
+
+impl AppFolder {
+    pub fn matches(&self, info: &WindowInfo) -> bool {
+        let app = self.app.trim();
+        !app.is_empty()
+            && (info.owner_name.eq_ignore_ascii_case(app)
+                || info.bundle_id.as_deref().is_some_and(|id| id.eq_ignore_ascii_case(app)))
+    }
+}
+
+/// Configuration for recording, persisted between launches
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct RecordingConfig {
+    pub quality_preset: Option<QualityPreset>, // None once fps/bitrate/encoder are edited by hand
     pub fps: i32,
-    pub bitrate_kbps: i32,
+    pub match_display_refresh: bool, // Use the owning display's refresh rate instead of `fps`
+    pub capture_fps: Option<i32>, // Capture and pipe frames at this rate, repeated up to `fps`; None captures at `fps`
+    pub rate_control: RateControl, // Bitrate in kbps, or CRF (0-51, lower is better; mapped onto -q:v for VideoToolbox)
+    #[serde(rename = "bitrate_kbps", skip_serializing)]
+    pub legacy_bitrate_kbps: Option<i32>, // Saved before `rate_control`; moved into it by `migrate_saved_fields`
     pub output_dir: Option<PathBuf>,
+    pub app_folders: Vec<AppFolder>, // Per-app replacements for `output_dir`; the first match wins
+    pub folder_template: Option<String>, // Subfolders under `output_dir`, e.g. "{year}/{month}-{day}"
     pub encoder: VideoEncoder,
+    pub encoder_fallback: bool, // Try other encoders when `encoder` fails to start, instead of failing
+    pub pixel_format: PixelFormat, // Encoders that can't take it use yuv420p
+    pub prores_profile: ProResProfile, // Only used by the ProRes encoder
+    pub x264_preset: X264Preset, // Only used by libx264
+    pub container: Option<Container>, // Forced container; None picks one that fits the audio codec
+    pub mp4_layout: Mp4Layout,
+    pub verify_recordings: bool, // Check each finished file with ffprobe and flag it if it won't play
     pub audio_input_device: Option<String>, // Audio input device ID
+    pub second_audio_input_device: Option<String>, // Recorded as a separate second track
+    pub audio_backend: AudioBackend, // How the primary device reaches ffmpeg
+    pub audio_monitoring: AudioMonitoring,
+    pub pause_monitoring_unfocused: bool, // Release the microphone while another app is in front
+    pub audio_codec: AudioCodec,
+    pub audio_bitrate_kbps: i32, // Ignored by lossless codecs
+    pub audio_highpass_hz: Option<u32>, // None disables the highpass filter
+    pub audio_lowpass_hz: Option<u32>, // None disables the lowpass filter
+    pub stream_end: StreamEnd, // Whether the video or the shorter stream sets the length of recordings with audio
+    pub stop_after_silence_secs: Option<u32>, // Stop once the audio input stays quiet this long
+    pub silence_threshold: f32, // Input level (0.0 to 1.0, as metered) counted as silence
+    pub voice_trigger_threshold: f32, // Input level that starts recording armed windows
+    pub voice_cooldown_secs: u32, // Quiet period after a recording before an armed window retriggers
+    pub voice_min_duration_secs: u32, // Armed windows' recordings aren't stopped for silence before this
+    pub output_width: Option<u32>, // Scale recordings to this width; None keeps the window's
+    pub output_height: Option<u32>, // Scale recordings to this height; None keeps the window's
+    pub lock_aspect_ratio: bool, // Derive the missing side from the window, or fit inside both
+    pub rotation: Rotation, // Applied before scaling; output sizes refer to the rotated frame
+    pub crop: Option<CropRegion>, // Applied to captures, before rotation; set per window
+    pub canvas: Option<Canvas>, // Letterbox every frame into this size instead of following the window
+    pub even_size: EvenSize, // How odd window or canvas sizes become the even sizes yuv420p needs
+    pub max_capture_dimension: Option<u32>, // Larger outputs are scaled down to fit, keeping the aspect ratio
+    pub split_size_mb: Option<u64>, // Start a new file when the estimated size reaches this
+    pub segment_seconds: Option<u32>, // Cut files every this many seconds as name_000, name_001, …; replaces `split_size_mb`
+    pub stderr_log_lines: usize, // ffmpeg output lines kept per recording for display
+    pub debug_tools: bool, // Show actions that export what the pipeline sees, for bug reports
+    pub show_recording_border: bool, // Draw a border around windows being recorded
+    pub show_status_item: bool, // Menu bar item showing recording state, with stop actions
+    pub hide_from_capture: bool, // Keep this app's own windows out of screen captures
+    pub low_power_mode: LowPowerMode,
+    pub first_run_complete: bool, // Onboarding dismissed
+    pub mini_mode: bool, // Compact always-on-top control strip instead of the full UI
+    pub screenshot_hotkey: Option<Hotkey>, // Global shortcut to screenshot the frontmost window
+    pub tag_source_app: bool, // Write the recorded app's name and bundle id into the file's metadata
+    #[serde(skip)]
+    pub source_tags: Vec<(String, String)>, // Container tags for the recorded app; set per recording by `with_source_tags`
+    #[serde(skip)]
+    pub skip_static_frames: bool, // Capture less often while the window isn't changing
 }
 
 impl RecordingConfig {
@@ -26,39 +209,454 @@ impl RecordingConfig {
         let default_dir = std::env::current_dir().ok();
         
         Self {
+            quality_preset: Some(QualityPreset::Medium), // Matches the defaults below
             fps: 30,
-            bitrate_kbps: 6000,
+            match_display_refresh: false,
+            capture_fps: None,
+            rate_control: RateControl::Bitrate(RateControl::DEFAULT_BITRATE_KBPS),
+            legacy_bitrate_kbps: None,
             output_dir: default_dir,
+            app_folders: Vec::new(),
+            folder_template: None, // Write straight into `output_dir`
             encoder: VideoEncoder::Libx264, // Default to software encoder for reliability
+            encoder_fallback: true,
+            pixel_format: PixelFormat::Yuv420p,
+            prores_profile: ProResProfile::Standard,
+            x264_preset: X264Preset::Veryfast,
+            container: None,
+            mp4_layout: Mp4Layout::Faststart,
+            verify_recordings: true,
             audio_input_device,
+            second_audio_input_device: None,
+            audio_backend: AudioBackend::Avfoundation,
+            audio_monitoring: AudioMonitoring::WhenNeeded,
+            pause_monitoring_unfocused: true,
+            audio_codec: AudioCodec::Aac,
+            audio_bitrate_kbps: 192,
+            audio_highpass_hz: Some(60),
+            audio_lowpass_hz: Some(18000),
+            stream_end: StreamEnd::Video,
+            stop_after_silence_secs: None,
+            silence_threshold: 0.05,
+            voice_trigger_threshold: 0.2,
+            voice_cooldown_secs: 10,
+            voice_min_duration_secs: 5,
+            output_width: None,
+            output_height: None,
+            lock_aspect_ratio: true,
+            rotation: Rotation::None,
+            crop: None,
+            canvas: None,
+            even_size: EvenSize::Crop,
+            max_capture_dimension: Some(4096), // H.264 VideoToolbox sessions fail above this
+            split_size_mb: None, // Splitting is off by default
+            segment_seconds: None,
+            stderr_log_lines: 200,
+            debug_tools: false,
+            show_recording_border: true,
+            show_status_item: false,
+            hide_from_capture: false,
+            low_power_mode: LowPowerMode::Auto,
+            first_run_complete: false,
+            mini_mode: false,
+            screenshot_hotkey: None, // The event tap prompts for Input Monitoring
+            tag_source_app: false,
+            source_tags: Vec::new(),
+            skip_static_frames: false, // Set per recording by `apply_low_power`
+        }
+    }
+
+    /// Folder a recording of `info` goes to unless the window has its own:
+    /// its app's folder, or else `output_dir`
+    pub fn output_dir_for(&self, info: &WindowInfo) -> Option<&PathBuf> {
+        self.app_folders.iter()
+            .find(|rule| rule.matches(info))
+            .map(|rule| &rule.folder)
+            .or(self.output_dir.as_ref())
+    }
+
+    /// Video bitrate to plan with, e.g. for file size estimates: the set one,
+    /// or the default when encoding at constant quality
+    pub fn bitrate_kbps(&self) -> i32 {
+        self.rate_control.bitrate_kbps().unwrap_or(RateControl::DEFAULT_BITRATE_KBPS)
+    }
+
+    /// File extension of the container recordings are written to
+    pub fn container_extension(&self) -> &'static str {
+        let automatic = if let Some(container) = self.encoder.required_container() {
+            container.extension()
+        } else if self.audio_input_device.is_some() {
+            self.audio_codec.container()
+        } else {
+            "mp4"
+        };
+        match self.container {
+            // MP4 can't hold every audio or video codec, so it only ever means "automatic"
+            None | Some(Container::Mp4) => automatic,
+            Some(container) => container.extension(),
+        }
+    }
+
+    /// Whether recordings are scaled rather than encoded at the window's size
+    pub fn scales_output(&self) -> bool {
+        self.output_width.is_some() || self.output_height.is_some()
+    }
+
+    /// Output size for a window captured at `width`x`height`, before rounding
+    /// to even; `None` encodes at the captured size. A canvas is already the
+    /// final size, so neither the output size nor the limit apply to it.
+    pub fn output_size_for(&self, width: usize, height: usize) -> Option<(usize, usize)> {
+        if self.canvas.is_some() {
+            return None;
+        }
+        let mut size = self.scales_output().then(|| {
+            scaled_size(
+                width,
+                height,
+                self.output_width.map(|w| w as usize),
+                self.output_height.map(|h| h as usize),
+                self.lock_aspect_ratio,
+            )
+        });
+        if let Some(max) = self.max_capture_dimension.map(|m| m as usize) {
+            let (w, h) = size.unwrap_or((width, height));
+            if w.max(h) > max {
+                size = Some(scaled_size(w, h, Some(max), Some(max), true));
+            }
+        }
+        size
+    }
+
+    /// Conservative settings for running on battery: hardware encoding and
+    /// fewer captures while a window is static
+    pub fn apply_low_power(&mut self) {
+        if self.encoder == VideoEncoder::Libx264 {
+            self.encoder = VideoEncoder::H264VideoToolbox;
         }
+        self.skip_static_frames = true;
+    }
+
+    /// This config with `source_tags` naming the app that owns `info`, if
+    /// `tag_source_app` is on
+    pub fn with_source_tags(&self, info: &WindowInfo) -> Self {
+        let mut config = self.clone();
+        config.source_tags = if self.tag_source_app {
+            std::iter::once(("app".to_string(), info.owner_name.clone()))
+                .chain(info.bundle_id.clone().map(|id| ("bundle_id".to_string(), id)))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        config
+    }
+
+    /// Frames per second captured and piped to ffmpeg: `capture_fps` when
+    /// it's below `fps`. Fewer captures and a smaller pipe save CPU, at the
+    /// cost of motion updating less often than the file's frame rate.
+    pub fn capture_rate(&self) -> i32 {
+        let fps = self.fps.max(1);
+        self.capture_fps.map_or(fps, |rate| rate.clamp(1, fps))
+    }
+
+    /// Bring a config saved by an older version up to date: audio device ids
+    /// saved as CPAL indices become device names (see
+    /// `audio::migrate_device_id`), and a plain `bitrate_kbps` becomes
+    /// `rate_control`
+    pub fn migrate_saved_fields(&mut self) {
+        if let Some(kbps) = self.legacy_bitrate_kbps.take() {
+            self.rate_control = RateControl::Bitrate(kbps);
+        }
+        for id in [&mut self.audio_input_device, &mut self.second_audio_input_device].into_iter().flatten() {
+            *id = crate::audio::migrate_device_id(id);
+        }
+    }
+
+    /// Recordings with audio that end with the video: ffmpeg has to be
+    /// interrupted to stop reading the audio input
+    pub fn audio_outlives_video(&self) -> bool {
+        self.audio_input_device.is_some() && self.stream_end == StreamEnd::Video
+    }
+
+    /// Capture fps for a window: the refresh rate of the display it is on when
+    /// `match_display_refresh` is set, otherwise the configured fps
+    pub fn resolve_fps(&self, info: &WindowInfo) -> i32 {
+        if self.match_display_refresh {
+            #[cfg(target_os = "macos")]
+            {
+                return macos::display_refresh_rate(info);
+            }
+            #[cfg(not(target_os = "macos"))]
+            {
+                let _ = info;
+            }
+        }
+        self.fps.max(1)
+    }
+}
+
+impl Default for RecordingConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// How long stopping waits for a recording's capture loop to exit
+const EMITTER_STOP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A recording in progress
+pub struct ActiveRecording {
+    pub window_id: u64,
+    pub segment: Arc<Mutex<OutputSegment>>, // Current ffmpeg process and output file
+    pub stop_signal: Arc<AtomicBool>,
+    pub stats: Arc<Mutex<CaptureStats>>, // Published by the capture loop
+    pub stderr_log: Arc<Mutex<OutputLog>>, // Recent ffmpeg output
+    pub encoder: VideoEncoder, // Encoder actually in use after any fallback
+    output_claim: OutputClaim, // Held until the recording is finished
+    pub started_at: Instant,
+    markers: Arc<Mutex<Vec<Duration>>>, // Chapter markers, relative to `started_at`; shared with size splits
+    emitter: Option<std::thread::JoinHandle<()>>, // Capture loop; joined before ffmpeg is stopped
+    movflags: Option<String>, // Kept when chapters are remuxed in
+    pub bounds: WindowBounds, // Last known geometry of the recorded window
+    pub bounds_stale: bool, // Window minimized or off screen; `bounds` is the last seen value
+    events: EventBus, // Gets `Stopped` once the recording is finished
+    latest_frame: Arc<Mutex<Option<LatestFrame>>>, // Last frame sent to ffmpeg, for the preview
+    verify: bool, // Check the last file with ffprobe after finalizing
+    has_audio: bool,
+}
+
+impl ActiveRecording {
+    /// Stop the emitter and ffmpeg, then embed any chapter markers that fall
+    /// into the last output file and, if enabled, verify it with ffprobe.
+    /// Returns that file's path and emits `Stopped`.
+    pub fn finish(mut self, ffmpeg: Option<&PathBuf>) -> PathBuf {
+        self.stop_signal.store(true, std::sync::atomic::Ordering::Relaxed);
+        // Once the emitter has exited no size split can swap the process out
+        // from under us. It only outlives the timeout while blocked writing to
+        // an ffmpeg that stopped reading, which the wait below kills.
+        if let Some(emitter) = self.emitter.take() {
+            let deadline = Instant::now() + EMITTER_STOP_TIMEOUT;
+            while !emitter.is_finished() && Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            if emitter.is_finished() {
+                let _ = emitter.join();
+            } else {
+                warn!("Capture loop of window {} didn't stop; stopping ffmpeg anyway", self.window_id);
+            }
+        }
+        let mut segment = self.segment.lock();
+        // With time segments, only the last numbered file is still open. It
+        // may roll over while ffmpeg drains, so the list decides afterwards.
+        let (open_file, _) = segment.current_file();
+        let elapsed = segment.started_at.elapsed();
+        let live_audio = segment.live_audio;
+        let finalized = send_quit_and_wait(&mut segment.child, &open_file, live_audio);
+        let (path, file_start) = segment.last_file();
+        let files = segment.files();
+        let total = elapsed.saturating_sub(file_start);
+        if let Some(list) = &segment.segment_list {
+            let _ = std::fs::remove_file(list);
+        }
+
+        // Markers are relative to the recording start; rebase them onto this file
+        let offset = segment.started_at.saturating_duration_since(self.started_at) + file_start;
+        let markers = file_markers(&self.markers.lock(), offset, None);
+
+        if let Some(ffmpeg) = ffmpeg {
+            if let Err(e) = write_chapters(ffmpeg, &path, &markers, total, self.movflags.as_deref()) {
+                error!("Failed to write chapter markers for {}: {}", path.display(), e);
+            }
+        }
+        drop(segment);
+        let finalized = finalized.and_then(|()| match ffmpeg.filter(|_| self.verify) {
+            Some(ffmpeg) => verify_output(ffmpeg, &path, self.has_audio)
+                .with_context(|| format!("{} may be unplayable", path.display())),
+            None => Ok(()),
+        });
+        // The file is final; another recording may use its name now
+        drop(self.output_claim);
+        self.events.emit(RecordingEvent::Stopped {
+            window_id: self.window_id,
+            path: path.clone(),
+            files,
+            stats: self.stats.lock().clone(),
+            error: finalized.err().map(|e| format!("{:#}", e)),
+        });
+        path
     }
 }
 
 /// Manages recording state and processes
 pub struct RecorderState {
-    running: HashMap<u64, (Child, Arc<AtomicBool>)>,
+    running: HashMap<u64, ActiveRecording>,
+    events: EventBus,
+}
+
+impl Default for RecorderState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl RecorderState {
     pub fn new() -> Self {
-        Self { running: HashMap::new() }
+        Self { running: HashMap::new(), events: EventBus::new() }
+    }
+
+    /// Events of recordings started through this state; pass it to `start_ffmpeg_for_window`
+    pub fn events(&self) -> &EventBus {
+        &self.events
     }
 
     pub fn is_recording(&self, window_id: u64) -> bool {
         self.running.contains_key(&window_id)
     }
     
-    pub fn start_recording(&mut self, window_id: u64, child: Child, stop_signal: Arc<AtomicBool>) {
-        self.running.insert(window_id, (child, stop_signal));
+    pub fn start_recording(&mut self, window_id: u64, bounds: WindowBounds, handles: RecordingHandles) {
+        let started_at = handles.segment.lock().started_at;
+        self.running.insert(window_id, ActiveRecording {
+            window_id,
+            segment: handles.segment,
+            stop_signal: handles.stop_signal,
+            stats: handles.stats,
+            stderr_log: handles.stderr_log,
+            encoder: handles.encoder,
+            output_claim: handles.output_claim,
+            started_at,
+            markers: handles.markers,
+            emitter: handles.emitter,
+            movflags: handles.movflags,
+            bounds,
+            bounds_stale: false,
+            events: self.events.clone(),
+            latest_frame: handles.latest_frame,
+            verify: handles.verify,
+            has_audio: handles.has_audio,
+        });
     }
     
-    pub fn stop_recording(&mut self, window_id: u64) -> Option<(Child, Arc<AtomicBool>)> {
+    /// When a recording started, `None` once it's no longer running
+    pub fn started_at(&self, window_id: u64) -> Option<Instant> {
+        Some(self.running.get(&window_id)?.started_at)
+    }
+
+    pub fn stop_recording(&mut self, window_id: u64) -> Option<ActiveRecording> {
         self.running.remove(&window_id)
     }
     
-    pub fn stop_all(&mut self) -> Vec<(Child, Arc<AtomicBool>)> {
+    pub fn stop_all(&mut self) -> Vec<ActiveRecording> {
         self.running.drain().map(|(_, v)| v).collect()
     }
+
+    /// Add a chapter marker at the current position of a recording
+    pub fn add_marker(&mut self, window_id: u64) -> Option<(usize, Duration)> {
+        let recording = self.running.get_mut(&window_id)?;
+        let at = recording.started_at.elapsed();
+        let mut markers = recording.markers.lock();
+        markers.push(at);
+        Some((markers.len(), at))
+    }
+
+    pub fn marker_count(&self, window_id: u64) -> usize {
+        self.running.get(&window_id).map_or(0, |r| r.markers.lock().len())
+    }
+
+    /// Update tracked geometry from a fresh window list. Recorded windows missing
+    /// from it (minimized, hidden) keep their last bounds but are marked stale.
+    pub fn update_bounds(&mut self, windows: &[WindowInfo]) {
+        for (id, recording) in self.running.iter_mut() {
+            match windows.iter().find(|w| w.window_id == *id) {
+                Some(info) => {
+                    recording.bounds = info.bounds();
+                    recording.bounds_stale = false;
+                }
+                None => recording.bounds_stale = true,
+            }
+        }
+    }
+
+    /// Capture rate the recording's capture loop can currently sustain
+    pub fn capture_fps(&self, window_id: u64) -> Option<f64> {
+        self.running.get(&window_id)?.stats.lock().capture_fps
+    }
+
+    /// Whether the recording's capture loop is getting fresh frames
+    pub fn capture_health(&self, window_id: u64) -> Option<CaptureHealth> {
+        Some(self.running.get(&window_id)?.stats.lock().health)
+    }
+
+    /// Bytes written to a recording's current file, to within a second
+    pub fn output_bytes(&self, window_id: u64) -> Option<u64> {
+        self.running.get(&window_id)?.stats.lock().output_bytes
+    }
+
+    /// Whether a recording has run for `after` and its file hasn't grown in
+    /// that long, as when ffmpeg takes frames but writes nothing
+    pub fn output_stalled(&self, window_id: u64, after: Duration) -> bool {
+        self.running.get(&window_id).is_some_and(|r| {
+            r.started_at.elapsed() >= after
+                && r.stats.lock().output_grew_at.is_none_or(|t| t.elapsed() >= after)
+        })
+    }
+
+    /// When a recording last captured its window, to within a second
+    pub fn last_capture(&self, window_id: u64) -> Option<Instant> {
+        self.running.get(&window_id)?.stats.lock().last_capture
+    }
+
+    /// Where a recording publishes the frame it last sent to ffmpeg, so a
+    /// preview can show it without capturing the window again
+    pub fn latest_frame(&self, window_id: u64) -> Option<Arc<Mutex<Option<LatestFrame>>>> {
+        Some(self.running.get(&window_id)?.latest_frame.clone())
+    }
+
+    /// Encoder a recording ended up with after the fallback cascade
+    pub fn encoder(&self, window_id: u64) -> Option<VideoEncoder> {
+        Some(self.running.get(&window_id)?.encoder)
+    }
+
+    /// Recent ffmpeg output of a recording, oldest first
+    pub fn ffmpeg_output(&self, window_id: u64) -> Vec<String> {
+        self.running
+            .get(&window_id)
+            .map(|r| r.stderr_log.lock().lines().map(str::to_string).collect())
+            .unwrap_or_default()
+    }
+
+    /// Current bounds of a recorded window, `None` if not recording or stale
+    pub fn live_bounds(&self, window_id: u64) -> Option<WindowBounds> {
+        self.running
+            .get(&window_id)
+            .filter(|r| !r.bounds_stale)
+            .map(|r| r.bounds)
+    }
+
+    pub fn recording_ids(&self) -> Vec<u64> {
+        self.running.keys().copied().collect()
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_saved_bitrate_becomes_the_rate_control() {
+        let mut config: RecordingConfig = serde_json::from_str(r#"{ "bitrate_kbps": 9000 }"#).unwrap();
+        config.migrate_saved_fields();
+        assert_eq!(config.rate_control, RateControl::Bitrate(9000));
+
+        let saved = serde_json::to_value(&config).unwrap();
+        assert!(saved.get("bitrate_kbps").is_none());
+        assert_eq!(saved["rate_control"], serde_json::json!({ "Bitrate": 9000 }));
+    }
+
+    #[test]
+    fn a_saved_rate_control_is_kept() {
+        let mut config: RecordingConfig =
+            serde_json::from_str(r#"{ "rate_control": { "ConstantQuality": 20 } }"#).unwrap();
+        config.migrate_saved_fields();
+        assert_eq!(config.rate_control, RateControl::ConstantQuality(20));
+        assert_eq!(config.bitrate_kbps(), RateControl::DEFAULT_BITRATE_KBPS);
+    }
+}
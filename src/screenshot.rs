@@ -0,0 +1,144 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+use crate::ffmpeg::{build_output_path, stream_size};
+use crate::recorder::RecordingConfig;
+use crate::window::WindowInfo;
+
+#[cfg(target_os = "macos")]
+use crate::macos;
+
+/// Capture a window and save it as a PNG in `output_dir` (or the current
+/// directory), under `folder_template` if set. Returns the file's path.
+pub fn save_window_screenshot(
+    info: &WindowInfo,
+    output_dir: Option<&PathBuf>,
+    folder_template: Option<&str>,
+) -> Result<PathBuf> {
+    #[cfg(target_os = "macos")]
+    {
+        let (buffer, width, height) = macos::capture_window_image(info.window_id)
+            .with_context(|| format!("could not capture window {}", info.window_id))?;
+        let name = format!("screenshot_{}", info.owner_name);
+        let path = build_output_path(info, output_dir, folder_template, Some(&name), "png")?;
+        save_png(&path, &buffer, width, height)?;
+        Ok(path)
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = (info, output_dir, folder_template);
+        Err(anyhow::anyhow!("Screenshots are only supported on macOS"))
+    }
+}
+
+/// Capture a window for a bug report: the raw buffer as the recorder gets it
+/// (`.rgba`, premultiplied, no header), a PNG of it, and a `.txt` with the
+/// sizes the pipeline derives from it under `config`. Returns the PNG's path;
+/// the other files sit next to it.
+pub fn export_debug_frame(info: &WindowInfo, config: &RecordingConfig, output_dir: Option<&PathBuf>) -> Result<PathBuf> {
+    #[cfg(target_os = "macos")]
+    {
+        let (buffer, width, height) = macos::capture_window_image(info.window_id)
+            .with_context(|| format!("could not capture window {}", info.window_id))?;
+        let name = format!("debug_frame_{}", info.owner_name);
+        let path = build_output_path(info, output_dir, None, Some(&name), "png")?;
+        save_png(&path, &buffer, width, height)?;
+        let raw_path = path.with_extension("rgba");
+        std::fs::write(&raw_path, &buffer)
+            .with_context(|| format!("failed to write {}", raw_path.display()))?;
+
+        let scales: Vec<String> = macos::display_scales(info).iter().map(|s| format!("{}x", s)).collect();
+        let (stream_w, stream_h) = stream_size(config, info, width, height);
+        let (rotated_w, rotated_h) = config.rotation.apply(stream_w, stream_h);
+        let (output_w, output_h) = config.output_size_for(rotated_w, rotated_h).unwrap_or((rotated_w, rotated_h));
+        let report = [
+            format!("window: {} (id {}, bundle {})", info.display_name(), info.window_id, info.bundle_id.as_deref().unwrap_or("none")),
+            format!("window bounds: {}x{} points at {},{}", info.width, info.height, info.x, info.y),
+            format!("display scale: {}", if scales.is_empty() { "unknown".to_string() } else { scales.join(", ") }),
+            format!("captured: {}x{} px, {} bytes ({} per row), premultiplied RGBA", width, height, buffer.len(), width * 4),
+            format!("crop: {:?}", config.crop),
+            format!("canvas: {:?}", config.canvas),
+            format!("odd sizes: {}", config.even_size.label()),
+            format!("expected_w x expected_h (piped to ffmpeg): {}x{}", stream_w, stream_h),
+            format!("rotation: {}", config.rotation.label()),
+            format!("encoded size: {}x{}", output_w, output_h),
+            format!("encoder: {} at {} fps", config.encoder.label(), config.fps),
+        ];
+        let report_path = path.with_extension("txt");
+        std::fs::write(&report_path, report.join("\n") + "\n")
+            .with_context(|| format!("failed to write {}", report_path.display()))?;
+        Ok(path)
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = (info, config, output_dir);
+        Err(anyhow::anyhow!("Frame capture is only supported on macOS"))
+    }
+}
+
+/// Convert premultiplied RGBA, as captured, to straight alpha in place.
+/// Channels are rounded to nearest; fully transparent pixels keep their
+/// (zero) color.
+pub fn unpremultiply(rgba: &mut [u8]) {
+    for pixel in rgba.chunks_exact_mut(4) {
+        let alpha = pixel[3] as u32;
+        if alpha != 0 && alpha != 255 {
+            for channel in &mut pixel[..3] {
+                *channel = ((*channel as u32 * 255 + alpha / 2) / alpha).min(255) as u8;
+            }
+        }
+    }
+}
+
+/// Write premultiplied RGBA, as captured, to a PNG (which stores straight alpha)
+pub fn save_png(path: &Path, rgba: &[u8], width: usize, height: usize) -> Result<()> {
+    let mut straight = rgba.to_vec();
+    unpremultiply(&mut straight);
+    image::save_buffer(path, &straight, width as u32, height as u32, image::ColorType::Rgba8)
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unpremultiply_leaves_opaque_and_transparent_pixels() {
+        let mut rgba = [10, 20, 30, 255, 0, 0, 0, 0];
+        unpremultiply(&mut rgba);
+        assert_eq!(rgba, [10, 20, 30, 255, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn unpremultiply_divides_by_alpha_and_rounds() {
+        // 50% white over nothing, and a quarter-transparent orange
+        let mut rgba = [128, 128, 128, 128, 150, 75, 0, 191];
+        unpremultiply(&mut rgba);
+        assert_eq!(rgba, [255, 255, 255, 128, 200, 100, 0, 191]);
+    }
+
+    #[test]
+    fn unpremultiply_clamps_channels_brighter_than_alpha() {
+        let mut rgba = [200, 0, 0, 100];
+        unpremultiply(&mut rgba);
+        assert_eq!(rgba, [255, 0, 0, 100]);
+    }
+
+    #[test]
+    fn unpremultiply_undoes_premultiplying() {
+        for alpha in 1..=254u32 {
+            // Premultiplied channels keep only this much of the color
+            let tolerance = 255 / (2 * alpha) + 1;
+            for color in 0..=255u32 {
+                let premultiplied = ((color * alpha + 127) / 255) as u8;
+                let mut rgba = [premultiplied, premultiplied, premultiplied, alpha as u8];
+                unpremultiply(&mut rgba);
+                let restored = rgba[0] as u32;
+                assert!(
+                    restored.abs_diff(color) <= tolerance,
+                    "color {} at alpha {} came back as {}", color, alpha, restored
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,343 @@
+use anyhow::{anyhow, Context, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+use crate::control::ControlSettings;
+use crate::ffmpeg::RateControl;
+use crate::recorder::RecordingConfig;
+
+/// A named snapshot of the recording settings
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RecordingProfile {
+    pub name: String,
+    pub config: RecordingConfig,
+}
+
+/// Settings exported to share with another machine: the recording config and
+/// per-window overrides keyed by `WindowInfo::stable_key`
+#[derive(Serialize)]
+pub struct SharedSettings<W> {
+    pub config: RecordingConfig,
+    pub windows: BTreeMap<String, W>,
+}
+
+/// Settings directory: ~/Library/Application Support/screencast
+fn settings_dir() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME")?;
+    Some(
+        PathBuf::from(home)
+            .join("Library")
+            .join("Application Support")
+            .join("screencast"),
+    )
+}
+
+/// Settings file: ~/Library/Application Support/screencast/config.json
+pub fn settings_path() -> Option<PathBuf> {
+    Some(settings_dir()?.join("config.json"))
+}
+
+/// Saved profiles live next to the settings file in profiles.json
+pub fn profiles_path() -> Option<PathBuf> {
+    Some(settings_dir()?.join("profiles.json"))
+}
+
+/// Manual window list order lives in window_order.json
+pub fn window_order_path() -> Option<PathBuf> {
+    Some(settings_dir()?.join("window_order.json"))
+}
+
+/// Recently used output folders live in recent_dirs.json
+pub fn recent_dirs_path() -> Option<PathBuf> {
+    Some(settings_dir()?.join("recent_dirs.json"))
+}
+
+/// Control server settings live in control.json, apart from the recording
+/// config so profiles and exports never include the token
+pub fn control_settings_path() -> Option<PathBuf> {
+    Some(settings_dir()?.join("control.json"))
+}
+
+/// Fields older versions kept in config.json and profiles, before
+/// `ControlSettings` moved to its own file
+const LEGACY_CONTROL_FIELDS: [&str; 3] = ["control_server", "control_port", "control_token"];
+
+/// Load the saved recording config; missing fields fall back to defaults
+pub fn load_config() -> Option<RecordingConfig> {
+    let path = settings_path()?;
+    let data = std::fs::read_to_string(&path).ok()?;
+    match serde_json::from_str::<RecordingConfig>(&data) {
+        Ok(mut config) => {
+            config.migrate_saved_fields();
+            Some(config)
+        }
+        Err(e) => {
+            warn!("Ignoring unreadable settings file {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+pub fn save_config(config: &RecordingConfig) -> Result<()> {
+    let path = settings_path().context("HOME is not set")?;
+    write_json(&path, config)
+        .with_context(|| format!("failed to write settings: {}", path.display()))
+}
+
+pub fn load_profiles() -> Vec<RecordingProfile> {
+    let Some(path) = profiles_path() else {
+        return Vec::new();
+    };
+    let Ok(data) = std::fs::read_to_string(&path) else {
+        return Vec::new();
+    };
+    let mut profiles: Vec<RecordingProfile> = serde_json::from_str(&data).unwrap_or_else(|e| {
+        warn!("Ignoring unreadable profiles file {}: {}", path.display(), e);
+        Vec::new()
+    });
+    for profile in &mut profiles {
+        profile.config.migrate_saved_fields();
+    }
+    // Rewrite profiles saved with a control token so it leaves the file
+    let has_token = serde_json::from_str::<Vec<Value>>(&data).is_ok_and(|saved| {
+        saved.iter().any(|profile| profile["config"].get("control_token").is_some())
+    });
+    if has_token && !profiles.is_empty() {
+        if let Err(e) = save_profiles(&profiles) {
+            warn!("{:#}", e);
+        }
+    }
+    profiles
+}
+
+pub fn save_profiles(profiles: &[RecordingProfile]) -> Result<()> {
+    let path = profiles_path().context("HOME is not set")?;
+    write_json(&path, &profiles)
+        .with_context(|| format!("failed to write profiles: {}", path.display()))
+}
+
+/// Saved window order as `WindowInfo::stable_key`s, first to last
+pub fn load_window_order() -> Vec<String> {
+    let Some(path) = window_order_path() else {
+        return Vec::new();
+    };
+    let Ok(data) = std::fs::read_to_string(&path) else {
+        return Vec::new();
+    };
+    serde_json::from_str(&data).unwrap_or_else(|e| {
+        warn!("Ignoring unreadable window order file {}: {}", path.display(), e);
+        Vec::new()
+    })
+}
+
+pub fn save_window_order(order: &[String]) -> Result<()> {
+    let path = window_order_path().context("HOME is not set")?;
+    write_json(&path, &order)
+        .with_context(|| format!("failed to write window order: {}", path.display()))
+}
+
+/// Recently used output folders, newest first
+pub fn load_recent_dirs() -> Vec<PathBuf> {
+    let Some(path) = recent_dirs_path() else {
+        return Vec::new();
+    };
+    let Ok(data) = std::fs::read_to_string(&path) else {
+        return Vec::new();
+    };
+    serde_json::from_str(&data).unwrap_or_else(|e| {
+        warn!("Ignoring unreadable recent folders file {}: {}", path.display(), e);
+        Vec::new()
+    })
+}
+
+pub fn save_recent_dirs(dirs: &[PathBuf]) -> Result<()> {
+    let path = recent_dirs_path().context("HOME is not set")?;
+    write_json(&path, &dirs)
+        .with_context(|| format!("failed to write recent folders: {}", path.display()))
+}
+
+/// Load the control server settings. Before they had their own file they were
+/// part of config.json, so they are taken from there the first time.
+pub fn load_control_settings() -> ControlSettings {
+    let Some(path) = control_settings_path() else {
+        return ControlSettings::default();
+    };
+    if let Ok(data) = std::fs::read_to_string(&path) {
+        return serde_json::from_str(&data).unwrap_or_else(|e| {
+            warn!("Ignoring unreadable control settings {}: {}", path.display(), e);
+            ControlSettings::default()
+        });
+    }
+    let legacy = settings_path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|data| serde_json::from_str::<Map<String, Value>>(&data).ok());
+    let Some(legacy) = legacy else {
+        return ControlSettings::default();
+    };
+    let fields: Map<String, Value> = LEGACY_CONTROL_FIELDS
+        .iter()
+        .zip(["enabled", "port", "token"])
+        .filter_map(|(old, new)| Some((new.to_string(), legacy.get(*old)?.clone())))
+        .collect();
+    if fields.is_empty() {
+        return ControlSettings::default();
+    }
+    let control = serde_json::from_value(Value::Object(fields)).unwrap_or_default();
+    // config.json drops the old fields the next time it's saved
+    if let Err(e) = save_control_settings(&control) {
+        warn!("{:#}", e);
+    }
+    control
+}
+
+pub fn save_control_settings(control: &ControlSettings) -> Result<()> {
+    let path = control_settings_path().context("HOME is not set")?;
+    write_json(&path, control)
+        .with_context(|| format!("failed to write control settings: {}", path.display()))
+}
+
+pub fn export_settings<W: Serialize>(path: &Path, settings: &SharedSettings<W>) -> Result<()> {
+    write_json(path, settings)
+        .with_context(|| format!("failed to export settings: {}", path.display()))
+}
+
+/// Read settings exported by `export_settings`, possibly from another version.
+/// Fields that don't parse, such as an encoder this version doesn't know, take
+/// their defaults like missing ones do; their names come back alongside.
+pub fn import_settings<W: DeserializeOwned>(path: &Path) -> Result<(SharedSettings<W>, Vec<String>)> {
+    let data = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    let Value::Object(mut file) = serde_json::from_str(&data)
+        .with_context(|| format!("{} is not JSON", path.display()))?
+    else {
+        return Err(anyhow!("{} doesn't contain exported settings", path.display()));
+    };
+    let mut dropped = Vec::new();
+    let config = file.remove("config")
+        .ok_or_else(|| anyhow!("{} has no recording settings", path.display()))?;
+    let mut config: RecordingConfig = from_value_lenient(config, "config", &mut dropped)?;
+    clamp_to_ui_ranges(&mut config);
+    // Exports from before device ids were names carry CPAL indices
+    config.migrate_saved_fields();
+
+    let mut windows = BTreeMap::new();
+    if let Some(Value::Object(entries)) = file.remove("windows") {
+        for (key, value) in entries {
+            let name = format!("windows[{}]", key.replace('\u{1f}', " — "));
+            match from_value_lenient(value, &name, &mut dropped) {
+                Ok(settings) => {
+                    windows.insert(key, settings);
+                }
+                Err(e) => warn!("Skipping {}: {:#}", name, e),
+            }
+        }
+    }
+    Ok((SharedSettings { config, windows }, dropped))
+}
+
+/// Bring imported numbers into the ranges the settings tab allows, so a
+/// hand-edited file can't start a recording with e.g. a 0x0 canvas
+fn clamp_to_ui_ranges(config: &mut RecordingConfig) {
+    config.fps = config.fps.clamp(1, 120);
+    let bitrates = RateControl::BITRATE_RANGE_KBPS;
+    config.rate_control = match config.rate_control {
+        RateControl::Bitrate(kbps) => RateControl::Bitrate(kbps.clamp(*bitrates.start(), *bitrates.end())),
+        RateControl::ConstantQuality(crf) => RateControl::ConstantQuality(crf.min(51)),
+    };
+    config.capture_fps = config.capture_fps.map(|rate| rate.clamp(1, 120));
+    config.audio_bitrate_kbps = config.audio_bitrate_kbps.clamp(32, 512);
+    if let Some(canvas) = &mut config.canvas {
+        canvas.width = canvas.width.clamp(16, 7680);
+        canvas.height = canvas.height.clamp(16, 7680);
+    }
+    config.max_capture_dimension = config.max_capture_dimension.map(|max| max.clamp(320, 8192));
+    config.segment_seconds = config.segment_seconds.map(|seconds| seconds.clamp(10, 86_400));
+    config.split_size_mb = config.split_size_mb.map(|mb| mb.clamp(10, 100_000));
+}
+
+/// Deserialize a JSON object one field at a time, leaving out fields that
+/// fail so `T`'s serde defaults stand in. Left-out fields are added to
+/// `dropped` as `name.field`.
+fn from_value_lenient<T: DeserializeOwned>(value: Value, name: &str, dropped: &mut Vec<String>) -> Result<T> {
+    let Value::Object(fields) = value else {
+        return Err(anyhow!("{} is not an object", name));
+    };
+    let mut kept = Map::new();
+    for (key, field) in fields {
+        kept.insert(key.clone(), field);
+        if serde_json::from_value::<T>(Value::Object(kept.clone())).is_err() {
+            kept.remove(&key);
+            dropped.push(format!("{}.{}", name, key));
+        }
+    }
+    serde_json::from_value(Value::Object(kept)).with_context(|| format!("{} is incomplete", name))
+}
+
+fn write_json(path: &Path, value: &impl Serialize) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create settings directory: {}", dir.display()))?;
+    }
+    std::fs::write(path, serde_json::to_string_pretty(value)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn import(name: &str, config: Value) -> RecordingConfig {
+        let dir = std::env::temp_dir().join(format!("screencast-import-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(format!("{}.json", name));
+        std::fs::write(&path, json!({ "config": config, "windows": {} }).to_string()).unwrap();
+        let (settings, dropped) = import_settings::<Value>(&path).unwrap();
+        assert!(dropped.is_empty(), "{:?}", dropped);
+        settings.config
+    }
+
+    #[test]
+    fn imports_are_clamped_to_what_the_settings_allow() {
+        let config = import("out-of-range", json!({
+            "fps": 0,
+            "rate_control": { "Bitrate": 10 },
+            "capture_fps": -5,
+            "audio_bitrate_kbps": 100_000,
+            "canvas": { "width": 0, "height": 100_000, "background": [0, 0, 0] },
+            "max_capture_dimension": 1,
+            "segment_seconds": 0,
+            "split_size_mb": 0,
+        }));
+        assert_eq!(config.fps, 1);
+        assert_eq!(config.rate_control, RateControl::Bitrate(500));
+        assert_eq!(config.capture_fps, Some(1));
+        assert_eq!(config.audio_bitrate_kbps, 512);
+        let canvas = config.canvas.unwrap();
+        assert_eq!((canvas.width, canvas.height), (16, 7680));
+        assert_eq!(config.max_capture_dimension, Some(320));
+        assert_eq!(config.segment_seconds, Some(10));
+        assert_eq!(config.split_size_mb, Some(10));
+    }
+
+    #[test]
+    fn imports_in_range_are_kept() {
+        let config = import("in-range", json!({
+            "fps": 30,
+            "capture_fps": 5,
+            "canvas": { "width": 1920, "height": 1080, "background": [0, 0, 0] },
+            "max_capture_dimension": 4096,
+            "segment_seconds": 600,
+        }));
+        assert_eq!(config.fps, 30);
+        assert_eq!(config.capture_fps, Some(5));
+        let canvas = config.canvas.unwrap();
+        assert_eq!((canvas.width, canvas.height), (1920, 1080));
+        assert_eq!(config.max_capture_dimension, Some(4096));
+        assert_eq!(config.segment_seconds, Some(600));
+    }
+}
@@ -15,20 +15,129 @@ pub struct WindowInfo {
     pub y: i32,
     pub width: i32,
     pub height: i32,
+    pub untitled_index: Option<usize>, // 1-based, when the owner has several untitled windows
+    pub bundle_id: Option<String>, // Owner's bundle identifier, e.g. com.apple.Safari; None for unbundled processes
+}
+
+/// On-screen geometry of a window, in global display coordinates (top-left origin)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WindowBounds {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
 }
 
 impl WindowInfo {
+    pub fn bounds(&self) -> WindowBounds {
+        WindowBounds {
+            x: self.x,
+            y: self.y,
+            width: self.width,
+            height: self.height,
+        }
+    }
+
     pub fn display_name(&self) -> String {
-        format!(
-            "{} — {}",
-            self.owner_name,
-            if self.window_title.is_empty() { "(untitled)" } else { &self.window_title }
-        )
+        match self.untitled_index {
+            _ if !self.window_title.is_empty() => format!("{} — {}", self.owner_name, self.window_title),
+            Some(index) => format!("{} — (untitled) #{}", self.owner_name, index),
+            None => format!("{} — (untitled)", self.owner_name),
+        }
     }
     
     pub fn dimensions_str(&self) -> String {
         format!("{}x{}", self.width, self.height)
     }
+
+    /// Identifies the window across launches, unlike `window_id`
+    pub fn stable_key(&self) -> String {
+        format!("{}\u{1f}{}", self.owner_name, self.window_title)
+    }
+}
+
+/// Number untitled windows that share an owner, in window id order so the
+/// numbers stay put while windows are raised and lowered
+fn number_untitled_windows(windows: &mut [WindowInfo]) {
+    let mut untitled: Vec<&mut WindowInfo> = windows.iter_mut()
+        .filter(|w| w.window_title.is_empty())
+        .collect();
+    untitled.sort_by(|a, b| a.owner_name.cmp(&b.owner_name).then(a.window_id.cmp(&b.window_id)));
+    for group in untitled.chunk_by_mut(|a, b| a.owner_name == b.owner_name) {
+        let shared = group.len() > 1;
+        for (i, window) in group.iter_mut().enumerate() {
+            window.untitled_index = shared.then_some(i + 1);
+        }
+    }
+}
+
+/// Sort windows by a saved list of `stable_key`s. Windows not in the list go
+/// last; ties (unsaved or same key) are ordered by window id.
+pub fn sort_by_saved_order(windows: &mut [WindowInfo], order: &[String]) {
+    windows.sort_by_cached_key(|w| {
+        let position = order.iter().position(|key| *key == w.stable_key());
+        (position.unwrap_or(usize::MAX), w.window_id)
+    });
+}
+
+/// Keyboard focus in a window list after ↑/↓, kept as a window id so it stays
+/// on its window when the list is re-sorted or refreshed. Focus on a window
+/// that left the list is dropped until a key press focuses the first row.
+pub fn move_focus(windows: &[WindowInfo], focused: Option<u64>, up: bool, down: bool) -> Option<u64> {
+    let last = windows.len().checked_sub(1)?;
+    let focused = focused.and_then(|id| windows.iter().position(|w| w.window_id == id));
+    let index = match focused {
+        None if up || down => Some(0),
+        Some(i) if up => Some(i.saturating_sub(1)),
+        Some(i) if down => Some((i + 1).min(last)),
+        other => other,
+    };
+    index.map(|i| windows[i].window_id)
+}
+
+/// Score how well `query` matches `text` as a case-insensitive subsequence,
+/// or None if it doesn't. Runs of consecutive characters and matches at the
+/// start of a word score higher, so "chr" ranks "Chrome" above "Archive".
+fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+    let text: Vec<char> = text.chars().flat_map(char::to_lowercase).collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous: Option<usize> = None;
+    for q in query.chars().flat_map(char::to_lowercase).filter(|c| !c.is_whitespace()) {
+        let found = position + text[position..].iter().position(|&c| c == q)?;
+        score += 1;
+        if previous == Some(found.wrapping_sub(1)) {
+            score += 5;
+        }
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        previous = Some(found);
+        position = found + 1;
+    }
+    // Among equal matches, prefer shorter names
+    Some(score * 100 - text.len().min(99) as i32)
+}
+
+/// Windows whose display name fuzzy-matches `query`, best match first. An
+/// empty query keeps every window, in the given order.
+pub fn fuzzy_filter<'a>(windows: &'a [WindowInfo], query: &str) -> Vec<&'a WindowInfo> {
+    let mut scored: Vec<(i32, &WindowInfo)> = windows.iter()
+        .filter_map(|w| Some((fuzzy_score(query, &w.display_name())?, w)))
+        .collect();
+    if !query.trim().is_empty() {
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    }
+    scored.into_iter().map(|(_, w)| w).collect()
+}
+
+/// Windows whose display name contains `pattern`, ignoring case. Stricter
+/// than `fuzzy_filter`, so a script's pattern can't pick up stray windows.
+pub fn title_matches<'a>(windows: &'a [WindowInfo], pattern: &str) -> Vec<&'a WindowInfo> {
+    let pattern = pattern.trim().to_lowercase();
+    windows.iter()
+        .filter(|w| w.display_name().to_lowercase().contains(&pattern))
+        .collect()
 }
 
 /// Manages window enumeration
@@ -37,6 +146,12 @@ pub struct WindowManager {
     last_refresh: Instant,
 }
 
+impl Default for WindowManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl WindowManager {
     pub fn new() -> Self {
         Self {
@@ -49,6 +164,7 @@ impl WindowManager {
         #[cfg(target_os = "macos")]
         {
             self.windows = macos::list_windows()?;
+            number_untitled_windows(&mut self.windows);
             self.last_refresh = Instant::now();
         }
         
@@ -73,3 +189,70 @@ impl WindowManager {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(window_id: u64, title: &str) -> WindowInfo {
+        WindowInfo {
+            window_id,
+            owner_name: "Safari".to_string(),
+            window_title: title.to_string(),
+            x: 0,
+            y: 0,
+            width: 800,
+            height: 600,
+            untitled_index: None,
+            bundle_id: None,
+        }
+    }
+
+    fn ids(windows: &[WindowInfo]) -> Vec<u64> {
+        windows.iter().map(|w| w.window_id).collect()
+    }
+
+    #[test]
+    fn focus_stays_on_its_window_when_the_list_reorders() {
+        let mut windows = vec![window(10, "A"), window(20, "B"), window(30, "C")];
+        let focused = move_focus(&windows, None, false, true);
+        assert_eq!(focused, Some(10));
+        let focused = move_focus(&windows, focused, false, true);
+        assert_eq!(focused, Some(20));
+
+        // Re-sorted between frames: no key press keeps the same window
+        windows.reverse();
+        assert_eq!(move_focus(&windows, focused, false, false), Some(20));
+        // and the next press moves from where that window is now
+        assert_eq!(move_focus(&windows, focused, false, true), Some(10));
+        assert_eq!(move_focus(&windows, focused, true, false), Some(30));
+    }
+
+    #[test]
+    fn focus_is_dropped_with_its_window() {
+        let windows = vec![window(10, "A"), window(30, "C")];
+        assert_eq!(move_focus(&windows, Some(20), false, false), None);
+        assert_eq!(move_focus(&windows, Some(20), false, true), Some(10));
+        assert_eq!(move_focus(&[], Some(10), false, true), None);
+    }
+
+    #[test]
+    fn focus_stops_at_the_ends() {
+        let windows = vec![window(10, "A"), window(20, "B")];
+        assert_eq!(move_focus(&windows, Some(10), true, false), Some(10));
+        assert_eq!(move_focus(&windows, Some(20), false, true), Some(20));
+    }
+
+    #[test]
+    fn saved_order_follows_windows_not_positions() {
+        let mut windows = vec![window(3, "Docs"), window(1, "Mail"), window(2, "News")];
+        let order = vec![window(2, "News").stable_key(), window(3, "Docs").stable_key()];
+        sort_by_saved_order(&mut windows, &order);
+        assert_eq!(ids(&windows), [2, 3, 1]);
+
+        // A refresh brings a new window and new ids; saved ones keep their places
+        let mut refreshed = vec![window(7, "Mail"), window(9, "Docs"), window(8, "News"), window(4, "New")];
+        sort_by_saved_order(&mut refreshed, &order);
+        assert_eq!(ids(&refreshed), [8, 9, 4, 7]);
+    }
+}
@@ -0,0 +1,144 @@
+//! The control server protocol, driven over a real localhost connection
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use screencast::control::{generate_token, Command, ControlServer, MAX_CONNECTIONS, MAX_LINE_LEN};
+use serde_json::{json, Value};
+
+const TOKEN: &str = "0123456789abcdef0123456789abcdef";
+
+/// A server on a free port, owned by a thread standing in for the app: it
+/// answers `status` with a fixed result and `start`/`stop` with the id, until
+/// the returned flag is set
+fn serve() -> (u16, Arc<AtomicBool>) {
+    let server = ControlServer::start(0, TOKEN, || {}).unwrap();
+    let port = server.port();
+    let done = Arc::new(AtomicBool::new(false));
+    let stop = done.clone();
+    thread::spawn(move || {
+        while !stop.load(Ordering::Relaxed) {
+            for request in server.requests() {
+                let result = match request.command {
+                    Command::Status => json!({ "recording": [] }),
+                    Command::ListWindows => json!([]),
+                    Command::Start { window_id } | Command::Stop { window_id } => json!(window_id),
+                };
+                request.respond(Ok(result));
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    });
+    (port, done)
+}
+
+struct Client {
+    writer: TcpStream,
+    reader: BufReader<TcpStream>,
+}
+
+impl Client {
+    fn connect(port: u16) -> Self {
+        let writer = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        writer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let reader = BufReader::new(writer.try_clone().unwrap());
+        Self { writer, reader }
+    }
+
+    fn send(&mut self, line: &str) -> Value {
+        writeln!(self.writer, "{}", line).unwrap();
+        self.receive()
+    }
+
+    fn request(&mut self, request: Value) -> Value {
+        self.send(&request.to_string())
+    }
+
+    fn receive(&mut self) -> Value {
+        let mut line = String::new();
+        self.reader.read_line(&mut line).unwrap();
+        serde_json::from_str(&line).unwrap()
+    }
+}
+
+#[test]
+fn answers_commands_sent_with_the_token() {
+    let (port, done) = serve();
+    let mut client = Client::connect(port);
+
+    let status = client.request(json!({ "token": TOKEN, "command": "status" }));
+    assert_eq!(status, json!({ "ok": true, "result": { "recording": [] } }));
+
+    // Several requests share one connection
+    let start = client.request(json!({ "token": TOKEN, "command": "start", "window_id": 42 }));
+    assert_eq!(start, json!({ "ok": true, "result": 42 }));
+    done.store(true, Ordering::Relaxed);
+}
+
+#[test]
+fn rejects_a_wrong_token_and_malformed_requests() {
+    let (port, done) = serve();
+    let mut client = Client::connect(port);
+
+    let wrong = client.request(json!({ "token": "nope", "command": "status" }));
+    assert_eq!(wrong, json!({ "ok": false, "error": "invalid token" }));
+
+    let unknown = client.request(json!({ "token": TOKEN, "command": "reboot" }));
+    assert_eq!(unknown["ok"], json!(false));
+    assert!(unknown["error"].as_str().unwrap().starts_with("invalid request"));
+
+    let garbage = client.send("not json");
+    assert_eq!(garbage["ok"], json!(false));
+    done.store(true, Ordering::Relaxed);
+}
+
+#[test]
+fn closes_connections_sending_overlong_lines() {
+    let (port, done) = serve();
+    let mut client = Client::connect(port);
+
+    // A line of exactly the limit, newline included, is still read
+    let fits = client.send(&"x".repeat(MAX_LINE_LEN - 1));
+    assert!(fits["error"].as_str().unwrap().starts_with("invalid request"));
+
+    let reply = client.send(&"x".repeat(MAX_LINE_LEN));
+    assert_eq!(reply["ok"], json!(false));
+    assert!(reply["error"].as_str().unwrap().starts_with("request longer than"));
+    let mut rest = String::new();
+    assert_eq!(client.reader.read_line(&mut rest).unwrap(), 0, "connection should be closed");
+    done.store(true, Ordering::Relaxed);
+}
+
+#[test]
+fn turns_away_connections_over_the_limit() {
+    let (port, done) = serve();
+    let mut clients: Vec<_> = (0..MAX_CONNECTIONS).map(|_| Client::connect(port)).collect();
+    // Every allowed connection is being served
+    for client in &mut clients {
+        let status = client.request(json!({ "token": TOKEN, "command": "status" }));
+        assert_eq!(status["ok"], json!(true));
+    }
+
+    let mut extra = Client::connect(port);
+    assert_eq!(extra.receive(), json!({ "ok": false, "error": "too many connections" }));
+
+    // Hanging up frees a slot
+    drop(clients.pop());
+    thread::sleep(Duration::from_millis(300));
+    let mut next = Client::connect(port);
+    let status = next.request(json!({ "token": TOKEN, "command": "status" }));
+    assert_eq!(status["ok"], json!(true));
+    done.store(true, Ordering::Relaxed);
+}
+
+#[test]
+fn tokens_are_random_hex() {
+    let (a, b) = (generate_token().unwrap(), generate_token().unwrap());
+    assert_eq!(a.len(), 32);
+    assert!(a.chars().all(|c| c.is_ascii_hexdigit()));
+    assert_ne!(a, b);
+}